@@ -0,0 +1,185 @@
+//! Quantifies the overhead that allocation tracing adds to every allocation.
+//!
+//! The tracing allocator only traces allocations once allocation tracking is enabled, which is not safe to do in a
+//! process that already allocated, so this measures each part of the hot path on its own: the tracer that records the
+//! (de)allocations of a group, the tracing layer that enters and exits groups along with spans, and the timing that
+//! the `allocation-latency` feature adds around the wrapped allocator, which the tracer records into the latency
+//! histogram of the group.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    hint::black_box,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Barrier,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+use vector::internal_telemetry::allocations::{
+    acquire_allocation_group_id, AllocationGroupId, AllocationGroupSnapshot, AllocationLayer,
+    MainTracer, Tracer,
+};
+
+/// The number of threads tracing allocations at once in the contention benchmarks.
+const N_THREADS: usize = 4;
+
+/// The size class of the allocations traced when the size doesn't matter.
+const OBJECT_SIZE: usize = 64;
+
+fn register_group() -> AllocationGroupId {
+    let group_id = acquire_allocation_group_id(
+        "benchmark".to_string(),
+        "benchmark".to_string(),
+        "benchmark".to_string(),
+    );
+    assert_ne!(
+        group_id,
+        AllocationGroupId::OVERFLOW,
+        "benchmarks should not run out of allocation groups"
+    );
+    group_id
+}
+
+fn allocate(layout: Layout) {
+    // SAFETY: The layout has a non-zero size, and the pointer is deallocated with the layout it was allocated with.
+    unsafe {
+        let ptr = System.alloc(layout);
+        System.dealloc(black_box(ptr), layout);
+    }
+}
+
+fn allocate_timed(layout: Layout, group_id: AllocationGroupId) {
+    let started_at = Instant::now();
+    // SAFETY: The layout has a non-zero size, and the pointer is deallocated with the layout it was allocated with.
+    unsafe {
+        let ptr = System.alloc(layout);
+        MainTracer.trace_allocation_latency(started_at.elapsed(), group_id);
+        System.dealloc(black_box(ptr), layout);
+    }
+}
+
+/// Traces `iters` allocations on each of `N_THREADS` threads at once, into the groups returned for each thread.
+///
+/// Only the time the threads spend tracing is measured, not the time spent spawning them.
+fn trace_concurrently(
+    iters: u64,
+    group_for_thread: impl Fn(usize) -> AllocationGroupId,
+) -> Duration {
+    let barrier = Arc::new(Barrier::new(N_THREADS + 1));
+    let threads = (0..N_THREADS)
+        .map(|thread_idx| {
+            let barrier = Arc::clone(&barrier);
+            let group_id = group_for_thread(thread_idx);
+            thread::spawn(move || {
+                barrier.wait();
+                for _ in 0..iters {
+                    MainTracer.trace_allocation(black_box(OBJECT_SIZE), group_id);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    barrier.wait();
+    let started_at = Instant::now();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    started_at.elapsed()
+}
+
+fn benchmark_tracer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("allocation_tracing/tracer");
+    group.throughput(Throughput::Elements(1));
+    let group_id = register_group();
+    group.bench_function("trace_allocation", |b| {
+        b.iter(|| MainTracer.trace_allocation(black_box(OBJECT_SIZE), group_id));
+    });
+    // Snapshots read the statistics of every thread under the generation each thread writes them with, which the
+    // allocation path must not pay for beyond the stores of the generation.
+    group.bench_function("trace_allocation_during_snapshots", |b| {
+        let done = Arc::new(AtomicBool::new(false));
+        let reader = {
+            let done = Arc::clone(&done);
+            thread::spawn(move || {
+                while !done.load(Ordering::Relaxed) {
+                    black_box(AllocationGroupSnapshot::capture());
+                }
+            })
+        };
+        b.iter(|| MainTracer.trace_allocation(black_box(OBJECT_SIZE), group_id));
+        done.store(true, Ordering::Relaxed);
+        reader.join().unwrap();
+    });
+    group.finish();
+
+    // Each thread records into statistics of its own, but the live count of a group is shared by every thread, so
+    // threads allocating into the same group contend on it while threads allocating into their own groups don't.
+    let mut group = c.benchmark_group("allocation_tracing/tracer_contention");
+    group.throughput(Throughput::Elements(N_THREADS as u64));
+    let shared_group_id = register_group();
+    let own_group_ids = (0..N_THREADS).map(|_| register_group()).collect::<Vec<_>>();
+    group.bench_function(BenchmarkId::new("shared_group", N_THREADS), |b| {
+        b.iter_custom(|iters| trace_concurrently(iters, |_| shared_group_id));
+    });
+    group.bench_function(BenchmarkId::new("own_group", N_THREADS), |b| {
+        b.iter_custom(|iters| trace_concurrently(iters, |thread_idx| own_group_ids[thread_idx]));
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("allocation_tracing/round_trip");
+    let group_id = register_group();
+    for size in [16, 1024, 64 * 1024] {
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+            b.iter(|| {
+                MainTracer.trace_allocation(black_box(size), group_id);
+                MainTracer.trace_deallocation(black_box(size), group_id);
+            });
+        });
+    }
+    group.finish();
+}
+
+fn benchmark_layer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("allocation_tracing/layer");
+    group.bench_function("without_layer", |b| {
+        tracing::subscriber::with_default(Registry::default(), || {
+            let span = tracing::info_span!("component");
+            b.iter(|| drop(black_box(span.enter())));
+        });
+    });
+    group.bench_function("with_layer", |b| {
+        let subscriber = Registry::default().with(AllocationLayer::<Registry>::new());
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("component");
+            register_group().attach_to_span(&span);
+            b.iter(|| drop(black_box(span.enter())));
+        });
+    });
+    group.finish();
+}
+
+fn benchmark_latency(c: &mut Criterion) {
+    let mut group = c.benchmark_group("allocation_tracing/latency");
+    let group_id = register_group();
+    for size in [16, 1024, 64 * 1024] {
+        let layout = Layout::from_size_align(size, 8).unwrap();
+        group.bench_with_input(BenchmarkId::new("untimed", size), &layout, |b, &layout| {
+            b.iter(|| allocate(layout));
+        });
+        group.bench_with_input(BenchmarkId::new("timed", size), &layout, |b, &layout| {
+            b.iter(|| allocate_timed(layout, group_id));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    name = benches;
+    config = Criterion::default().noise_threshold(0.02);
+    targets = benchmark_tracer, benchmark_layer, benchmark_latency
+);
+criterion_main!(benches);
@@ -122,6 +122,7 @@ fn main() {
         println!("cargo:rerun-if-changed=proto/dd_metric.proto");
         println!("cargo:rerun-if-changed=proto/google/pubsub/v1/pubsub.proto");
         println!("cargo:rerun-if-changed=proto/google/rpc/status.proto");
+        println!("cargo:rerun-if-changed=proto/pprof/profile.proto");
         println!("cargo:rerun-if-changed=proto/vector.proto");
 
         let mut prost_build = prost_build::Config::new();
@@ -139,6 +140,7 @@ fn main() {
                     "proto/dd_trace.proto",
                     "proto/google/pubsub/v1/pubsub.proto",
                     "proto/google/rpc/status.proto",
+                    "proto/pprof/profile.proto",
                     "proto/vector.proto",
                 ],
                 &["proto/", "lib/vector-core/proto/"],
@@ -54,7 +54,10 @@ fn tracing_context_layer_enabled() -> bool {
     !matches!(std::env::var("DISABLE_INTERNAL_METRICS_TRACING_INTEGRATION"), Ok(x) if x == "true")
 }
 
-fn init(recorder: VectorRecorder) -> Result<()> {
+fn init(
+    recorder: VectorRecorder,
+    wrap: impl FnOnce(Box<dyn metrics::Recorder>) -> Box<dyn metrics::Recorder>,
+) -> Result<()> {
     // An escape hatch to allow disabling internal metrics core. May be used for
     // performance reasons. This is a hidden and undocumented functionality.
     if !metrics_enabled() {
@@ -95,7 +98,7 @@ fn init(recorder: VectorRecorder) -> Result<()> {
 
     // This where we combine metrics-rs and our registry. We box it to avoid
     // having to fiddle with statics ourselves.
-    metrics::set_boxed_recorder(recorder).map_err(|_| Error::AlreadyInitialized)
+    metrics::set_boxed_recorder(wrap(recorder)).map_err(|_| Error::AlreadyInitialized)
 }
 
 /// Initialize the default metrics sub-system
@@ -104,13 +107,26 @@ fn init(recorder: VectorRecorder) -> Result<()> {
 ///
 /// This function will error if it is called multiple times.
 pub fn init_global() -> Result<()> {
-    init(VectorRecorder::new_global())
+    init(VectorRecorder::new_global(), |recorder| recorder)
+}
+
+/// Initialize the default metrics sub-system, with the recorder wrapped by `wrap`.
+///
+/// This lets the caller intercept every metric registration, such as to add labels of its own.
+///
+/// # Errors
+///
+/// This function will error if it is called multiple times.
+pub fn init_global_with(
+    wrap: impl FnOnce(Box<dyn metrics::Recorder>) -> Box<dyn metrics::Recorder>,
+) -> Result<()> {
+    init(VectorRecorder::new_global(), wrap)
 }
 
 /// Initialize the thread-local metrics sub-system. This function will loop until a recorder is
 /// actually set.
 pub fn init_test() {
-    if init(VectorRecorder::new_test()).is_err() {
+    if init(VectorRecorder::new_test(), |recorder| recorder).is_err() {
         // The only error case returned by `init` is `AlreadyInitialized`. A race condition is
         // possible here: if metrics are being initialized by two (or more) test threads
         // simultaneously, the ones that fail to set return immediately, possibly allowing
@@ -21,3 +21,106 @@ pub(super) async fn health(running: Arc<AtomicBool>) -> Result<impl Reply, Rejec
         ))
     }
 }
+
+// Allocation group history handler, responds with the recent allocated bytes readings of the
+// allocation group, oldest first.
+#[cfg(feature = "allocation-tracing")]
+pub(super) async fn allocation_group_history(group_id: u8) -> Result<impl Reply, Rejection> {
+    use crate::internal_telemetry::allocations::{self, AllocationGroupId};
+
+    // Group IDs start at 1.
+    let history = std::num::NonZeroU8::new(group_id)
+        .and_then(|raw_group_id| {
+            allocations::allocation_group_history(AllocationGroupId::from_raw(raw_group_id.get()))
+        })
+        .ok_or_else(warp::reject::not_found)?;
+    let now = std::time::Instant::now();
+    let utc_now = chrono::Utc::now();
+    let readings = history
+        .readings()
+        .map(|(at, allocated_bytes)| {
+            let timestamp = chrono::Duration::from_std(now.saturating_duration_since(at))
+                .map_or(utc_now, |age| utc_now - age);
+            json!({ "timestamp": timestamp, "allocated_bytes": allocated_bytes })
+        })
+        .collect::<Vec<_>>();
+
+    Ok(json(&json!({ "group_id": group_id, "readings": readings })))
+}
+
+// Allocation group event log handler, responds with the most recent (de)allocations of the
+// allocation group, oldest first.
+#[cfg(feature = "allocation-tracing")]
+pub(super) async fn allocation_event_log(group_id: u8) -> Result<impl Reply, Rejection> {
+    use crate::internal_telemetry::allocations::{self, AllocationGroupId};
+
+    // Group IDs start at 1.
+    let raw_group_id = std::num::NonZeroU8::new(group_id).ok_or_else(warp::reject::not_found)?;
+    let events = allocations::allocation_event_log(AllocationGroupId::from_raw(raw_group_id.get()))
+        .into_iter()
+        .map(|event| {
+            json!({
+                "size": event.size,
+                "op": event.op,
+                "backtrace": event.backtrace.map(|backtrace| backtrace.to_string()),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    Ok(json(&json!({ "group_id": group_id, "events": events })))
+}
+
+// Component memory handler, responds with the memory report of the allocation group of the
+// component with the given ID.
+#[cfg(feature = "allocation-tracing")]
+pub(super) async fn component_memory_report(component_id: String) -> Result<impl Reply, Rejection> {
+    let token = crate::internal_telemetry::allocations::AllocationGroupRegistry::global()
+        .lookup(&component_id)
+        .ok_or_else(warp::reject::not_found)?;
+
+    Ok(json(&token.memory_report()))
+}
+
+// Allocation metrics handler, responds with the statistics of every allocation group in the
+// Prometheus text exposition format, for scraping them without an internal metrics pipeline, or in
+// the OpenMetrics text format when the scraper accepts it.
+#[cfg(feature = "allocation-tracing")]
+pub(super) async fn allocation_metrics(accept: Option<String>) -> Result<impl Reply, Rejection> {
+    use crate::internal_telemetry::allocations::{
+        encode_allocation_metrics_openmetrics, encode_allocation_metrics_prometheus,
+    };
+
+    let openmetrics = accept.map_or(false, |accept| {
+        accept.contains("application/openmetrics-text")
+    });
+    let (metrics, content_type) = if openmetrics {
+        (
+            encode_allocation_metrics_openmetrics(),
+            "application/openmetrics-text; version=1.0.0; charset=utf-8",
+        )
+    } else {
+        (
+            encode_allocation_metrics_prometheus(),
+            "text/plain; version=0.0.4",
+        )
+    };
+
+    Ok(warp::reply::with_header(
+        metrics,
+        "content-type",
+        content_type,
+    ))
+}
+
+// Allocation profile handler, responds with the statistics of every allocation group as a pprof
+// heap profile.
+#[cfg(feature = "allocation-tracing")]
+pub(super) async fn allocation_profile() -> Result<impl Reply, Rejection> {
+    let profile = crate::internal_telemetry::allocations::encode_allocation_profile_pprof();
+
+    Ok(warp::reply::with_header(
+        profile,
+        "content-type",
+        "application/octet-stream",
+    ))
+}
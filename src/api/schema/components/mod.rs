@@ -28,7 +28,8 @@ use crate::{
 #[derive(Debug, Clone, Interface)]
 #[graphql(
     field(name = "component_id", type = "String"),
-    field(name = "component_type", type = "String")
+    field(name = "component_type", type = "String"),
+    field(name = "memory_pressure_ratio", type = "Option<f64>")
 )]
 pub enum Component {
     Source(source::Source),
@@ -36,6 +37,22 @@ pub enum Component {
     Sink(sink::Sink),
 }
 
+/// Gets the fraction of its allocation budget that a component allocates, if allocation tracing is
+/// enabled and the component has a budget.
+fn memory_pressure_ratio(component_key: &ComponentKey) -> Option<f64> {
+    #[cfg(feature = "allocation-tracing")]
+    {
+        crate::internal_telemetry::allocations::AllocationGroupRegistry::global()
+            .lookup(component_key.id())
+            .and_then(|token| token.memory_pressure_ratio())
+    }
+    #[cfg(not(feature = "allocation-tracing"))]
+    {
+        let _ = component_key;
+        None
+    }
+}
+
 #[derive(Enum, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub enum ComponentKind {
     Source,
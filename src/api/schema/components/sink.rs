@@ -118,6 +118,11 @@ impl Sink {
         metrics::by_component_key(self.get_component_key())
             .into_sink_metrics(self.get_component_type())
     }
+
+    /// Sink allocated bytes relative to its allocation budget (1.0 = at budget), if it has one
+    pub async fn memory_pressure_ratio(&self) -> Option<f64> {
+        super::memory_pressure_ratio(self.get_component_key())
+    }
 }
 
 #[cfg(test)]
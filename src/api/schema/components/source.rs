@@ -138,6 +138,11 @@ impl Source {
         metrics::by_component_key(&self.0.component_key)
             .into_source_metrics(self.get_component_type())
     }
+
+    /// Source allocated bytes relative to its allocation budget (1.0 = at budget), if it has one
+    pub async fn memory_pressure_ratio(&self) -> Option<f64> {
+        super::memory_pressure_ratio(&self.0.component_key)
+    }
 }
 
 #[derive(Default, InputObject)]
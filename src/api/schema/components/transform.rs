@@ -111,6 +111,11 @@ impl Transform {
         metrics::by_component_key(&self.0.component_key)
             .into_transform_metrics(self.get_component_type())
     }
+
+    /// Transform allocated bytes relative to its allocation budget (1.0 = at budget), if it has one
+    pub async fn memory_pressure_ratio(&self) -> Option<f64> {
+        super::memory_pressure_ratio(&self.0.component_key)
+    }
 }
 
 #[derive(Default, InputObject)]
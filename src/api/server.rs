@@ -90,6 +90,52 @@ fn make_routes(
     // 404.
     let not_found = warp::any().and_then(|| async { Err(warp::reject::not_found()) });
 
+    // Allocation group history.
+    #[cfg(feature = "allocation-tracing")]
+    let allocation_group_history = warp::path!("internal" / "allocations" / u8 / "history")
+        .and(warp::get())
+        .and_then(handler::allocation_group_history)
+        .boxed();
+    #[cfg(not(feature = "allocation-tracing"))]
+    let allocation_group_history = not_found.boxed();
+
+    // Allocation group event log.
+    #[cfg(feature = "allocation-tracing")]
+    let allocation_event_log = warp::path!("internal" / "allocations" / u8 / "events")
+        .and(warp::get())
+        .and_then(handler::allocation_event_log)
+        .boxed();
+    #[cfg(not(feature = "allocation-tracing"))]
+    let allocation_event_log = not_found.boxed();
+
+    // Component memory report, by component ID.
+    #[cfg(feature = "allocation-tracing")]
+    let component_memory_report = warp::path!("internal" / "allocations" / "components" / String)
+        .and(warp::get())
+        .and_then(handler::component_memory_report)
+        .boxed();
+    #[cfg(not(feature = "allocation-tracing"))]
+    let component_memory_report = not_found.boxed();
+
+    // Allocation group metrics, in the Prometheus text exposition format.
+    #[cfg(feature = "allocation-tracing")]
+    let allocation_metrics = warp::path!("metrics" / "allocations")
+        .and(warp::get())
+        .and(warp::header::optional::<String>("accept"))
+        .and_then(handler::allocation_metrics)
+        .boxed();
+    #[cfg(not(feature = "allocation-tracing"))]
+    let allocation_metrics = not_found.boxed();
+
+    // Allocation group heap profile.
+    #[cfg(feature = "allocation-tracing")]
+    let allocation_profile = warp::path!("internal" / "pprof" / "heap")
+        .and(warp::get())
+        .and_then(handler::allocation_profile)
+        .boxed();
+    #[cfg(not(feature = "allocation-tracing"))]
+    let allocation_profile = not_found.boxed();
+
     // GraphQL subscription handler. Creates a Warp WebSocket handler and for each connection,
     // parses the required headers for GraphQL and builds per-connection context based on the
     // provided `WatchTx` channel sender. This allows GraphQL resolvers to subscribe to
@@ -146,6 +192,11 @@ fn make_routes(
     // Wire up the health + GraphQL endpoints. Provides a permissive CORS policy to allow for
     // cross-origin interaction with the Vector API.
     health
+        .or(allocation_group_history)
+        .or(allocation_event_log)
+        .or(component_memory_report)
+        .or(allocation_metrics)
+        .or(allocation_profile)
         .or(graphql_handler)
         .or(graphql_playground)
         .or(not_found)
@@ -85,6 +85,11 @@ impl ApplicationConfig {
         #[cfg(feature = "enterprise")]
         let enterprise = build_enterprise(&mut config, config_paths.clone())?;
 
+        #[cfg(feature = "allocation-tracing")]
+        crate::internal_telemetry::allocations::apply_allocation_tracing_config(
+            config.internal_telemetry.allocations,
+        );
+
         let diff = config::ConfigDiff::initial(&config);
         let pieces = topology::build_or_log_errors(&config, &diff, HashMap::new())
             .await
@@ -352,8 +357,14 @@ impl FinishedApplication {
 pub fn init_global() {
     openssl_probe::init_ssl_cert_env_vars();
 
-    #[cfg(not(feature = "enterprise-tests"))]
+    #[cfg(all(not(feature = "enterprise-tests"), not(feature = "metrics-auto-tag")))]
     metrics::init_global().expect("metrics initialization failed");
+
+    #[cfg(all(not(feature = "enterprise-tests"), feature = "metrics-auto-tag"))]
+    metrics::init_global_with(|recorder| {
+        Box::new(crate::internal_telemetry::allocations::AllocationGroupRecorder::new(recorder))
+    })
+    .expect("metrics initialization failed");
 }
 
 fn get_log_levels(default: &str) -> String {
@@ -403,6 +414,21 @@ pub fn build_runtime(threads: Option<usize>, thread_name: &str) -> Result<Runtim
         }
     }
 
+    // Thread stacks are not allocated through the global allocator, so the size they reserve is
+    // accounted for separately.
+    #[cfg(feature = "allocation-tracing")]
+    {
+        use crate::internal_telemetry::allocations::{
+            record_thread_start, record_thread_stop, DEFAULT_THREAD_STACK_SIZE,
+        };
+
+        let pool = thread_name.to_string();
+        let stopped_pool = pool.clone();
+        rt_builder
+            .on_thread_start(move || record_thread_start(&pool, DEFAULT_THREAD_STACK_SIZE))
+            .on_thread_stop(move || record_thread_stop(&stopped_pool, DEFAULT_THREAD_STACK_SIZE));
+    }
+
     Ok(rt_builder.build().expect("Unable to create async runtime"))
 }
 
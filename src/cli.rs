@@ -165,13 +165,111 @@ pub struct RootOpts {
     pub allocation_tracing: bool,
 
     /// Set allocation tracing reporting rate in milliseconds.
+    ///
+    /// Takes precedence over `internal_telemetry.allocations.reporting_interval_ms` in the
+    /// configuration. Defaults to `5000`.
+    #[cfg(feature = "allocation-tracing")]
+    #[arg(long, env = "ALLOCATION_TRACING_REPORTING_INTERVAL_MS")]
+    pub allocation_tracing_reporting_interval_ms: Option<u64>,
+
+    /// Set the `instance` label attached to allocation tracing metrics.
+    ///
+    /// Defaults to the hostname and process ID, which keeps the metrics of several Vector
+    /// instances reporting to the same backend apart.
+    #[cfg(feature = "allocation-tracing")]
+    #[arg(long, env = "ALLOCATION_TRACING_NAMESPACE")]
+    pub allocation_tracing_namespace: Option<String>,
+
+    /// Set the prefix of the metric names in the Prometheus exposition of allocation tracing
+    /// statistics.
+    ///
+    /// Characters that are not allowed in metric names are removed. Defaults to
+    /// `vector_allocations_`.
+    #[cfg(feature = "allocation-tracing")]
+    #[arg(long, env = "ALLOCATION_TRACING_METRIC_PREFIX")]
+    pub allocation_tracing_metric_prefix: Option<String>,
+
+    /// Check that allocations are traced when allocation tracing is enabled, and abort if they
+    /// are not.
+    #[cfg(feature = "allocation-tracing")]
+    #[arg(long, env = "VALIDATE_ALLOCATION_TRACKING", default_value = "false")]
+    pub validate_allocation_tracking: bool,
+
+    /// Register and enter allocation groups without tracking the bytes they allocate.
+    ///
+    /// Only the number of times each allocation group is entered is reported, which cheaply
+    /// checks that allocations would be attributed to the right components before enabling
+    /// allocation tracing fully.
+    #[cfg(feature = "allocation-tracing")]
+    #[arg(long, env = "ALLOCATION_TRACING_DRY_RUN", default_value = "false")]
+    pub allocation_tracing_dry_run: bool,
+
+    /// Attach exemplars to the OpenMetrics exposition of allocation tracing statistics.
+    ///
+    /// The allocated bytes gauge of a component carries the trace ID last recorded in the
+    /// `trace_id` field of its span. Not every OpenMetrics parser accepts exemplars on gauges.
+    #[cfg(feature = "allocation-tracing")]
+    #[arg(long, env = "ALLOCATION_TRACING_EXEMPLARS", default_value = "false")]
+    pub allocation_tracing_exemplars: bool,
+
+    /// Set the number of bytes allocation tracing may use for its own bookkeeping.
+    ///
+    /// Once reached, allocations of newly registered components are attributed to the root
+    /// allocation group. Takes precedence over `internal_telemetry.allocations.max_overhead_bytes`
+    /// in the configuration. Unlimited by default.
+    #[cfg(feature = "allocation-tracing")]
+    #[arg(long, env = "ALLOCATION_TRACING_MAX_OVERHEAD_BYTES")]
+    pub allocation_tracing_max_overhead_bytes: Option<u64>,
+
+    /// Set the number of allocated bytes above which a warning is logged about a component.
+    #[cfg(feature = "allocation-tracing")]
+    #[arg(long, env = "ALLOCATION_TRACING_THRESHOLD_BYTES")]
+    pub allocation_tracing_threshold_bytes: Option<u64>,
+
+    /// Set the path of a Unix domain socket on which other processes can report their own
+    /// allocations.
+    ///
+    /// Clients must send the token in `--allocation-tracing-proxy-token-file` first.
+    #[cfg(feature = "allocation-tracing")]
+    #[arg(
+        long,
+        env = "ALLOCATION_TRACING_PROXY_SOCKET",
+        requires = "allocation_tracing_proxy_token_file"
+    )]
+    pub allocation_tracing_proxy_socket: Option<PathBuf>,
+
+    /// Set the path of the file holding the token clients of the allocation tracing proxy must
+    /// send. The file must not be accessible by other users.
+    #[cfg(feature = "allocation-tracing")]
+    #[arg(long, env = "ALLOCATION_TRACING_PROXY_TOKEN_FILE")]
+    pub allocation_tracing_proxy_token_file: Option<PathBuf>,
+
+    /// Set the number of allocation groups the allocation tracing proxy registers for the groups
+    /// of other processes. The events of any other group are dropped.
     #[cfg(feature = "allocation-tracing")]
     #[arg(
         long,
-        env = "ALLOCATION_TRACING_REPORTING_INTERVAL_MS",
-        default_value = "5000"
+        env = "ALLOCATION_TRACING_PROXY_MAX_GROUPS",
+        default_value = "16"
     )]
-    pub allocation_tracing_reporting_interval_ms: u64,
+    pub allocation_tracing_proxy_max_groups: usize,
+
+    /// Write a summary of the bytes allocated by each component when Vector receives `SIGUSR1`.
+    ///
+    /// The summary is logged, unless `--allocation-tracing-heap-summary-path` is set.
+    #[cfg(all(unix, feature = "allocation-tracing-sigusr1"))]
+    #[arg(
+        long,
+        env = "ALLOCATION_TRACING_HEAP_SUMMARY_ON_SIGUSR1",
+        default_value = "false"
+    )]
+    pub allocation_tracing_heap_summary_on_sigusr1: bool,
+
+    /// Set the path of the file that the summary requested with `SIGUSR1` is written to,
+    /// replacing the previous summary.
+    #[cfg(all(unix, feature = "allocation-tracing-sigusr1"))]
+    #[arg(long, env = "ALLOCATION_TRACING_HEAP_SUMMARY_PATH")]
+    pub allocation_tracing_heap_summary_path: Option<PathBuf>,
 }
 
 impl RootOpts {
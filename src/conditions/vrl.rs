@@ -38,11 +38,14 @@ impl ConditionalConfig for VrlConfig {
         //     },
         // };
 
-        let functions = vrl_stdlib::all()
+        #[allow(unused_mut)]
+        let mut functions = vrl_stdlib::all()
             .into_iter()
             .chain(enrichment::vrl_functions().into_iter())
             .chain(vector_vrl_functions::all())
             .collect::<Vec<_>>();
+        #[cfg(feature = "allocation-tracing")]
+        functions.append(&mut crate::internal_telemetry::allocations::vrl_functions());
 
         let state = vrl::state::TypeState::default();
 
@@ -18,8 +18,9 @@ use super::api;
 #[cfg(feature = "enterprise")]
 use super::enterprise;
 use super::{
-    compiler, schema, BoxedSource, BoxedTransform, ComponentKey, Config, EnrichmentTableOuter,
-    HealthcheckOptions, SinkOuter, SourceOuter, TestDefinition, TransformOuter,
+    compiler, internal_telemetry, schema, BoxedSource, BoxedTransform, ComponentKey, Config,
+    EnrichmentTableOuter, HealthcheckOptions, SinkOuter, SourceOuter, TestDefinition,
+    TransformOuter,
 };
 
 /// A complete Vector configuration.
@@ -49,6 +50,10 @@ pub struct ConfigBuilder {
     #[serde(default)]
     pub healthchecks: HealthcheckOptions,
 
+    #[configurable(derived)]
+    #[serde(default)]
+    pub internal_telemetry: internal_telemetry::Options,
+
     /// All configured enrichment tables.
     #[serde(default)]
     pub enrichment_tables: IndexMap<ComponentKey, EnrichmentTableOuter>,
@@ -189,6 +194,7 @@ impl From<Config> for ConfigBuilder {
             #[cfg(feature = "enterprise")]
             enterprise,
             healthchecks,
+            internal_telemetry,
             enrichment_tables,
             sources,
             sinks,
@@ -218,6 +224,7 @@ impl From<Config> for ConfigBuilder {
             #[cfg(feature = "enterprise")]
             enterprise,
             healthchecks,
+            internal_telemetry,
             enrichment_tables,
             sources,
             sinks,
@@ -331,6 +338,9 @@ impl ConfigBuilder {
 
         self.healthchecks.merge(with.healthchecks);
 
+        self.internal_telemetry
+            .append(with.internal_telemetry, &mut errors);
+
         with.enrichment_tables.keys().for_each(|k| {
             if self.enrichment_tables.contains_key(k) {
                 errors.push(format!("duplicate enrichment_table name found: {}", k));
@@ -48,6 +48,7 @@ pub fn compile(mut builder: ConfigBuilder) -> Result<(Config, Vec<String>), Vec<
         #[cfg(feature = "enterprise")]
         enterprise,
         healthchecks,
+        internal_telemetry,
         enrichment_tables,
         sources,
         sinks,
@@ -104,6 +105,7 @@ pub fn compile(mut builder: ConfigBuilder) -> Result<(Config, Vec<String>), Vec<
             enterprise,
             hash,
             healthchecks,
+            internal_telemetry,
             enrichment_tables,
             sources,
             sinks,
@@ -0,0 +1,164 @@
+use vector_config::configurable_component;
+
+/// Internal telemetry options.
+#[configurable_component]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Options {
+    /// Allocation tracing options.
+    ///
+    /// These only take effect when Vector is built with allocation tracing, and runs with it enabled.
+    #[configurable(derived)]
+    pub allocations: AllocationTracingConfigFromFile,
+}
+
+impl Options {
+    /// Merges two internal telemetry options together.
+    pub fn append(&mut self, with: Self, errors: &mut Vec<String>) {
+        self.allocations.append(with.allocations, errors);
+    }
+}
+
+/// Allocation tracing options.
+///
+/// An option that is also set on the command line, or through its environment variable, takes that value instead.
+/// Changes to these options are applied when the configuration is reloaded.
+#[configurable_component]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AllocationTracingConfigFromFile {
+    /// The interval, in milliseconds, at which allocation statistics are reported.
+    ///
+    /// Defaults to `5000`.
+    pub reporting_interval_ms: Option<u64>,
+
+    /// The size, in bytes, below which allocations are not traced.
+    ///
+    /// Defaults to `0`, which traces every allocation.
+    pub min_tracked_size_bytes: Option<usize>,
+
+    /// The number of readings of its allocated bytes kept in the history of each component.
+    ///
+    /// It is capped at `3600` readings. Defaults to `60`.
+    pub history_len: Option<usize>,
+
+    /// The number of bytes allocation tracing may use for its own bookkeeping.
+    ///
+    /// Unlimited by default.
+    pub max_overhead_bytes: Option<u64>,
+}
+
+impl AllocationTracingConfigFromFile {
+    /// Merges two sets of allocation tracing options together.
+    pub fn append(&mut self, with: Self, errors: &mut Vec<String>) {
+        fn append_option<T: Copy + PartialEq + std::fmt::Debug>(
+            name: &str,
+            option: &mut Option<T>,
+            with: Option<T>,
+            errors: &mut Vec<String>,
+        ) {
+            match (*option, with) {
+                (Some(value), Some(with)) if value != with => errors.push(format!(
+                    "conflicting values for 'internal_telemetry.allocations.{}' found: {:?} and {:?}",
+                    name, value, with
+                )),
+                (None, Some(with)) => *option = Some(with),
+                _ => {}
+            }
+        }
+
+        append_option(
+            "reporting_interval_ms",
+            &mut self.reporting_interval_ms,
+            with.reporting_interval_ms,
+            errors,
+        );
+        append_option(
+            "min_tracked_size_bytes",
+            &mut self.min_tracked_size_bytes,
+            with.min_tracked_size_bytes,
+            errors,
+        );
+        append_option(
+            "history_len",
+            &mut self.history_len,
+            with.history_len,
+            errors,
+        );
+        append_option(
+            "max_overhead_bytes",
+            &mut self.max_overhead_bytes,
+            with.max_overhead_bytes,
+            errors,
+        );
+    }
+
+    /// Overrides these options with each of the given options that is set.
+    pub fn with_overrides(self, overrides: Self) -> Self {
+        Self {
+            reporting_interval_ms: overrides
+                .reporting_interval_ms
+                .or(self.reporting_interval_ms),
+            min_tracked_size_bytes: overrides
+                .min_tracked_size_bytes
+                .or(self.min_tracked_size_bytes),
+            history_len: overrides.history_len.or(self.history_len),
+            max_overhead_bytes: overrides.max_overhead_bytes.or(self.max_overhead_bytes),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn append_conflicts() {
+        let mut options = AllocationTracingConfigFromFile {
+            reporting_interval_ms: Some(1000),
+            ..Default::default()
+        };
+        let mut errors = Vec::new();
+        options.append(
+            AllocationTracingConfigFromFile {
+                reporting_interval_ms: Some(1000),
+                history_len: Some(10),
+                ..Default::default()
+            },
+            &mut errors,
+        );
+        assert!(errors.is_empty());
+        assert_eq!(options.history_len, Some(10));
+
+        options.append(
+            AllocationTracingConfigFromFile {
+                reporting_interval_ms: Some(2000),
+                ..Default::default()
+            },
+            &mut errors,
+        );
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn overrides_take_precedence() {
+        let file = AllocationTracingConfigFromFile {
+            reporting_interval_ms: Some(1000),
+            history_len: Some(10),
+            ..Default::default()
+        };
+        let overrides = AllocationTracingConfigFromFile {
+            reporting_interval_ms: Some(250),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            file.with_overrides(overrides),
+            AllocationTracingConfigFromFile {
+                reporting_interval_ms: Some(250),
+                history_len: Some(10),
+                ..Default::default()
+            }
+        );
+    }
+}
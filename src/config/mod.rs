@@ -28,6 +28,7 @@ pub mod enterprise;
 pub mod format;
 mod graph;
 mod id;
+pub mod internal_telemetry;
 mod loading;
 pub mod provider;
 pub mod schema;
@@ -93,6 +94,7 @@ pub struct Config {
     pub enterprise: Option<enterprise::Options>,
     pub global: GlobalOptions,
     pub healthchecks: HealthcheckOptions,
+    pub internal_telemetry: internal_telemetry::Options,
     sources: IndexMap<ComponentKey, SourceOuter>,
     sinks: IndexMap<ComponentKey, SinkOuter<OutputId>>,
     transforms: IndexMap<ComponentKey, TransformOuter<OutputId>>,
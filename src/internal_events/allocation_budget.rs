@@ -0,0 +1,16 @@
+use crate::emit;
+use vector_core::internal_event::{ComponentEventsDropped, InternalEvent, INTENTIONAL};
+
+#[derive(Debug)]
+pub struct AllocationBudgetEventsDropped {
+    pub count: usize,
+}
+
+impl InternalEvent for AllocationBudgetEventsDropped {
+    fn emit(self) {
+        emit!(ComponentEventsDropped::<INTENTIONAL> {
+            count: self.count,
+            reason: "Allocated bytes exceed the allocation budget of the component.",
+        });
+    }
+}
@@ -3,6 +3,8 @@ pub mod prelude;
 
 mod adaptive_concurrency;
 mod aggregate;
+#[cfg(feature = "allocation-tracing")]
+mod allocation_budget;
 #[cfg(any(feature = "sources-amqp", feature = "sinks-amqp"))]
 mod amqp;
 #[cfg(feature = "sources-apache_metrics")]
@@ -136,6 +138,8 @@ pub(crate) use mongodb_metrics::*;
 
 #[cfg(feature = "transforms-aggregate")]
 pub(crate) use self::aggregate::*;
+#[cfg(feature = "allocation-tracing")]
+pub(crate) use self::allocation_budget::*;
 #[cfg(any(feature = "sources-amqp", feature = "sinks-amqp"))]
 pub(crate) use self::amqp::*;
 #[cfg(feature = "sources-apache_metrics")]
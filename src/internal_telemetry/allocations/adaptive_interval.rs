@@ -0,0 +1,82 @@
+//! A reporting interval that adapts to how fast the process allocates.
+//!
+//! A fixed interval wastes emissions while the process is idle, and is too coarse to see a burst of allocations in
+//! detail. With an [`AdaptiveReportingInterval`], the processor halves its interval after an interval in which the
+//! process allocated quickly, and doubles it after a quiet one, within configured bounds.
+
+use std::time::Duration;
+
+/// The bounds of the reporting interval, and the allocation rate above which the processor emits more often.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AdaptiveReportingInterval {
+    /// The shortest interval the processor emits at. Defaults to 500 milliseconds.
+    pub min_interval: Duration,
+
+    /// The longest interval the processor emits at. Defaults to 30 seconds.
+    pub max_interval: Duration,
+
+    /// The number of bytes allocated per second, across every allocation group, at or above which the interval is
+    /// halved.
+    ///
+    /// The interval is doubled after an interval in which the process allocated less than a quarter of this rate, and
+    /// kept as it is otherwise, so that a steady rate doesn't keep it moving. Defaults to 64 MiB.
+    pub busy_bytes_per_sec: u64,
+}
+
+impl Default for AdaptiveReportingInterval {
+    fn default() -> Self {
+        Self {
+            min_interval: Duration::from_millis(500),
+            max_interval: Duration::from_secs(30),
+            busy_bytes_per_sec: 64 * 1024 * 1024,
+        }
+    }
+}
+
+impl AdaptiveReportingInterval {
+    /// Gets the interval to wait for after an interval of the given length, in which `allocated_bytes` were allocated.
+    pub(super) fn next_interval(&self, interval: Duration, allocated_bytes: u64) -> Duration {
+        let bytes_per_sec = allocated_bytes as f64 / interval.as_secs_f64().max(f64::EPSILON);
+        let busy_bytes_per_sec = self.busy_bytes_per_sec as f64;
+        let next_interval = if bytes_per_sec >= busy_bytes_per_sec {
+            interval / 2
+        } else if bytes_per_sec < busy_bytes_per_sec / 4.0 {
+            interval.saturating_mul(2)
+        } else {
+            interval
+        };
+        // Bounds that cross each other are not rejected, and the maximum wins.
+        next_interval.max(self.min_interval).min(self.max_interval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shrinks_during_bursts_and_grows_when_quiet() {
+        let adaptive = AdaptiveReportingInterval {
+            min_interval: Duration::from_secs(1),
+            max_interval: Duration::from_secs(16),
+            busy_bytes_per_sec: 1024,
+        };
+
+        // A burst of allocations shrinks the interval down to its floor.
+        let mut interval = Duration::from_secs(8);
+        for expected_secs in [4, 2, 1, 1] {
+            interval = adaptive.next_interval(interval, interval.as_secs() * 4096);
+            assert_eq!(interval, Duration::from_secs(expected_secs));
+        }
+
+        // A steady rate keeps it, and a quiet process grows it up to its ceiling.
+        assert_eq!(
+            adaptive.next_interval(Duration::from_secs(4), 4 * 512),
+            Duration::from_secs(4)
+        );
+        for expected_secs in [2, 4, 8, 16, 16] {
+            interval = adaptive.next_interval(interval, 0);
+            assert_eq!(interval, Duration::from_secs(expected_secs));
+        }
+    }
+}
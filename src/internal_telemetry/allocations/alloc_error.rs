@@ -0,0 +1,93 @@
+//! A diagnostic written to stderr when the wrapped allocator fails to allocate.
+//!
+//! `std::alloc::set_alloc_error_hook` is not available on the stable toolchain, so the diagnostic is written by the
+//! tracing allocator instead, right before it returns the null pointer, whether or not the process aborts afterwards.
+//! Most callers hand the null pointer to `std::alloc::handle_alloc_error`, whose default hook aborts, but fallible
+//! callers such as `Vec::try_reserve` recover from it, and the process keeps running. The allocator can't allocate at
+//! that point, so the diagnostic is formatted into a buffer on the stack, and the lock of the thread-local statistics
+//! is only taken if it is not already held.
+
+use std::{
+    io::{self, Cursor, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use super::{
+    group_mem_totals, panic_hook::held_lock, AllocationGroupId, DoubleBufferedMetrics,
+    THREAD_LOCAL_REFS,
+};
+
+/// The size of the buffer the diagnostic is formatted into, which fits the longest diagnostic.
+const DIAGNOSTIC_BUFFER_SIZE: usize = 256;
+
+/// Whether the diagnostic is written when an allocation fails, as set at initialization.
+static ALLOC_ERROR_HOOK: AtomicBool = AtomicBool::new(false);
+
+/// Makes the tracing allocator write a diagnostic to stderr when the wrapped allocator fails to allocate.
+pub(super) fn install_alloc_error_hook() {
+    ALLOC_ERROR_HOOK.store(true, Ordering::Relaxed);
+}
+
+/// Writes the diagnostic of an allocation of `object_size` bytes that failed in the given allocation group.
+///
+/// This is called by the tracer on the allocation failure path, while the allocation group is suspended.
+#[cold]
+pub(super) fn report_allocation_failure(object_size: usize, group_id: AllocationGroupId) {
+    if !ALLOC_ERROR_HOOK.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let thread_local_refs = THREAD_LOCAL_REFS.try_lock();
+    // The thread that failed to allocate might be the one holding the lock, while registering its statistics.
+    let thread_local_refs = held_lock(&thread_local_refs).map_or(&[][..], |refs| refs.as_slice());
+    let mut buffer = [0; DIAGNOSTIC_BUFFER_SIZE];
+    let mut cursor = Cursor::new(&mut buffer[..]);
+    // A diagnostic that doesn't fit the buffer is written truncated, and there is nowhere to report a failure to write
+    // to stderr.
+    let _ = write_diagnostic(&mut cursor, object_size, group_id, thread_local_refs);
+    let written = cursor.position() as usize;
+    let _ = io::stderr().lock().write_all(&buffer[..written]);
+}
+
+/// Writes the active allocation group, the bytes it currently allocates, and the size of the failed allocation.
+fn write_diagnostic(
+    writer: &mut impl Write,
+    object_size: usize,
+    group_id: AllocationGroupId,
+    thread_local_refs: &[&'static DoubleBufferedMetrics],
+) -> io::Result<()> {
+    let totals = group_mem_totals(thread_local_refs, group_id.traced_slot_index());
+    writeln!(
+        writer,
+        "allocator returned null for an allocation of {} bytes in allocation group {} (allocated_bytes={})",
+        object_size,
+        group_id.as_raw(),
+        totals.allocations.saturating_sub(totals.deallocations),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal_telemetry::allocations::{tests::test_group_id, MainTracer, Tracer};
+
+    #[test]
+    fn writes_the_group_and_size_of_failed_allocations() {
+        let group_id = test_group_id(67);
+        MainTracer.trace_allocation(2048, group_id);
+
+        let mut output = Vec::new();
+        write_diagnostic(
+            &mut output,
+            1 << 40,
+            group_id,
+            &THREAD_LOCAL_REFS.lock().unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "allocator returned null for an allocation of 1099511627776 bytes in allocation group 67 (allocated_bytes=2048)\n"
+        );
+    }
+}
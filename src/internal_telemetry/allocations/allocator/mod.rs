@@ -5,11 +5,19 @@ mod tracer;
 mod tracing;
 mod tracing_allocator;
 
+pub use self::token::current_allocation_group;
 pub use self::token::AllocationGroupId;
-pub use self::token::AllocationGroupToken;
+pub use self::token::{
+    enter_task_allocation_group, exit_task_allocation_group, in_task_allocation_group,
+};
+pub use self::token::{with_allocation_group, ScopedAllocationGroup};
+pub use self::token::{AllocationGroupToken, WeakAllocationGroupToken};
+pub use self::token::{AllocationTrackedFuture, TrackAllocationsExt};
 pub use self::tracer::Tracer;
-pub use self::tracing::AllocationLayer;
-pub use self::tracing_allocator::GroupedTraceableAllocator;
+pub use self::tracing::{AllocationLayer, NET_ALLOCATED_BYTES_FIELD};
+pub use self::tracing_allocator::{
+    allocation_overhead, header_overhead, GroupedTraceableAllocator,
+};
 
 /// Runs the given closure without tracing allocations or deallocations.
 ///
@@ -1,13 +1,27 @@
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
+    fmt,
+    future::Future,
+    marker::PhantomData,
     num::NonZeroU8,
-    sync::atomic::{AtomicU8, Ordering},
+    pin::Pin,
+    sync::atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering},
+    task::{Context, Poll},
 };
 
+use arr_macro::arr;
+use pin_project::pin_project;
 use tracing::Span;
 
 use super::stack::GroupStack;
 use super::tracing::WithAllocationGroup;
+use crate::internal_telemetry::allocations::{
+    allocation_group_registration, component_log_fields, current_allocated_bytes,
+    deregister_allocation_group, diff_table::format_bytes, group_generation, group_log_name,
+    group_name, hierarchy, memory_pressure_ratio, memory_report, merge_allocation_groups,
+    record_allocation_group_entered, set_group_name, task_groups::task_fallback_group,
+    ComponentMemoryReport, GROUP_USAGE_LOG_TARGET, NUM_GROUPS,
+};
 
 thread_local! {
     /// The currently executing allocation token.
@@ -18,6 +32,14 @@ thread_local! {
         const { RefCell::new(GroupStack::new()) };
 }
 
+tokio::task_local! {
+    /// The allocation group of the task that is currently being polled.
+    ///
+    /// When set, allocations are associated with this allocation group rather than the one of the thread, as the
+    /// thread may run many tasks in turn.
+    static TASK_ALLOCATION_GROUP: Cell<Option<AllocationGroupId>>;
+}
+
 /// The identifier that uniquely identifiers an allocation group.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct AllocationGroupId(NonZeroU8);
@@ -27,7 +49,13 @@ impl AllocationGroupId {
     // Group IDs start at 1. The value 0 is reserved for handling runtime allocation edge cases.
     pub const ROOT: Self = AllocationGroupId::from_raw(1);
 
-    pub(super) const fn from_raw(raw_group_id: u8) -> Self {
+    /// The group ID shared by the allocation groups registered once every other slot is taken.
+    ///
+    /// Registration never hands out its slot, the last one, to a single component. The (de)allocations traced with an
+    /// ID that has no slot are attributed to it as well.
+    pub const OVERFLOW: Self = AllocationGroupId::from_raw(NUM_GROUPS as u8 - 1);
+
+    pub(crate) const fn from_raw(raw_group_id: u8) -> Self {
         unsafe { Self(NonZeroU8::new_unchecked(raw_group_id)) }
     }
 
@@ -37,6 +65,44 @@ impl AllocationGroupId {
         self.0.get()
     }
 
+    /// Gets the index of the slot that holds the statistics of this allocation group, if it has one.
+    ///
+    /// Raw group IDs go up to `u8::MAX`, but only the IDs below `NUM_GROUPS` have a slot in the per-group statistics.
+    /// [`register`](Self::register) stops handing out IDs before the slot of the overflow group, so every registered
+    /// allocation group has a slot, but an ID stored along with an allocation or received from another process is only
+    /// as trustworthy as its source. Indexing with
+    /// this instead of [`as_raw`](Self::as_raw) keeps such IDs from panicking in the allocator.
+    #[inline(always)]
+    #[must_use]
+    pub const fn slot_index(self) -> Option<usize> {
+        let slot_index = self.0.get() as usize;
+        if slot_index < NUM_GROUPS {
+            Some(slot_index)
+        } else {
+            None
+        }
+    }
+
+    /// Gets the index of the slot that the (de)allocations of this allocation group are attributed to.
+    ///
+    /// This is the slot of the group if it has one, or else the slot of the [overflow group](Self::OVERFLOW), so that
+    /// the allocator neither panics on nor loses the (de)allocations of an ID without a slot.
+    #[inline(always)]
+    #[must_use]
+    pub(crate) const fn traced_slot_index(self) -> usize {
+        match self.slot_index() {
+            Some(slot_index) => slot_index,
+            None => Self::OVERFLOW.0.get() as usize,
+        }
+    }
+
+    /// Whether this allocation group is shared by every component it is attributed to, like the root and overflow
+    /// allocation groups, which are never deregistered.
+    #[must_use]
+    pub(crate) fn is_shared(self) -> bool {
+        self == Self::ROOT || self == Self::OVERFLOW
+    }
+
     /// Registers an allocation group ID.
     ///
     /// This group ID uniquely identifies a given allocation group, and is the means by which to
@@ -45,16 +111,19 @@ impl AllocationGroupId {
     /// Group IDs must be attached to a [`Span`][tracing::Span] in order to become active,
     /// associating allocations and deallocations within an active span as being attached to the
     /// given allocation group.
-    pub fn register() -> Option<AllocationGroupId> {
+    ///
+    /// Returns `None` once every ID below the one of the [overflow group](Self::OVERFLOW) was handed out: the IDs of
+    /// deregistered groups are handed out again by `register_allocation_group` instead.
+    pub(crate) fn register() -> Option<AllocationGroupId> {
         static GROUP_ID: AtomicU8 = AtomicU8::new(AllocationGroupId::ROOT.0.get() + 1);
 
-        let group_id = GROUP_ID.fetch_add(1, Ordering::Relaxed);
-
-        if group_id != u8::MAX {
-            Some(AllocationGroupId::from_raw(group_id))
-        } else {
-            None
-        }
+        // The counter stops at the overflow group, rather than wrapping around to the reserved zero ID.
+        GROUP_ID
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |group_id| {
+                (group_id < Self::OVERFLOW.0.get()).then(|| group_id + 1)
+            })
+            .ok()
+            .map(AllocationGroupId::from_raw)
     }
 
     /// Attaches this allocation group to a [`Span`][tracing::Span].
@@ -66,31 +135,469 @@ impl AllocationGroupId {
         tracing::dispatcher::get_default(move |dispatch| {
             if let Some(id) = span.id() {
                 if let Some(ctx) = dispatch.downcast_ref::<WithAllocationGroup>() {
-                    (ctx.with_allocation_group)(dispatch, &id, AllocationGroupToken::from(self));
+                    (ctx.with_allocation_group)(dispatch, &id, self);
                 }
             }
         });
     }
 }
 
+/// Gets the allocation group that is active on the current thread.
+///
+/// A group entered explicitly on the thread, such as with [`ScopedAllocationGroup`], takes precedence over the group
+/// of the task being polled, so that a component running as a task can still attribute parts of its work to groups of
+/// their own. While the active allocation group is suspended, such as within
+/// [`without_allocation_tracing`][super::without_allocation_tracing], the root allocation group is returned instead.
+pub fn current_allocation_group() -> AllocationGroupId {
+    LOCAL_ALLOCATION_GROUP_STACK
+        .try_with(|stack| {
+            stack
+                .try_borrow()
+                .map_or(AllocationGroupId::ROOT, |stack| match stack.current() {
+                    AllocationGroupId::ROOT => {
+                        task_allocation_group().unwrap_or(AllocationGroupId::ROOT)
+                    }
+                    group_id => group_id,
+                })
+        })
+        .unwrap_or(AllocationGroupId::ROOT)
+}
+
+/// Gets the allocation group of the task that is currently being polled, if it has one.
+fn task_allocation_group() -> Option<AllocationGroupId> {
+    TASK_ALLOCATION_GROUP.try_with(Cell::get).ok().flatten()
+}
+
+/// Runs the given future as a task with the given allocation group.
+///
+/// Every allocation made while the future is polled is associated with the allocation group, regardless of the
+/// allocation group of the thread polling it.
+pub fn in_task_allocation_group<F: Future>(
+    group_id: AllocationGroupId,
+    future: F,
+) -> impl Future<Output = F::Output> {
+    TASK_ALLOCATION_GROUP.scope(Cell::new(Some(group_id)), future)
+}
+
+/// Sets the allocation group of the current task.
+///
+/// This only has an effect within a future run with [`in_task_allocation_group`].
+pub async fn enter_task_allocation_group(group_id: AllocationGroupId) {
+    let _ = TASK_ALLOCATION_GROUP.try_with(|group| group.set(Some(group_id)));
+}
+
+/// Clears the allocation group of the current task, so that allocations are associated with the allocation group of
+/// the thread again.
+///
+/// This only has an effect within a future run with [`in_task_allocation_group`].
+pub async fn exit_task_allocation_group() {
+    let _ = TASK_ALLOCATION_GROUP.try_with(|group| group.set(None));
+}
+
+/// Marks the given allocation group as the active allocation group on the current thread, returning whether it was.
+///
+/// While the active allocation group is suspended, the stack of allocation groups is borrowed, so the group is not.
+pub(super) fn enter_allocation_group(group_id: AllocationGroupId) -> bool {
+    record_allocation_group_entered(group_id);
+    LOCAL_ALLOCATION_GROUP_STACK
+        .try_with(|stack| {
+            stack
+                .try_borrow_mut()
+                .map(|mut stack| stack.push(group_id))
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+/// Restores the allocation group that was active before the last call to `enter_allocation_group`.
+pub(super) fn exit_allocation_group() {
+    let _ = LOCAL_ALLOCATION_GROUP_STACK.try_with(|stack| {
+        if let Ok(mut stack) = stack.try_borrow_mut() {
+            stack.pop();
+        }
+    });
+}
+
+/// An allocation group that is active on the current thread until this guard is dropped.
+///
+/// This is a safer alternative to [`AllocationGroupToken::enter`] and [`AllocationGroupToken::exit`], which can be left
+/// unbalanced: when a component calls a utility that makes allocations in a group of its own, dropping the guard
+/// restores the group of the component, even if the utility returns early. Like [`tracing::span::Entered`], the guard
+/// is tied to the thread it was created on, so it can't be sent to another thread.
+#[must_use = "the allocation group is only active until the guard is dropped"]
+pub struct ScopedAllocationGroup {
+    previous: AllocationGroupId,
+    entered: bool,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl ScopedAllocationGroup {
+    /// Marks the given allocation group as the active allocation group on the current thread, until the returned guard
+    /// is dropped.
+    pub fn enter(group_id: AllocationGroupId) -> Self {
+        let previous = LOCAL_ALLOCATION_GROUP_STACK
+            .try_with(|stack| {
+                // The stack is mutably borrowed while the active allocation group is suspended.
+                stack
+                    .try_borrow()
+                    .map_or(AllocationGroupId::ROOT, |stack| stack.current())
+            })
+            .unwrap_or(AllocationGroupId::ROOT);
+        let entered = enter_allocation_group(group_id);
+        Self {
+            previous,
+            entered,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl Drop for ScopedAllocationGroup {
+    fn drop(&mut self) {
+        if !self.entered {
+            return;
+        }
+        exit_allocation_group();
+        debug_assert!(
+            std::thread::panicking()
+                || LOCAL_ALLOCATION_GROUP_STACK
+                    .try_with(|stack| stack
+                        .try_borrow()
+                        .map_or(true, |stack| stack.current() == self.previous))
+                    .unwrap_or(true),
+            "allocation groups entered within a scoped allocation group were not exited"
+        );
+    }
+}
+
+/// Runs the given fallible closure with the given allocation group active on the current thread.
+///
+/// The group is exited whether the closure succeeds or fails, so the closure can use `?` and return early, and its
+/// result is returned unchanged.
+pub fn with_allocation_group<R, E, F>(group_id: AllocationGroupId, f: F) -> Result<R, E>
+where
+    F: FnOnce() -> Result<R, E>,
+{
+    let _scope = ScopedAllocationGroup::enter(group_id);
+    f()
+}
+
+/// A future that enters its allocation group on the thread that polls it, for as long as each poll lasts.
+///
+/// The executor can poll a future on any of its threads, so entering an allocation group before awaiting a future only
+/// attributes the allocations of the first poll to it. This enters the group around every poll instead, on whichever
+/// thread that poll runs. Unlike [`in_task_allocation_group`], this doesn't need to wrap the whole task, so it can
+/// attribute a single future of a component to a group of its own: the group it enters takes precedence over the group
+/// of the task that polls it, which every component of a topology runs with. Created with
+/// [`TrackAllocationsExt::track_allocations`].
+#[pin_project]
+#[must_use = "futures do nothing unless polled"]
+pub struct AllocationTrackedFuture<F> {
+    #[pin]
+    inner: F,
+    group_id: AllocationGroupId,
+}
+
+impl<F: Future> Future for AllocationTrackedFuture<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let _scope = ScopedAllocationGroup::enter(*this.group_id);
+        this.inner.poll(cx)
+    }
+}
+
+/// An extension trait for attributing the allocations of a future to an allocation group.
+pub trait TrackAllocationsExt: Future + Sized {
+    /// Wraps this future so that the allocation group of the given token is entered on every poll.
+    ///
+    /// The future only stores the ID of the group, so the token must keep the group registered for as long as the
+    /// future runs.
+    fn track_allocations(self, token: &AllocationGroupToken) -> AllocationTrackedFuture<Self> {
+        AllocationTrackedFuture {
+            inner: self,
+            group_id: token.id(),
+        }
+    }
+}
+
+impl<F: Future> TrackAllocationsExt for F {}
+
+/// The number of tokens created with [`AllocationGroupToken::clone_for_child_task`] that still own the registration of
+/// each allocation group, on top of the token it was registered with.
+static CHILD_TOKENS: [AtomicU32; NUM_GROUPS] = arr![AtomicU32::new(0); 128];
+
 /// A token that allows controlling when an allocation group is active or inactive.
+///
+/// The token owns the registration of its allocation group. It should be released with
+/// [`AllocationGroupToken::drain_and_deregister`] once the group is no longer needed: dropping a token whose group
+/// still has allocated bytes is treated as an accounting leak, which panics in debug builds and logs a warning in
+/// release builds. Either way, the group is deregistered, and its ID is reused once its bytes have been deallocated.
+///
+/// # Thread safety
+///
+/// The token is `Send` and `Sync`, so it can be stored in components that Tokio moves between threads. It only holds
+/// the ID of its group, and the statistics of the group live in static arrays of atomics, so a token can be used and
+/// dropped on any thread. Entering the group only affects the thread it is entered on, which is why the token should
+/// be exited on the thread it was entered on, or be used through [`ScopedAllocationGroup`] instead, which enforces it.
+#[must_use = "dropping without deregistering may leak allocation tracking state"]
 pub struct AllocationGroupToken {
     id: AllocationGroupId,
+    /// Whether this token owns the registration of its group, rather than having been upgraded from a
+    /// [`WeakAllocationGroupToken`].
+    owns_registration: bool,
+    /// Whether the group is drained rather than checked for leaked bytes once this token is dropped, as set by
+    /// [`AllocationGroupToken::drain_on_drop`].
+    drain_on_drop: AtomicBool,
 }
 
 impl AllocationGroupToken {
+    /// Creates a token owning the registration of the given allocation group, recording `parent` as its parent.
+    ///
+    /// The parent is what hierarchical views, such as the aggregate bytes of a group and its descendants, roll the
+    /// bytes of the group into. It is cleared once the ID of either group is reused.
+    pub fn with_parent(group_id: AllocationGroupId, parent: Option<AllocationGroupId>) -> Self {
+        if let Some(parent) = parent {
+            hierarchy::set_parent(group_id, parent);
+        }
+        Self::from(group_id)
+    }
+
+    /// Gets the ID of the allocation group controlled by this token.
+    pub const fn id(&self) -> AllocationGroupId {
+        self.id
+    }
+
+    /// Gets the parent of the allocation group controlled by this token, if it has one.
+    pub fn parent(&self) -> Option<AllocationGroupId> {
+        hierarchy::allocation_group_parent(self.id)
+    }
+
+    /// Creates a weak reference to this allocation group, which doesn't keep it registered.
+    ///
+    /// This lets observers, such as the API, hold on to a group for reading its statistics without delaying its
+    /// deregistration.
+    pub fn downgrade(&self) -> WeakAllocationGroupToken {
+        WeakAllocationGroupToken {
+            id: self.id,
+            generation: group_generation(self.id),
+        }
+    }
+
+    /// Creates a token for a child task, which shares the allocation group of this token.
+    ///
+    /// Both tokens own the registration of the group, which is only deregistered, and checked for leaked bytes, once
+    /// the last of them is dropped or deregistered, in whichever order that happens. Each token is entered and exited
+    /// on its own, so the child task can enter the group on the threads it runs on without affecting this token.
+    pub fn clone_for_child_task(&self) -> AllocationGroupToken {
+        if self.owns_registration && !self.id.is_shared() {
+            if let Some(group_idx) = self.id.slot_index() {
+                CHILD_TOKENS[group_idx].fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        AllocationGroupToken {
+            id: self.id,
+            owns_registration: self.owns_registration,
+            drain_on_drop: AtomicBool::new(false),
+        }
+    }
+
+    /// Releases the registration owned by this token, returning whether it was the last token owning it.
+    fn release_registration(&self) -> bool {
+        self.owns_registration
+            && self.id.slot_index().map_or(true, |group_idx| {
+                CHILD_TOKENS[group_idx]
+                    .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |children| {
+                        children.checked_sub(1)
+                    })
+                    .is_err()
+            })
+    }
+
     pub fn enter(&self) {
-        let _ = LOCAL_ALLOCATION_GROUP_STACK.try_with(|stack| stack.borrow_mut().push(self.id));
+        enter_allocation_group(self.id);
     }
 
     pub fn exit(&self) {
-        let _ = LOCAL_ALLOCATION_GROUP_STACK.try_with(|stack| stack.borrow_mut().pop());
+        exit_allocation_group();
+    }
+
+    /// Gets the number of bytes currently allocated by this allocation group.
+    pub fn current_allocated_bytes(&self) -> u64 {
+        current_allocated_bytes(self.id)
+    }
+
+    /// Gets the fraction of its threshold that this allocation group currently allocates, if it has a threshold.
+    pub fn memory_pressure_ratio(&self) -> Option<f64> {
+        memory_pressure_ratio(self.id)
+    }
+
+    /// Sets the human-readable name of this allocation group, which log messages about the group prefer over its ID.
+    ///
+    /// The name is cleared once the ID of the group is reused by another group.
+    pub fn set_name(&self, name: &str) {
+        set_group_name(self.id, name);
+    }
+
+    /// Gets the human-readable name of this allocation group, if it was set with [`AllocationGroupToken::set_name`].
+    pub fn name(&self) -> Option<String> {
+        group_name(self.id)
+    }
+
+    /// Gets the memory report of the component this allocation group belongs to.
+    pub fn memory_report(&self) -> ComponentMemoryReport {
+        memory_report(self.id)
+    }
+
+    /// Merges another allocation group into this one.
+    ///
+    /// The bytes currently allocated by `other` are added to this group, the tags of both groups are combined, and
+    /// `other` is deregistered so that its ID can be reused. This is only meaningful when both groups track the same
+    /// logical resource, such as two identical components that were deduplicated on reload. A token upgraded from a
+    /// [`WeakAllocationGroupToken`] doesn't own the registration of its group, so merging it does nothing, and neither
+    /// does merging a token while tokens cloned with [`AllocationGroupToken::clone_for_child_task`] still use its group.
+    pub fn merge(&self, other: AllocationGroupToken) {
+        if other.release_registration() {
+            merge_allocation_groups(self.id, other.id);
+        }
+        std::mem::forget(other);
+    }
+
+    /// Deregisters the allocation group without checking it for leaked bytes.
+    ///
+    /// Bytes that are still allocated by the group remain attributed to it until they are deallocated, after which its
+    /// ID can be reused. A token upgraded from a [`WeakAllocationGroupToken`] leaves the group registered, and so does a
+    /// token while other tokens cloned for child tasks still own the registration of its group.
+    pub fn drain_and_deregister(self) {
+        if self.release_registration() {
+            deregister_allocation_group(self.id);
+        }
+        std::mem::forget(self);
+    }
+
+    /// Deregisters the allocation group without checking it for leaked bytes once this token is dropped, as
+    /// [`AllocationGroupToken::drain_and_deregister`] does.
+    ///
+    /// This is for tokens shared behind an [`Arc`](std::sync::Arc), whose last reference may be dropped by whichever
+    /// holder outlives the others.
+    pub fn drain_on_drop(&self) {
+        self.drain_on_drop.store(true, Ordering::Relaxed);
     }
 }
 
+impl Drop for AllocationGroupToken {
+    fn drop(&mut self) {
+        // Shared allocation groups are never deregistered, an upgraded token doesn't own its registration, the tokens
+        // cloned for child tasks keep the group registered until the last of them is dropped, and a panic while
+        // unwinding would abort.
+        if self.id.is_shared() || !self.release_registration() || std::thread::panicking() {
+            return;
+        }
+        if self.drain_on_drop.load(Ordering::Relaxed) {
+            deregister_allocation_group(self.id);
+            return;
+        }
+
+        let leaked_bytes = self.current_allocated_bytes();
+        if leaked_bytes > 0 {
+            if cfg!(debug_assertions) {
+                panic!(
+                    "allocation group {} was dropped without being deregistered while {} bytes were still allocated",
+                    self.id.as_raw(),
+                    leaked_bytes
+                );
+            } else {
+                let tags = allocation_group_registration(self.id)
+                    .map(|registration| registration.tags)
+                    .unwrap_or_default();
+                let [component_id, component_type, component_kind] = component_log_fields(&tags);
+                warn!(
+                    target: GROUP_USAGE_LOG_TARGET,
+                    message = "Allocation group was dropped without being deregistered while bytes were still allocated.",
+                    group = %group_log_name(self.id),
+                    group_id = self.id.as_raw(),
+                    component_id,
+                    component_type,
+                    component_kind,
+                    leaked_bytes,
+                );
+            }
+        }
+        deregister_allocation_group(self.id);
+    }
+}
+
+// The name and bytes of the group are only looked up when the token is formatted.
+impl fmt::Debug for AllocationGroupToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("AllocationGroupToken");
+        debug.field("id", &self.id.as_raw());
+        match self.name() {
+            Some(name) => debug.field("name", &name),
+            None => debug.field("name", &format_args!("None")),
+        };
+        debug
+            .field("current_bytes", &self.current_allocated_bytes())
+            .finish()
+    }
+}
+
+impl fmt::Display for AllocationGroupToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[alloc_group:{}", self.id.as_raw())?;
+        if let Some(name) = self.name() {
+            write!(f, " {:?}", name)?;
+        }
+        write!(f, " {}]", format_bytes(self.current_allocated_bytes()))
+    }
+}
+
+// The token is `Send` and `Sync` because its fields are, rather than through an `unsafe impl`, so that adding a field
+// that is not thread-safe fails to compile here instead of being silently unsound.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<AllocationGroupToken>();
+};
+
 impl From<AllocationGroupId> for AllocationGroupToken {
     fn from(group_id: AllocationGroupId) -> Self {
-        Self { id: group_id }
+        Self {
+            id: group_id,
+            owns_registration: true,
+            drain_on_drop: AtomicBool::new(false),
+        }
+    }
+}
+
+/// A weak reference to an allocation group, which doesn't keep the group registered.
+///
+/// The slot of a deregistered group is reused by a later group, so the weak reference remembers the generation of the
+/// slot it was created in: it only upgrades while the group of that generation is still registered, and never to the
+/// group that reused its slot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WeakAllocationGroupToken {
+    id: AllocationGroupId,
+    generation: u32,
+}
+
+impl WeakAllocationGroupToken {
+    /// Gets the ID of the allocation group this weak reference was created for.
+    pub const fn id(&self) -> AllocationGroupId {
+        self.id
+    }
+
+    /// Upgrades this weak reference to a token, if its allocation group has not been deregistered since.
+    ///
+    /// The token can read the statistics of the group, but doesn't own its registration: dropping it leaves the group
+    /// registered. The group can still be deregistered while the token is held, in which case the token reads the
+    /// statistics of the drained group, or of the group that reused its slot, so it should only be held briefly.
+    pub fn upgrade(&self) -> Option<AllocationGroupToken> {
+        (group_generation(self.id) == self.generation).then_some(AllocationGroupToken {
+            id: self.id,
+            owns_registration: false,
+            drain_on_drop: AtomicBool::new(false),
+        })
     }
 }
 
@@ -103,6 +610,42 @@ impl From<AllocationGroupId> for AllocationGroupToken {
 pub(super) fn try_with_suspended_allocation_group<F>(f: F)
 where
     F: FnOnce(AllocationGroupId),
+{
+    try_with_suspended_group_stack(
+        #[inline(always)]
+        |stack| {
+            // A group entered on the thread takes precedence over the group of the task, as it was entered within the
+            // task.
+            f(match stack.current() {
+                AllocationGroupId::ROOT => task_allocation_group()
+                    .or_else(task_fallback_group)
+                    .unwrap_or(AllocationGroupId::ROOT),
+                group_id => group_id,
+            });
+        },
+    );
+}
+
+/// Calls `f` after suspending the active allocation group, if it was not already suspended, without resolving it.
+///
+/// This is `try_with_suspended_allocation_group` for the callers that already know the allocation group they trace
+/// to, such as deallocations, which are traced to the group of the allocation: the group of the task isn't looked up.
+#[inline(always)]
+pub(super) fn try_with_suspended_tracing<F>(f: F)
+where
+    F: FnOnce(),
+{
+    try_with_suspended_group_stack(
+        #[inline(always)]
+        |_| f(),
+    );
+}
+
+/// Calls `f` with the allocation group stack of the thread, borrowed to suspend it, if it was not already borrowed.
+#[inline(always)]
+fn try_with_suspended_group_stack<F>(f: F)
+where
+    F: FnOnce(&GroupStack<256>),
 {
     let _result = LOCAL_ALLOCATION_GROUP_STACK.try_with(
         #[inline(always)]
@@ -111,7 +654,7 @@ where
             // `f` if they cannot mutably borrow the current allocation group. As `try_borrow_mut` will only let one
             // mutable borrow happen at a time, the tracker logic is never reentrant.
             if let Ok(stack) = group_stack.try_borrow_mut() {
-                f(stack.current());
+                f(&stack);
             }
         },
     );
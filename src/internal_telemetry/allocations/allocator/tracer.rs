@@ -1,3 +1,6 @@
+#[cfg(feature = "allocation-latency")]
+use std::time::Duration;
+
 use super::token::AllocationGroupId;
 
 /// Traces allocations and deallocations.
@@ -25,4 +28,41 @@ pub trait Tracer {
     ///
     /// The object size is from the original layout excluding the group ID size.
     fn trace_deallocation(&self, object_size: usize, source_group_id: AllocationGroupId);
+
+    /// Traces a reallocation.
+    ///
+    /// The reallocated object stays attributed to `source_group_id`, the group ID where the original allocation
+    /// originated from, even if another group is active on the current thread.
+    ///
+    /// All allocations/deallocations that occur within the call to `Tracer::trace_reallocation` are ignored, just as
+    /// they are for `Tracer::trace_allocation`.
+    ///
+    /// The object sizes are from the original and new layouts excluding the group ID size.
+    ///
+    /// By default, this traces a deallocation of `old_size` followed by an allocation of `new_size`, both of
+    /// `source_group_id`. Implementors that count bytes can instead grow or shrink the group by the difference in size
+    /// at once, as the main tracer does.
+    fn trace_reallocation(
+        &self,
+        old_size: usize,
+        new_size: usize,
+        source_group_id: AllocationGroupId,
+    ) {
+        self.trace_deallocation(old_size, source_group_id);
+        self.trace_allocation(new_size, source_group_id);
+    }
+
+    /// Traces an allocation that the wrapped allocator failed to make, as it returned a null pointer.
+    ///
+    /// A failed reallocation is traced as a failed allocation of its new size, as the original object is left in place.
+    /// `group_id` is the group that was active when the allocation was attempted. No bytes were allocated, so this
+    /// must not be counted as an allocation. All allocations/deallocations that occur within the call are ignored, just
+    /// as they are for `Tracer::trace_allocation`. Failures are not traced by default.
+    fn trace_allocation_failure(&self, _object_size: usize, _group_id: AllocationGroupId) {}
+
+    /// Traces the time the wrapped allocator took to allocate an object.
+    ///
+    /// This is called right after `Tracer::trace_allocation`, under the same conditions.
+    #[cfg(feature = "allocation-latency")]
+    fn trace_allocation_latency(&self, latency: Duration, group_id: AllocationGroupId);
 }
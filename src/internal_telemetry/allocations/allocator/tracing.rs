@@ -1,14 +1,25 @@
 use std::{any::TypeId, marker::PhantomData, ptr::addr_of};
 
-use tracing::{Dispatch, Id, Subscriber};
+use tracing::{span::Record, Dispatch, Id, Subscriber, Value};
 use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
 
-use super::AllocationGroupToken;
+use super::token::{enter_allocation_group, exit_allocation_group, AllocationGroupId};
 
 pub(crate) struct WithAllocationGroup {
-    pub with_allocation_group: fn(&Dispatch, &Id, AllocationGroupToken),
+    pub with_allocation_group: fn(&Dispatch, &Id, AllocationGroupId),
 }
 
+/// The span field that the net bytes allocated by the allocation group of a span since it was first entered are
+/// recorded into, every time the span is exited.
+///
+/// Fields can only be recorded into a span that declares them, so only the spans created with this field, usually as
+/// `tracing::field::Empty`, have it recorded, which keeps reading the bytes of the group off the others. The bytes are
+/// those of the whole group, including what other spans of the group allocate meanwhile.
+pub const NET_ALLOCATED_BYTES_FIELD: &str = "allocation_net_bytes";
+
+/// The bytes allocated by the allocation group of a span when it was first entered.
+struct AllocatedBytesOnEnter(u64);
+
 /// [`AllocationLayer`] is a [`tracing_subscriber::Layer`] that handles entering and exiting an allocation
 /// group as the span it is attached to is itself entered and exited.
 ///
@@ -37,7 +48,7 @@ where
         }
     }
 
-    fn with_allocation_group(dispatch: &Dispatch, id: &Id, unsafe_token: AllocationGroupToken) {
+    fn with_allocation_group(dispatch: &Dispatch, id: &Id, group_id: AllocationGroupId) {
         let subscriber = dispatch
             .downcast_ref::<S>()
             .expect("subscriber should downcast to expected type; this is a bug!");
@@ -45,7 +56,7 @@ where
             .span(id)
             .expect("registry should have a span for the current ID");
 
-        span.extensions_mut().insert(unsafe_token);
+        span.extensions_mut().insert(group_id);
     }
 }
 
@@ -55,16 +66,71 @@ where
 {
     fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
         if let Some(span_ref) = ctx.span(id) {
-            if let Some(token) = span_ref.extensions().get::<AllocationGroupToken>() {
-                token.enter();
+            // Spans without an allocation group of their own get the one of their name, which is stored like an
+            // attached one so that exiting the span exits it.
+            #[cfg(feature = "allocation-tracing-span-groups")]
+            if span_ref.extensions().get::<AllocationGroupId>().is_none() {
+                if let Some(group_id) =
+                    crate::internal_telemetry::allocations::span_name_group(span_ref.name())
+                {
+                    span_ref.extensions_mut().insert(group_id);
+                }
+            }
+            let group_id = span_ref.extensions().get::<AllocationGroupId>().copied();
+            if let Some(group_id) = group_id {
+                // The bytes are only read when first entering the span, so that they cover every time it is entered.
+                let first_enter = span_ref
+                    .extensions()
+                    .get::<AllocatedBytesOnEnter>()
+                    .is_none();
+                if first_enter && span_ref.fields().field(NET_ALLOCATED_BYTES_FIELD).is_some() {
+                    let allocated_bytes =
+                        crate::internal_telemetry::allocations::current_allocated_bytes(group_id);
+                    span_ref
+                        .extensions_mut()
+                        .insert(AllocatedBytesOnEnter(allocated_bytes));
+                }
+                enter_allocation_group(group_id);
+                #[cfg(tokio_unstable)]
+                crate::internal_telemetry::allocations::record_allocation_group_task(group_id);
+            }
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span_ref) = ctx.span(id) {
+            if let Some(group_id) = span_ref.extensions().get::<AllocationGroupId>() {
+                crate::internal_telemetry::allocations::record_allocation_group_tags(
+                    *group_id, values,
+                );
             }
         }
     }
 
     fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
         if let Some(span_ref) = ctx.span(id) {
-            if let Some(token) = span_ref.extensions().get::<AllocationGroupToken>() {
-                token.exit();
+            let group_id = span_ref.extensions().get::<AllocationGroupId>().copied();
+            if let Some(group_id) = group_id {
+                exit_allocation_group();
+
+                let allocated_bytes_on_enter = span_ref
+                    .extensions()
+                    .get::<AllocatedBytesOnEnter>()
+                    .map(|on_enter| on_enter.0);
+                let field = span_ref.fields().field(NET_ALLOCATED_BYTES_FIELD);
+                if let (Some(allocated_bytes_on_enter), Some(field)) =
+                    (allocated_bytes_on_enter, field)
+                {
+                    let allocated_bytes =
+                        crate::internal_telemetry::allocations::current_allocated_bytes(group_id);
+                    let net_bytes = allocated_bytes as i64 - allocated_bytes_on_enter as i64;
+                    let values = [(&field, Some(&net_bytes as &dyn Value))];
+                    let values = span_ref.fields().value_set(&values);
+                    // Other layers may lock the extensions of the span as they record, so none may be held here.
+                    tracing::dispatcher::get_default(|dispatch| {
+                        dispatch.record(id, &Record::new(&values));
+                    });
+                }
             }
         }
     }
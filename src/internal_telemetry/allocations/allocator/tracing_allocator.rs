@@ -3,16 +3,55 @@ use std::{
     sync::atomic::Ordering,
 };
 
-use crate::internal_telemetry::allocations::TRACK_ALLOCATIONS;
+#[cfg(feature = "allocation-tracing-debug-frees")]
+use crate::internal_telemetry::allocations::debug_frees;
+use crate::internal_telemetry::allocations::{
+    is_traced_allocation, record_small_allocation, record_untracked_allocation,
+    MIN_TRACKED_SIZE_BYTES, TRACK_ALLOCATIONS,
+};
 
 use super::{
-    token::{try_with_suspended_allocation_group, AllocationGroupId},
+    token::{try_with_suspended_allocation_group, try_with_suspended_tracing, AllocationGroupId},
     tracer::Tracer,
 };
 
+/// The raw group ID stored for allocations that were too small to be traced, which are traced if they grow past the
+/// minimum.
+///
+/// Group IDs start at 1, so this can never collide with a registered allocation group.
+const UNTRACED_GROUP_ID: u8 = 0;
+
+/// The raw group ID stored for allocations that were made while the allocation group was suspended, which are never
+/// traced.
+///
+/// Group IDs are below the number of slots of allocation groups, so this can never collide with a registered
+/// allocation group either.
+const SUSPENDED_GROUP_ID: u8 = u8::MAX;
+
+/// Gets the number of bytes the tracing allocator stores along with every allocation, for its own bookkeeping.
+///
+/// This is the size of the allocation group ID that is stored after the requested bytes of each allocation, while
+/// allocations are tracked. The wrapped allocation is then padded to the alignment of the requested layout, so an
+/// allocation can use up to its alignment minus one more bytes than this: use [`allocation_overhead`] for the exact
+/// overhead of a given layout.
+pub const fn header_overhead() -> usize {
+    std::mem::size_of::<u8>()
+}
+
+/// Gets the number of bytes the tracing allocator allocates for the given layout on top of its size, while allocations
+/// are tracked.
+///
+/// This includes the bytes of [`header_overhead`], and the padding that keeps the wrapped allocation aligned. It does
+/// not include the overhead of the wrapped allocator itself, such as its size classes.
+pub fn allocation_overhead(object_layout: Layout) -> usize {
+    get_wrapped_layout(object_layout).0.size() - object_layout.size()
+}
+
 /// A tracing allocator that groups allocation events by groups.
 ///
-/// This allocator can only be used when specified via `#[global_allocator]`.
+/// This allocator can only be used when specified via `#[global_allocator]`. It only implements the stable
+/// [`GlobalAlloc`] API, and wraps any allocator that does, such as [`std::alloc::System`], so it builds on the stable
+/// toolchain the rest of Vector builds on.
 pub struct GroupedTraceableAllocator<A, T> {
     allocator: A,
     tracer: T,
@@ -26,40 +65,83 @@ impl<A, T> GroupedTraceableAllocator<A, T> {
     }
 }
 
-unsafe impl<A: GlobalAlloc, T: Tracer> GlobalAlloc for GroupedTraceableAllocator<A, T> {
-    #[inline]
-    unsafe fn alloc(&self, object_layout: Layout) -> *mut u8 {
-        if !TRACK_ALLOCATIONS.load(Ordering::Relaxed) {
-            return self.allocator.alloc(object_layout);
-        }
+impl<A, T: Tracer> GroupedTraceableAllocator<A, T> {
+    /// Traces an allocation or reallocation that the wrapped allocator failed to make, in the active allocation group.
+    #[cold]
+    fn trace_allocation_failure(&self, object_layout: Layout) {
+        try_with_suspended_allocation_group(|group_id| {
+            self.tracer
+                .trace_allocation_failure(object_layout.size(), group_id);
+        });
+    }
+}
 
+impl<A: GlobalAlloc, T: Tracer> GroupedTraceableAllocator<A, T> {
+    /// Allocates an object, with the ID of its allocation group stored after it, while allocations are tracked.
+    ///
+    /// # Safety
+    ///
+    /// This has the same requirements as [`GlobalAlloc::alloc`]. The allocation must only be reallocated and
+    /// deallocated with [`realloc_traced`](Self::realloc_traced) and [`dealloc_traced`](Self::dealloc_traced).
+    #[inline(always)]
+    pub(crate) unsafe fn alloc_traced(
+        &self,
+        object_layout: Layout,
+        min_tracked_size: usize,
+    ) -> *mut u8 {
         // Allocate our wrapped layout and make sure the allocation succeeded.
         let (actual_layout, offset_to_group_id) = get_wrapped_layout(object_layout);
+        #[cfg(feature = "allocation-latency")]
+        let started_at = std::time::Instant::now();
         let actual_ptr = self.allocator.alloc(actual_layout);
+        #[cfg(feature = "allocation-latency")]
+        let latency = started_at.elapsed();
         if actual_ptr.is_null() {
+            self.trace_allocation_failure(object_layout);
             return actual_ptr;
         }
+        #[cfg(feature = "allocation-tracing-debug-frees")]
+        debug_frees::record_allocation(actual_ptr);
 
         let group_id_ptr = actual_ptr.add(offset_to_group_id).cast::<u8>();
+        // Allocations made while the allocation group is suspended are not traced, so their deallocation must not be
+        // traced either. The group ID is overwritten below if the allocation isn't made while suspended.
+        group_id_ptr.write(SUSPENDED_GROUP_ID);
 
         let object_size = object_layout.size();
+        let wrapped_size = actual_layout.size();
 
+        let mut attributed = false;
         try_with_suspended_allocation_group(
             #[inline(always)]
             |group_id| {
+                attributed = true;
+                // Small allocations get the untraced group ID, so that their deallocation is not traced either.
+                if !is_traced_allocation(wrapped_size, min_tracked_size, group_id) {
+                    group_id_ptr.write(UNTRACED_GROUP_ID);
+                    record_small_allocation(object_size, group_id);
+                    return;
+                }
                 group_id_ptr.write(group_id.as_raw());
                 self.tracer.trace_allocation(object_size, group_id);
+                #[cfg(feature = "allocation-latency")]
+                self.tracer.trace_allocation_latency(latency, group_id);
             },
         );
+        // The allocation group was suspended, so the allocation is not attributed to any.
+        if !attributed {
+            record_untracked_allocation(object_size);
+        }
         actual_ptr
     }
 
-    #[inline]
-    unsafe fn dealloc(&self, object_ptr: *mut u8, object_layout: Layout) {
-        if !TRACK_ALLOCATIONS.load(Ordering::Relaxed) {
-            self.allocator.dealloc(object_ptr, object_layout);
-            return;
-        }
+    /// Deallocates an object allocated with [`alloc_traced`](Self::alloc_traced).
+    ///
+    /// # Safety
+    ///
+    /// This has the same requirements as [`GlobalAlloc::dealloc`].
+    #[inline(always)]
+    pub(crate) unsafe fn dealloc_traced(&self, object_ptr: *mut u8, object_layout: Layout) {
         // Regenerate the wrapped layout so we know where we have to look, as the pointer we've given relates to the
         // requested layout, not the wrapped layout that was actually allocated.
         let (wrapped_layout, offset_to_group_id) = get_wrapped_layout(object_layout);
@@ -69,16 +151,156 @@ unsafe impl<A: GlobalAlloc, T: Tracer> GlobalAlloc for GroupedTraceableAllocator
         // Deallocate before tracking, just to make sure we're reclaiming memory as soon as possible.
         self.allocator.dealloc(object_ptr, wrapped_layout);
 
+        if raw_group_id == UNTRACED_GROUP_ID || raw_group_id == SUSPENDED_GROUP_ID {
+            return;
+        }
+
         let object_size = object_layout.size();
         let source_group_id = AllocationGroupId::from_raw(raw_group_id);
 
-        try_with_suspended_allocation_group(
+        try_with_suspended_tracing(
             #[inline(always)]
-            |_| {
+            || {
                 self.tracer.trace_deallocation(object_size, source_group_id);
             },
         );
     }
+
+    /// Reallocates an object allocated with [`alloc_traced`](Self::alloc_traced).
+    ///
+    /// # Safety
+    ///
+    /// This has the same requirements as [`GlobalAlloc::realloc`].
+    #[inline(always)]
+    pub(crate) unsafe fn realloc_traced(
+        &self,
+        object_ptr: *mut u8,
+        object_layout: Layout,
+        new_size: usize,
+        min_tracked_size: usize,
+    ) -> *mut u8 {
+        let (wrapped_layout, offset_to_group_id) = get_wrapped_layout(object_layout);
+        let raw_group_id = object_ptr.add(offset_to_group_id).cast::<u8>().read();
+
+        // The group ID is stored after the object, so it moves along with the end of the object.
+        let new_object_layout = Layout::from_size_align_unchecked(new_size, object_layout.align());
+        let (new_wrapped_layout, new_offset_to_group_id) = get_wrapped_layout(new_object_layout);
+        let new_ptr = self
+            .allocator
+            .realloc(object_ptr, wrapped_layout, new_wrapped_layout.size());
+        if new_ptr.is_null() {
+            self.trace_allocation_failure(new_object_layout);
+            return new_ptr;
+        }
+        #[cfg(feature = "allocation-tracing-debug-frees")]
+        record_debug_reallocation(object_ptr, new_ptr);
+        let new_group_id_ptr = new_ptr.add(new_offset_to_group_id).cast::<u8>();
+        new_group_id_ptr.write(raw_group_id);
+
+        // The reallocated object stays in the group of the original allocation, which only changes by the difference in
+        // size.
+        if raw_group_id != UNTRACED_GROUP_ID && raw_group_id != SUSPENDED_GROUP_ID {
+            let old_size = object_layout.size();
+            let source_group_id = AllocationGroupId::from_raw(raw_group_id);
+
+            try_with_suspended_tracing(
+                #[inline(always)]
+                || {
+                    self.tracer
+                        .trace_reallocation(old_size, new_size, source_group_id);
+                },
+            );
+        } else if raw_group_id == UNTRACED_GROUP_ID
+            && new_wrapped_layout.size() > wrapped_layout.size()
+        {
+            // An allocation that was too small to be traced is traced once it grows past the minimum, as most large
+            // buffers start small, in the active allocation group. Its original bytes were never counted, so the
+            // whole object is counted as a new allocation.
+            let new_wrapped_size = new_wrapped_layout.size();
+            try_with_suspended_allocation_group(
+                #[inline(always)]
+                |group_id| {
+                    if is_traced_allocation(new_wrapped_size, min_tracked_size, group_id) {
+                        new_group_id_ptr.write(group_id.as_raw());
+                        self.tracer.trace_allocation(new_size, group_id);
+                    }
+                },
+            );
+        }
+        new_ptr
+    }
+}
+
+unsafe impl<A: GlobalAlloc, T: Tracer> GlobalAlloc for GroupedTraceableAllocator<A, T> {
+    #[inline]
+    unsafe fn alloc(&self, object_layout: Layout) -> *mut u8 {
+        if !TRACK_ALLOCATIONS.load(Ordering::Relaxed) {
+            let ptr = self.allocator.alloc(object_layout);
+            // Failures are rare, and an early warning of running out of memory, so they are traced regardless.
+            if ptr.is_null() {
+                self.trace_allocation_failure(object_layout);
+            } else {
+                record_untracked_allocation(object_layout.size());
+            }
+            #[cfg(feature = "allocation-tracing-debug-frees")]
+            debug_frees::record_allocation(ptr);
+            return ptr;
+        }
+        self.alloc_traced(
+            object_layout,
+            MIN_TRACKED_SIZE_BYTES.load(Ordering::Relaxed),
+        )
+    }
+
+    #[inline]
+    unsafe fn dealloc(&self, object_ptr: *mut u8, object_layout: Layout) {
+        #[cfg(feature = "allocation-tracing-debug-frees")]
+        debug_frees::record_free(object_ptr);
+        if !TRACK_ALLOCATIONS.load(Ordering::Relaxed) {
+            self.allocator.dealloc(object_ptr, object_layout);
+            return;
+        }
+        self.dealloc_traced(object_ptr, object_layout);
+    }
+
+    #[inline]
+    unsafe fn realloc(
+        &self,
+        object_ptr: *mut u8,
+        object_layout: Layout,
+        new_size: usize,
+    ) -> *mut u8 {
+        if !TRACK_ALLOCATIONS.load(Ordering::Relaxed) {
+            let new_ptr = self.allocator.realloc(object_ptr, object_layout, new_size);
+            if new_ptr.is_null() {
+                self.trace_allocation_failure(Layout::from_size_align_unchecked(
+                    new_size,
+                    object_layout.align(),
+                ));
+            }
+            #[cfg(feature = "allocation-tracing-debug-frees")]
+            record_debug_reallocation(object_ptr, new_ptr);
+            return new_ptr;
+        }
+        self.realloc_traced(
+            object_ptr,
+            object_layout,
+            new_size,
+            MIN_TRACKED_SIZE_BYTES.load(Ordering::Relaxed),
+        )
+    }
+}
+
+/// Records that a successful reallocation freed `old_ptr` and handed out `new_ptr`, which may be the same pointer.
+///
+/// A failed reallocation leaves the original allocation in place, so nothing is recorded for it.
+#[cfg(feature = "allocation-tracing-debug-frees")]
+#[inline(always)]
+fn record_debug_reallocation(old_ptr: *mut u8, new_ptr: *mut u8) {
+    if !new_ptr.is_null() {
+        debug_frees::record_free(old_ptr);
+        debug_frees::record_allocation(new_ptr);
+    }
 }
 
 #[inline(always)]
@@ -0,0 +1,291 @@
+//! Enforcement of the actions taken against allocation groups that exceed their threshold, for the actions that apply
+//! to the events of a group rather than to the processor.
+
+use std::{
+    sync::{
+        atomic::{AtomicU8, AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use arr_macro::arr;
+use tokio::time::Instant;
+use vector_core::event::LogEvent;
+
+use super::{
+    current_allocation_group, AllocationBudgetExceededAction, AllocationGroupId, NUM_GROUPS,
+    PROCESSOR_SHUTDOWN,
+};
+
+/// How often the events held back by an allocation group that applies back pressure check its budget again.
+const BACK_PRESSURE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long the events held back by an allocation group that applies back pressure wait at most, before they are sent
+/// regardless.
+const BACK_PRESSURE_MAX_WAIT: Duration = Duration::from_secs(30);
+
+const NOT_ENFORCED: u8 = 0;
+const APPLY_BACK_PRESSURE: u8 = 1;
+const DROP_EVENTS: u8 = 2;
+const PANIC_IN_DEBUG: u8 = 3;
+const EMIT_EVENT: u8 = 4;
+
+/// The action currently enforced against the events of each allocation group, as last set by the processor.
+static GROUP_BUDGET_ENFORCEMENT: [AtomicU8; NUM_GROUPS] = arr![AtomicU8::new(NOT_ENFORCED); 128];
+
+/// The number of allocation groups that an action is currently enforced against the events of.
+static ENFORCED_GROUPS: AtomicUsize = AtomicUsize::new(0);
+
+/// The memory pressure event that each allocation group sends along with its next events.
+static PENDING_PRESSURE_EVENTS: [Mutex<Option<MemoryPressureEvent>>; NUM_GROUPS] =
+    arr![Mutex::new(None); 128];
+
+/// An event sent by a source whose allocation group exceeds its threshold, with the
+/// [`AllocationBudgetExceededAction::EmitEvent`] action.
+///
+/// It is sent as a log event with the fields `type`, which is always `memory_pressure`, `component`, `allocated_bytes`
+/// and `threshold`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryPressureEvent {
+    /// The ID of the component of the allocation group.
+    pub component_id: String,
+
+    /// The number of bytes the allocation group allocated when it exceeded its threshold.
+    pub allocated_bytes: u64,
+
+    /// The threshold of the allocation group.
+    pub threshold_bytes: u64,
+}
+
+impl From<MemoryPressureEvent> for LogEvent {
+    fn from(event: MemoryPressureEvent) -> Self {
+        let mut log = LogEvent::default();
+        log.insert("type", "memory_pressure");
+        log.insert("component", event.component_id);
+        log.insert(
+            "allocated_bytes",
+            i64::try_from(event.allocated_bytes).unwrap_or(i64::MAX),
+        );
+        log.insert(
+            "threshold",
+            i64::try_from(event.threshold_bytes).unwrap_or(i64::MAX),
+        );
+        log
+    }
+}
+
+/// Sets the action enforced against the events of an allocation group, which only applies to the actions that concern
+/// events: the others stop any enforcement.
+///
+/// Returns whether the action was not already enforced against the group.
+pub(super) fn set_budget_enforcement(
+    group_idx: usize,
+    action: Option<AllocationBudgetExceededAction>,
+) -> bool {
+    let enforcement = match action {
+        Some(AllocationBudgetExceededAction::ApplyBackPressure) => APPLY_BACK_PRESSURE,
+        Some(AllocationBudgetExceededAction::DropEvents) => DROP_EVENTS,
+        Some(AllocationBudgetExceededAction::PanicInDebug) if cfg!(debug_assertions) => {
+            PANIC_IN_DEBUG
+        }
+        Some(AllocationBudgetExceededAction::EmitEvent) => EMIT_EVENT,
+        _ => NOT_ENFORCED,
+    };
+    let previous = GROUP_BUDGET_ENFORCEMENT[group_idx].swap(enforcement, Ordering::Relaxed);
+    match (previous == NOT_ENFORCED, enforcement == NOT_ENFORCED) {
+        (true, false) => {
+            ENFORCED_GROUPS.fetch_add(1, Ordering::Relaxed);
+        }
+        (false, true) => {
+            ENFORCED_GROUPS.fetch_sub(1, Ordering::Relaxed);
+        }
+        _ => {}
+    }
+    previous != enforcement
+}
+
+/// Gets whether an action is enforced against the events of any allocation group.
+///
+/// Sources check this before each send, so that they only look up the budget of their allocation group while some
+/// group exceeds its threshold with an action that concerns events.
+pub fn any_budget_enforced() -> bool {
+    ENFORCED_GROUPS.load(Ordering::Relaxed) != 0
+}
+
+/// Queues the memory pressure event that an allocation group sends along with its next events, replacing any event it
+/// has not sent yet.
+pub(super) fn queue_memory_pressure_event(group_idx: usize, event: MemoryPressureEvent) {
+    *PENDING_PRESSURE_EVENTS[group_idx].lock().unwrap() = Some(event);
+}
+
+/// Takes the memory pressure event that the active allocation group should send along with its events, if any.
+///
+/// Sources call this as they send events, so the event is only looked up while the group exceeds its threshold with
+/// the [`AllocationBudgetExceededAction::EmitEvent`] action.
+pub fn take_memory_pressure_event() -> Option<MemoryPressureEvent> {
+    let group_idx = current_allocation_group().slot_index()?;
+    if GROUP_BUDGET_ENFORCEMENT[group_idx].load(Ordering::Relaxed) != EMIT_EVENT {
+        return None;
+    }
+    PENDING_PRESSURE_EVENTS[group_idx].lock().unwrap().take()
+}
+
+/// Gets the action currently enforced against the events of the given allocation group, if any.
+pub fn budget_enforcement(group_id: AllocationGroupId) -> Option<AllocationBudgetExceededAction> {
+    let group_idx = group_id.slot_index()?;
+    match GROUP_BUDGET_ENFORCEMENT[group_idx].load(Ordering::Relaxed) {
+        APPLY_BACK_PRESSURE => Some(AllocationBudgetExceededAction::ApplyBackPressure),
+        DROP_EVENTS => Some(AllocationBudgetExceededAction::DropEvents),
+        PANIC_IN_DEBUG => Some(AllocationBudgetExceededAction::PanicInDebug),
+        EMIT_EVENT => Some(AllocationBudgetExceededAction::EmitEvent),
+        _ => None,
+    }
+}
+
+/// Waits until the active allocation group may send events, returning whether they should be sent or dropped.
+///
+/// This holds the events back while the group applies back pressure, for at most [`BACK_PRESSURE_MAX_WAIT`]: the
+/// events are sent once the group frees enough memory, once the wait times out, or once the processor, which lifts the
+/// back pressure, shuts down.
+pub async fn admit_events_within_budget() -> bool {
+    let group_id = current_allocation_group();
+    let deadline = Instant::now() + BACK_PRESSURE_MAX_WAIT;
+    loop {
+        match budget_enforcement(group_id) {
+            Some(AllocationBudgetExceededAction::ApplyBackPressure) => {
+                if PROCESSOR_SHUTDOWN.load(Ordering::Acquire) {
+                    return true;
+                }
+                if Instant::now() >= deadline {
+                    warn!(
+                        message = "Sending events held back by allocation group back pressure for too long.",
+                        group_id = group_id.as_raw(),
+                        internal_log_rate_limit = true,
+                    );
+                    return true;
+                }
+                tokio::time::sleep(BACK_PRESSURE_POLL_INTERVAL).await;
+            }
+            Some(AllocationBudgetExceededAction::DropEvents) => return false,
+            Some(AllocationBudgetExceededAction::PanicInDebug) => {
+                error!(
+                    message = "Dropping events of allocation group exceeding its allocated bytes threshold.",
+                    group_id = group_id.as_raw(),
+                    internal_log_rate_limit = true,
+                );
+                return false;
+            }
+            _ => return true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal_telemetry::allocations::{tests::TestGroup, ScopedAllocationGroup};
+
+    #[test]
+    fn only_enforces_actions_on_events() {
+        let group = TestGroup::register(83);
+        let group_id = group.id();
+        let group_idx = group_id.as_raw() as usize;
+
+        set_budget_enforcement(group_idx, Some(AllocationBudgetExceededAction::EmitMetric));
+        assert_eq!(budget_enforcement(group_id), None);
+
+        set_budget_enforcement(group_idx, Some(AllocationBudgetExceededAction::DropEvents));
+        assert_eq!(
+            budget_enforcement(group_id),
+            Some(AllocationBudgetExceededAction::DropEvents)
+        );
+        assert!(any_budget_enforced());
+        {
+            let _scope = ScopedAllocationGroup::enter(group_id);
+            assert!(!futures::executor::block_on(admit_events_within_budget()));
+        }
+
+        set_budget_enforcement(group_idx, None);
+        assert_eq!(budget_enforcement(group_id), None);
+        let _scope = ScopedAllocationGroup::enter(group_id);
+        assert!(futures::executor::block_on(admit_events_within_budget()));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn drops_events_instead_of_panicking_in_debug() {
+        let group = TestGroup::register(56);
+        let group_id = group.id();
+        let group_idx = group_id.as_raw() as usize;
+        set_budget_enforcement(
+            group_idx,
+            Some(AllocationBudgetExceededAction::PanicInDebug),
+        );
+        {
+            let _scope = ScopedAllocationGroup::enter(group_id);
+            assert!(!futures::executor::block_on(admit_events_within_budget()));
+        }
+    }
+
+    #[test]
+    fn sends_memory_pressure_events_once_per_crossing() {
+        let group = TestGroup::register(72);
+        let group_id = group.id();
+        let group_idx = group_id.as_raw() as usize;
+        let _scope = ScopedAllocationGroup::enter(group_id);
+        let event = MemoryPressureEvent {
+            component_id: "in".to_string(),
+            allocated_bytes: 2000,
+            threshold_bytes: 1000,
+        };
+
+        assert!(set_budget_enforcement(
+            group_idx,
+            Some(AllocationBudgetExceededAction::EmitEvent)
+        ));
+        queue_memory_pressure_event(group_idx, event.clone());
+        assert!(futures::executor::block_on(admit_events_within_budget()));
+        assert_eq!(take_memory_pressure_event(), Some(event.clone()));
+        assert_eq!(take_memory_pressure_event(), None);
+        assert!(!set_budget_enforcement(
+            group_idx,
+            Some(AllocationBudgetExceededAction::EmitEvent)
+        ));
+
+        let log = LogEvent::from(event);
+        assert_eq!(log["type"], "memory_pressure".into());
+        assert_eq!(log["component"], "in".into());
+        assert_eq!(log["allocated_bytes"], 2000_i64.into());
+        assert_eq!(log["threshold"], 1000_i64.into());
+
+        // Events queued while the action is no longer enforced are not sent.
+        set_budget_enforcement(group_idx, None);
+        queue_memory_pressure_event(
+            group_idx,
+            MemoryPressureEvent {
+                component_id: "in".to_string(),
+                allocated_bytes: 2000,
+                threshold_bytes: 1000,
+            },
+        );
+        assert_eq!(take_memory_pressure_event(), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn back_pressure_times_out() {
+        let group = TestGroup::register(55);
+        let group_id = group.id();
+        let group_idx = group_id.as_raw() as usize;
+        set_budget_enforcement(
+            group_idx,
+            Some(AllocationBudgetExceededAction::ApplyBackPressure),
+        );
+        let started_at = Instant::now();
+        {
+            let _scope = ScopedAllocationGroup::enter(group_id);
+            assert!(admit_events_within_budget().await);
+        }
+        assert!(started_at.elapsed() >= BACK_PRESSURE_MAX_WAIT);
+    }
+}
@@ -0,0 +1,190 @@
+//! Memory usage of the cgroup of the process, which is what the memory limit of a container applies to.
+
+#[cfg(target_os = "linux")]
+use std::path::{Path, PathBuf};
+
+use super::AllocationMetricSink;
+
+/// The directory of the cgroup v2 hierarchy, as seen from within a container.
+#[cfg(target_os = "linux")]
+const CGROUP_DIR: &str = "/sys/fs/cgroup";
+
+/// The fraction of the cgroup memory limit below which the headroom is warned about by default.
+pub const DEFAULT_HEADROOM_WARNING_RATIO: f64 = 0.1;
+
+/// The memory used by a cgroup.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CgroupMemory {
+    /// The number of bytes used by the cgroup.
+    pub used_bytes: u64,
+
+    /// The number of bytes the cgroup may use, or `None` if it is unlimited.
+    pub limit_bytes: Option<u64>,
+}
+
+impl CgroupMemory {
+    /// Gets the number of bytes the cgroup may still use before reaching its limit, or `None` if it is unlimited.
+    pub const fn headroom_bytes(&self) -> Option<u64> {
+        match self.limit_bytes {
+            Some(limit_bytes) => Some(limit_bytes.saturating_sub(self.used_bytes)),
+            None => None,
+        }
+    }
+}
+
+/// Reports the memory used by the cgroup v2 of the process, warning when it is about to reach its limit.
+///
+/// Only Linux has cgroups, so on other platforms no memory is ever read.
+pub struct CgroupMemoryTracker {
+    #[cfg(target_os = "linux")]
+    dir: PathBuf,
+    headroom_warning_ratio: f64,
+    headroom_warning_emitted: bool,
+}
+
+impl CgroupMemoryTracker {
+    /// Creates a tracker that warns once the headroom drops below `headroom_warning_ratio` of the limit.
+    // This can only be `const` on platforms without cgroups.
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn new(headroom_warning_ratio: f64) -> Self {
+        Self {
+            #[cfg(target_os = "linux")]
+            dir: PathBuf::from(CGROUP_DIR),
+            headroom_warning_ratio,
+            headroom_warning_emitted: false,
+        }
+    }
+
+    /// Reads the memory currently used by the cgroup, if the process is in a cgroup v2 hierarchy.
+    #[cfg(target_os = "linux")]
+    pub fn read(&self) -> Option<CgroupMemory> {
+        read_cgroup_memory(&self.dir)
+    }
+
+    /// Reads the memory currently used by the cgroup, which is never available on this platform.
+    #[cfg(not(target_os = "linux"))]
+    pub const fn read(&self) -> Option<CgroupMemory> {
+        None
+    }
+
+    /// Emits the memory used by the cgroup, and warns when its headroom drops below the configured fraction.
+    pub(super) fn report(
+        &mut self,
+        metric_sink: &dyn AllocationMetricSink,
+        instance_labels: &[(String, String)],
+    ) {
+        let memory = match self.read() {
+            Some(memory) => memory,
+            None => return,
+        };
+        metric_sink.record_gauge(
+            "cgroup_memory_used_bytes",
+            memory.used_bytes as f64,
+            instance_labels,
+        );
+        let (limit_bytes, headroom_bytes) = match (memory.limit_bytes, memory.headroom_bytes()) {
+            (Some(limit_bytes), Some(headroom_bytes)) => (limit_bytes, headroom_bytes),
+            _ => return,
+        };
+        metric_sink.record_gauge(
+            "cgroup_memory_limit_bytes",
+            limit_bytes as f64,
+            instance_labels,
+        );
+        metric_sink.record_gauge(
+            "cgroup_memory_headroom_bytes",
+            headroom_bytes as f64,
+            instance_labels,
+        );
+
+        let headroom_low = self.headroom_low(&memory);
+        if headroom_low && !self.headroom_warning_emitted {
+            warn!(
+                message = "Cgroup memory headroom is low. Vector may be killed for running out of memory.",
+                used_bytes = memory.used_bytes,
+                limit_bytes,
+                headroom_bytes,
+            );
+        }
+        self.headroom_warning_emitted = headroom_low;
+    }
+
+    fn headroom_low(&self, memory: &CgroupMemory) -> bool {
+        match (memory.limit_bytes, memory.headroom_bytes()) {
+            (Some(limit_bytes), Some(headroom_bytes)) => {
+                (headroom_bytes as f64) < limit_bytes as f64 * self.headroom_warning_ratio
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn read_cgroup_memory(dir: &Path) -> Option<CgroupMemory> {
+    let current = std::fs::read_to_string(dir.join("memory.current")).ok()?;
+    let max = std::fs::read_to_string(dir.join("memory.max")).ok()?;
+    parse_cgroup_memory(&current, &max)
+}
+
+#[cfg(any(target_os = "linux", test))]
+fn parse_cgroup_memory(current: &str, max: &str) -> Option<CgroupMemory> {
+    let used_bytes = current.trim().parse().ok()?;
+    let limit_bytes = match max.trim() {
+        "max" => None,
+        max => Some(max.parse().ok()?),
+    };
+    Some(CgroupMemory {
+        used_bytes,
+        limit_bytes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cgroup_memory() {
+        assert_eq!(
+            parse_cgroup_memory("1024\n", "4096\n"),
+            Some(CgroupMemory {
+                used_bytes: 1024,
+                limit_bytes: Some(4096),
+            })
+        );
+        assert_eq!(
+            parse_cgroup_memory("1024\n", "max\n"),
+            Some(CgroupMemory {
+                used_bytes: 1024,
+                limit_bytes: None,
+            })
+        );
+        assert_eq!(parse_cgroup_memory("", "max\n"), None);
+    }
+
+    #[test]
+    fn detects_low_headroom() {
+        let tracker = CgroupMemoryTracker::new(DEFAULT_HEADROOM_WARNING_RATIO);
+        let memory = |used_bytes, limit_bytes| CgroupMemory {
+            used_bytes,
+            limit_bytes,
+        };
+
+        assert!(!tracker.headroom_low(&memory(800, Some(1000))));
+        assert!(tracker.headroom_low(&memory(950, Some(1000))));
+        assert!(tracker.headroom_low(&memory(1100, Some(1000))));
+        assert!(!tracker.headroom_low(&memory(1100, None)));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn reads_cgroup_memory_files() {
+        let dir = crate::test_util::temp_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("memory.current"), "2048\n").unwrap();
+        std::fs::write(dir.join("memory.max"), "8192\n").unwrap();
+
+        let memory = read_cgroup_memory(&dir).unwrap();
+        assert_eq!(memory.headroom_bytes(), Some(6144));
+    }
+}
@@ -0,0 +1,59 @@
+//! The allocation tracing options of the `internal_telemetry.allocations` section of the configuration, which are applied
+//! to the running processor at startup and again on every reload.
+
+use std::sync::atomic::Ordering;
+
+use once_cell::sync::OnceCell;
+
+use super::{
+    history, without_allocation_tracing, ALLOCATION_TRACING_INITIALIZED,
+    DEFAULT_REPORTING_INTERVAL_MS, MAX_TRACKING_OVERHEAD_BYTES, MIN_TRACKED_SIZE_BYTES,
+    REPORTING_INTERVAL_MS,
+};
+use crate::config::internal_telemetry::AllocationTracingConfigFromFile;
+
+/// The options set on the command line or through environment variables, which take precedence over the configuration.
+static CONFIG_OVERRIDES: OnceCell<AllocationTracingConfigFromFile> = OnceCell::new();
+
+/// Sets the allocation tracing options that take precedence over those of the configuration.
+///
+/// Only the first call has any effect.
+pub fn set_allocation_tracing_config_overrides(overrides: AllocationTracingConfigFromFile) {
+    let _ = CONFIG_OVERRIDES.set(overrides);
+}
+
+/// Applies the allocation tracing options of the configuration, along with their overrides, to the running processor.
+///
+/// Every option is read by the processor or the allocator as it goes, so this takes effect without restarting the
+/// processor. An option that is not set goes back to its default. This does nothing unless allocation tracing is
+/// initialized.
+pub fn apply_allocation_tracing_config(config: AllocationTracingConfigFromFile) {
+    if !ALLOCATION_TRACING_INITIALIZED.load(Ordering::Relaxed) {
+        return;
+    }
+    let config = config.with_overrides(CONFIG_OVERRIDES.get().copied().unwrap_or_default());
+    without_allocation_tracing(|| {
+        REPORTING_INTERVAL_MS.store(
+            config
+                .reporting_interval_ms
+                .unwrap_or(DEFAULT_REPORTING_INTERVAL_MS),
+            Ordering::Relaxed,
+        );
+        MIN_TRACKED_SIZE_BYTES.store(
+            config.min_tracked_size_bytes.unwrap_or(0),
+            Ordering::Relaxed,
+        );
+        MAX_TRACKING_OVERHEAD_BYTES.store(
+            config.max_overhead_bytes.unwrap_or(u64::MAX),
+            Ordering::Relaxed,
+        );
+        let history_len = config.history_len.unwrap_or(history::DEFAULT_HISTORY_LEN);
+        if history::set_history_len(history_len) {
+            warn!(
+                message = "Allocation group history length is too large, and was capped.",
+                history_len,
+                max_history_len = history::MAX_HISTORY_LEN,
+            );
+        }
+    });
+}
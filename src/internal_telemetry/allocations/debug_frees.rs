@@ -0,0 +1,139 @@
+//! Detection of pointers freed twice, for hunting memory-management bugs in custom components.
+//!
+//! With the `allocation-tracing-debug-frees` feature, the tracing allocator remembers the pointers each thread recently
+//! freed, in a bounded ring per thread, and forgets a pointer once the wrapped allocator hands it out again. Freeing a
+//! pointer that is still in the ring of the thread is a double free. The allocator can't log, so it only counts them,
+//! and the processor logs a warning on its next emission.
+//!
+//! The ring is bounded, so a double free is only detected if the pointer was freed recently, and on the same thread. A
+//! bounded ring also can't tell a pointer that was never allocated from one that was allocated long ago or on another
+//! thread, and such frees are not warned about.
+
+use std::{
+    cell::RefCell,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+
+/// The number of recently freed pointers each thread remembers.
+const RECENTLY_FREED: usize = 256;
+
+/// The pointers a thread recently freed, oldest first from `next`.
+struct RecentlyFreed {
+    pointers: [usize; RECENTLY_FREED],
+    next: usize,
+}
+
+impl RecentlyFreed {
+    const fn new() -> Self {
+        Self {
+            pointers: [0; RECENTLY_FREED],
+            next: 0,
+        }
+    }
+
+    /// Forgets the given pointer, which the wrapped allocator handed out again.
+    fn allocated(&mut self, ptr: usize) {
+        for freed in self.pointers.iter_mut().filter(|freed| **freed == ptr) {
+            *freed = 0;
+        }
+    }
+
+    /// Remembers the given pointer as freed, returning whether it already was.
+    fn freed(&mut self, ptr: usize) -> bool {
+        if self.pointers.contains(&ptr) {
+            return true;
+        }
+        self.pointers[self.next] = ptr;
+        self.next = (self.next + 1) % RECENTLY_FREED;
+        false
+    }
+}
+
+thread_local! {
+    static RECENTLY_FREED_POINTERS: RefCell<RecentlyFreed> = const { RefCell::new(RecentlyFreed::new()) };
+}
+
+/// The number of double frees detected since the processor's last emission.
+static DOUBLE_FREES: AtomicU64 = AtomicU64::new(0);
+
+/// The pointer of the most recently detected double free.
+static LAST_DOUBLE_FREE: AtomicUsize = AtomicUsize::new(0);
+
+/// Records that the wrapped allocator handed out the given pointer.
+#[inline(always)]
+pub(super) fn record_allocation(ptr: *mut u8) {
+    if ptr.is_null() {
+        return;
+    }
+    let _ = RECENTLY_FREED_POINTERS.try_with(|recently_freed| {
+        if let Ok(mut recently_freed) = recently_freed.try_borrow_mut() {
+            recently_freed.allocated(ptr as usize);
+        }
+    });
+}
+
+/// Records that the given pointer is being freed, counting a double free if this thread recently freed it already.
+#[inline(always)]
+pub(super) fn record_free(ptr: *mut u8) {
+    let double_free = RECENTLY_FREED_POINTERS
+        .try_with(|recently_freed| {
+            recently_freed
+                .try_borrow_mut()
+                .map_or(false, |mut recently_freed| {
+                    recently_freed.freed(ptr as usize)
+                })
+        })
+        .unwrap_or(false);
+    if double_free {
+        DOUBLE_FREES.fetch_add(1, Ordering::Relaxed);
+        LAST_DOUBLE_FREE.store(ptr as usize, Ordering::Relaxed);
+    }
+}
+
+/// Takes the number of double frees detected since the last call, along with the pointer of the most recent one.
+fn take_double_frees() -> Option<(u64, usize)> {
+    match DOUBLE_FREES.swap(0, Ordering::Relaxed) {
+        0 => None,
+        count => Some((count, LAST_DOUBLE_FREE.load(Ordering::Relaxed))),
+    }
+}
+
+/// Logs a warning if double frees were detected since the processor's last emission.
+pub(super) fn warn_double_frees() {
+    if let Some((count, last_pointer)) = take_double_frees() {
+        warn!(
+            message = "Pointers were freed twice.",
+            count,
+            last_pointer = format_args!("{:#x}", last_pointer),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_pointers_freed_twice() {
+        let mut recently_freed = RecentlyFreed::new();
+        assert!(!recently_freed.freed(0x1000));
+        assert!(recently_freed.freed(0x1000));
+
+        // A pointer handed out again can be freed again.
+        recently_freed.allocated(0x1000);
+        assert!(!recently_freed.freed(0x1000));
+    }
+
+    #[test]
+    fn counts_double_frees_for_the_processor() {
+        // The pointer is only recorded, never dereferenced, so no memory is actually freed twice.
+        let ptr = 0xdead_0000 as *mut u8;
+        record_allocation(ptr);
+        record_free(ptr);
+        record_free(ptr);
+
+        let (count, last_pointer) = take_double_frees().unwrap();
+        assert!(count >= 1);
+        assert_eq!(last_pointer, ptr as usize);
+    }
+}
@@ -0,0 +1,154 @@
+//! Reports of the change in the bytes allocated by each allocation group between two baselines, for benchmarks and
+//! tests that bound how much an operation allocates.
+
+use std::fmt;
+
+use super::{AllocationGroupId, Baseline};
+
+/// The bytes allocated by an allocation group in two baselines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GroupDiff {
+    /// The ID of the allocation group.
+    pub group_id: AllocationGroupId,
+
+    /// The number of bytes allocated by the group in the earlier baseline.
+    pub before: u64,
+
+    /// The number of bytes allocated by the group in the later baseline.
+    pub after: u64,
+
+    /// The number of bytes allocated between the baselines, or deallocated if negative.
+    pub delta: i64,
+}
+
+/// The change in the bytes allocated by every allocation group whose allocated bytes changed between two baselines.
+///
+/// It displays as a table with a row per allocation group.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DiffReport {
+    /// The allocation groups whose allocated bytes changed, by group ID.
+    pub groups: Vec<GroupDiff>,
+}
+
+impl Baseline {
+    /// Compares this baseline with a later one.
+    pub fn diff(&self, after: &Baseline) -> DiffReport {
+        let groups = self
+            .live_bytes
+            .iter()
+            .zip(&after.live_bytes)
+            .enumerate()
+            .skip(1)
+            .filter(|(_, (before, after))| before != after)
+            .map(|(group_idx, (before, after))| GroupDiff {
+                group_id: AllocationGroupId::from_raw(group_idx as u8),
+                before: *before,
+                after: *after,
+                delta: *after as i64 - *before as i64,
+            })
+            .collect();
+        DiffReport { groups }
+    }
+}
+
+impl DiffReport {
+    /// Gets the change in the bytes allocated by the given allocation group, which is zero if they did not change.
+    pub fn delta(&self, group_id: AllocationGroupId) -> i64 {
+        self.groups
+            .iter()
+            .find(|group| group.group_id == group_id)
+            .map_or(0, |group| group.delta)
+    }
+
+    /// Asserts that the given allocation group allocated no more than `max_bytes` between the baselines.
+    ///
+    /// # Panics
+    ///
+    /// Panics, with the whole report, if the group allocated more than `max_bytes`.
+    #[track_caller]
+    pub fn assert_max_allocation_for_group(&self, group_id: AllocationGroupId, max_bytes: u64) {
+        let delta = self.delta(group_id);
+        assert!(
+            delta <= max_bytes as i64,
+            "allocation group {} allocated {} bytes, more than the maximum of {} bytes:\n{}",
+            group_id.as_raw(),
+            delta,
+            max_bytes,
+            self
+        );
+    }
+}
+
+impl fmt::Display for DiffReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>5} {:>20} {:>20} {:>21}",
+            "group", "before", "after", "delta"
+        )?;
+        for group in &self.groups {
+            writeln!(
+                f,
+                "{:>5} {:>20} {:>20} {:>+21}",
+                group.group_id.as_raw(),
+                group.before,
+                group.after,
+                group.delta
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal_telemetry::allocations::NUM_GROUPS;
+
+    fn baseline(bytes: &[(usize, u64)]) -> Baseline {
+        let mut live_bytes = vec![0; NUM_GROUPS];
+        for (group_idx, allocated_bytes) in bytes {
+            live_bytes[*group_idx] = *allocated_bytes;
+        }
+        Baseline { live_bytes }
+    }
+
+    #[test]
+    fn reports_changed_groups() {
+        let report =
+            baseline(&[(2, 100), (3, 50), (4, 10)]).diff(&baseline(&[(2, 300), (3, 50), (4, 0)]));
+
+        assert_eq!(
+            report.groups,
+            vec![
+                GroupDiff {
+                    group_id: AllocationGroupId::from_raw(2),
+                    before: 100,
+                    after: 300,
+                    delta: 200,
+                },
+                GroupDiff {
+                    group_id: AllocationGroupId::from_raw(4),
+                    before: 10,
+                    after: 0,
+                    delta: -10,
+                },
+            ]
+        );
+        assert_eq!(
+            report.to_string(),
+            "group               before                after                 delta\n    \
+                 2                  100                  300                  +200\n    \
+                 4                   10                    0                   -10\n"
+        );
+        report.assert_max_allocation_for_group(AllocationGroupId::from_raw(2), 200);
+        report.assert_max_allocation_for_group(AllocationGroupId::from_raw(3), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "allocation group 2 allocated 200 bytes")]
+    fn asserts_max_allocation() {
+        let report = baseline(&[(2, 100)]).diff(&baseline(&[(2, 300)]));
+        report.assert_max_allocation_for_group(AllocationGroupId::from_raw(2), 199);
+    }
+}
@@ -0,0 +1,145 @@
+//! A human-readable table of the bytes allocated by each allocation group, and their change since the table was last
+//! rendered, for operators watching memory live in a terminal.
+
+use std::{collections::HashMap, fmt::Write};
+
+use super::{allocation_snapshot, AllocationGroupId, GroupAllocationStats};
+
+/// The units of byte sizes, in powers of 1024.
+const BYTE_UNITS: [&str; 7] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+/// Renders the bytes allocated by each allocation group as a table, along with their change since the last rendering.
+#[derive(Debug, Default)]
+pub struct AllocationDiffTable {
+    previous_bytes: HashMap<AllocationGroupId, u64>,
+}
+
+impl AllocationDiffTable {
+    /// Creates a table that has not been rendered yet, so the first rendering has no change.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Renders the current statistics of every allocation group, and stores them to compare the next rendering with.
+    pub fn refresh(&mut self) -> String {
+        self.render(&allocation_snapshot())
+    }
+
+    fn render(&mut self, groups: &[GroupAllocationStats]) -> String {
+        let mut rows = groups
+            .iter()
+            .map(|group| {
+                let component = group
+                    .tags
+                    .iter()
+                    .find(|(key, _)| key == "component_id")
+                    .map_or_else(
+                        || format!("group {}", group.group_id.as_raw()),
+                        |(_, value)| value.clone(),
+                    );
+                let live_bytes = group.current_allocated_bytes();
+                let previous_bytes = self
+                    .previous_bytes
+                    .insert(group.group_id, live_bytes)
+                    .unwrap_or(live_bytes);
+                (
+                    component,
+                    live_bytes,
+                    live_bytes as i64 - previous_bytes as i64,
+                )
+            })
+            .collect::<Vec<_>>();
+        rows.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        let component_width = rows
+            .iter()
+            .map(|(component, _, _)| component.len())
+            .chain(Some("component".len()))
+            .max()
+            .unwrap_or_default();
+        let mut output = String::new();
+        // Writing to a `String` cannot fail.
+        let _ = writeln!(
+            output,
+            "{:<component_width$}  {:>12}  {:>12}",
+            "component", "live bytes", "change"
+        );
+        for (component, live_bytes, delta_bytes) in rows {
+            let _ = writeln!(
+                output,
+                "{:<component_width$}  {:>12}  {:>12}",
+                component,
+                format_bytes(live_bytes),
+                format_delta_bytes(delta_bytes)
+            );
+        }
+        output
+    }
+}
+
+/// Formats a number of bytes with the largest binary unit it is at least one of, such as `1.5 KiB`.
+pub(super) fn format_bytes(bytes: u64) -> String {
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64;
+    let mut unit_idx = 0;
+    while value >= 1024.0 && unit_idx < BYTE_UNITS.len() - 1 {
+        value /= 1024.0;
+        unit_idx += 1;
+    }
+    format!("{:.1} {}", value, BYTE_UNITS[unit_idx])
+}
+
+/// Formats a change in a number of bytes, with its sign unless it is zero.
+fn format_delta_bytes(delta_bytes: i64) -> String {
+    let bytes = format_bytes(delta_bytes.unsigned_abs());
+    match delta_bytes.signum() {
+        1 => format!("+{}", bytes),
+        -1 => format!("-{}", bytes),
+        _ => bytes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(
+        raw_group_id: u8,
+        component_id: Option<&str>,
+        live_bytes: u64,
+    ) -> GroupAllocationStats {
+        GroupAllocationStats {
+            group_id: AllocationGroupId::from_raw(raw_group_id),
+            tags: component_id
+                .map(|component_id| vec![("component_id".to_string(), component_id.to_string())])
+                .unwrap_or_default(),
+            parent: None,
+            allocated_bytes: live_bytes,
+            deallocated_bytes: 0,
+        }
+    }
+
+    #[test]
+    fn formats_bytes_with_binary_units() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 * 1024), "3.0 GiB");
+        assert_eq!(format_delta_bytes(-2048), "-2.0 KiB");
+        assert_eq!(format_delta_bytes(0), "0 B");
+    }
+
+    #[test]
+    fn renders_change_since_last_rendering() {
+        let mut table = AllocationDiffTable::new();
+        table.render(&[group(2, Some("in"), 1024), group(3, None, 100)]);
+
+        assert_eq!(
+            table.render(&[group(2, Some("in"), 2048), group(3, None, 100)]),
+            "component    live bytes        change\n\
+             in              2.0 KiB      +1.0 KiB\n\
+             group 3           100 B           0 B\n"
+        );
+    }
+}
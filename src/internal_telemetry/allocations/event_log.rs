@@ -0,0 +1,158 @@
+//! Log of the most recent (de)allocations of each allocation group, for post-mortem debugging of leaks.
+//!
+//! Logging every (de)allocation, and capturing its backtrace when `RUST_BACKTRACE` is set, is slow, so the log is
+//! disabled unless its capacity is set with the `VECTOR_ALLOC_EVENT_LOG_CAPACITY` environment variable.
+
+use std::{
+    backtrace::{Backtrace, BacktraceStatus},
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use super::{without_allocation_tracing, AllocationGroupId, NUM_GROUPS};
+
+/// The environment variable that sets the number of (de)allocations logged for each allocation group.
+const EVENT_LOG_CAPACITY_ENV_VAR: &str = "VECTOR_ALLOC_EVENT_LOG_CAPACITY";
+
+/// The number of (de)allocations logged for each allocation group, or zero if the log is disabled.
+static EVENT_LOG_CAPACITY: AtomicUsize = AtomicUsize::new(0);
+
+/// The most recent (de)allocations of each allocation group, oldest first.
+static EVENT_LOGS: Lazy<Vec<Mutex<VecDeque<AllocationLogEvent>>>> = Lazy::new(|| {
+    (0..NUM_GROUPS)
+        .map(|_| Mutex::new(VecDeque::new()))
+        .collect()
+});
+
+/// The kind of a logged allocation event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AllocationOp {
+    /// Memory was allocated.
+    Alloc,
+
+    /// Memory was deallocated.
+    Dealloc,
+}
+
+/// A (de)allocation of an allocation group.
+#[derive(Clone, Debug)]
+pub struct AllocationLogEvent {
+    /// The number of bytes (de)allocated.
+    pub size: usize,
+
+    /// Whether the bytes were allocated or deallocated.
+    pub op: AllocationOp,
+
+    /// Where the (de)allocation was made from, if backtraces are enabled with `RUST_BACKTRACE`.
+    pub backtrace: Option<Arc<Backtrace>>,
+}
+
+/// Enables the event log from the `VECTOR_ALLOC_EVENT_LOG_CAPACITY` environment variable, if it is set.
+pub(super) fn init_from_env() {
+    let capacity = match std::env::var(EVENT_LOG_CAPACITY_ENV_VAR) {
+        Ok(capacity) => capacity,
+        Err(_) => return,
+    };
+    match capacity.parse::<usize>() {
+        Ok(capacity) => {
+            if capacity > 0 {
+                Lazy::force(&EVENT_LOGS);
+                info!(
+                    message = "Logging the most recent allocation events of each allocation group.",
+                    capacity,
+                );
+            }
+            EVENT_LOG_CAPACITY.store(capacity, Ordering::Relaxed);
+        }
+        Err(error) => warn!(
+            message = "Invalid allocation event log capacity. The allocation event log is disabled.",
+            env_var = EVENT_LOG_CAPACITY_ENV_VAR,
+            %error,
+        ),
+    }
+}
+
+/// Logs a (de)allocation of the given allocation group, if the event log is enabled.
+///
+/// This is called by the tracer while the active allocation group is suspended, so the allocations made to log the
+/// event are not traced themselves.
+#[inline(always)]
+pub(super) fn record(group_idx: usize, size: usize, op: AllocationOp) {
+    let capacity = EVENT_LOG_CAPACITY.load(Ordering::Relaxed);
+    if capacity == 0 {
+        return;
+    }
+
+    let backtrace = Backtrace::capture();
+    let event = AllocationLogEvent {
+        size,
+        op,
+        backtrace: (backtrace.status() == BacktraceStatus::Captured).then(|| Arc::new(backtrace)),
+    };
+    push_bounded(&mut EVENT_LOGS[group_idx].lock().unwrap(), event, capacity);
+}
+
+fn push_bounded(
+    log: &mut VecDeque<AllocationLogEvent>,
+    event: AllocationLogEvent,
+    capacity: usize,
+) {
+    while log.len() >= capacity {
+        log.pop_front();
+    }
+    log.push_back(event);
+}
+
+/// Gets the logged (de)allocations of the given allocation group, oldest first.
+///
+/// This is empty unless the event log is enabled with the `VECTOR_ALLOC_EVENT_LOG_CAPACITY` environment variable.
+pub fn allocation_event_log(group_id: AllocationGroupId) -> Vec<AllocationLogEvent> {
+    let mut events = Vec::new();
+    if EVENT_LOG_CAPACITY.load(Ordering::Relaxed) == 0 {
+        return events;
+    }
+    without_allocation_tracing(|| {
+        if let Some(group_idx) = group_id.slot_index() {
+            events = EVENT_LOGS[group_idx]
+                .lock()
+                .unwrap()
+                .iter()
+                .cloned()
+                .collect();
+        }
+    });
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(size: usize) -> AllocationLogEvent {
+        AllocationLogEvent {
+            size,
+            op: AllocationOp::Alloc,
+            backtrace: None,
+        }
+    }
+
+    #[test]
+    fn keeps_most_recent_events() {
+        let mut log = VecDeque::new();
+        for size in 1..=5 {
+            push_bounded(&mut log, event(size), 3);
+        }
+
+        assert_eq!(
+            log.iter().map(|event| event.size).collect::<Vec<_>>(),
+            vec![3, 4, 5]
+        );
+    }
+}
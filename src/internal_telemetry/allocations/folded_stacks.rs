@@ -0,0 +1,88 @@
+//! Folded stack exposition of the bytes currently allocated by each allocation group, for flame graph tooling such as
+//! `inferno` or `flamegraph.pl`.
+//!
+//! Each line is a stack of frames separated by `;`, followed by a space and the bytes currently allocated by the group.
+//! The frames of a group are the values of its tags, in the order they were registered in: `component_kind`,
+//! `component_type`, then `component_id` for the groups of components. A group with a parent, as acquired with
+//! [`acquire_allocation_group_id_with_parent`](super::acquire_allocation_group_id_with_parent), has the frames of its
+//! ancestors first, so that flame graphs nest its bytes within those of its parent. Characters of tag values that are
+//! special to the format, `;` and newlines, are replaced with `_`.
+
+use std::io;
+
+use super::{
+    allocation_group_parent, allocation_group_registration, iter_live_groups,
+    without_allocation_tracing, AllocationGroupId, NUM_GROUPS,
+};
+
+/// Writes a line for every allocation group that currently has allocated bytes, in the folded stack format.
+///
+/// # Errors
+///
+/// If writing to `writer` fails, an error variant will be returned.
+pub fn write_folded_stacks(writer: &mut impl io::Write) -> io::Result<()> {
+    let mut result = Ok(());
+    without_allocation_tracing(|| {
+        result = iter_live_groups().try_for_each(|stats| {
+            writeln!(
+                writer,
+                "{} {}",
+                group_stack(stats.group_id),
+                stats.current_allocated_bytes()
+            )
+        });
+    });
+    result
+}
+
+/// Builds the stack of the given allocation group, starting with the frames of its furthest ancestor.
+fn group_stack(group_id: AllocationGroupId) -> String {
+    let mut groups = vec![group_id];
+    while let Some(parent) = groups.last().copied().and_then(allocation_group_parent) {
+        // Parents that form a cycle end the stack at the first repeated group.
+        if groups.contains(&parent) || groups.len() == NUM_GROUPS {
+            break;
+        }
+        groups.push(parent);
+    }
+
+    groups
+        .iter()
+        .rev()
+        .filter_map(|group_id| allocation_group_registration(*group_id))
+        .flat_map(|registration| registration.tags)
+        .map(|(_, value)| value.replace([';', '\n'], "_"))
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal_telemetry::allocations::{
+        component_tags, hierarchy, set_group_tags, tests::TestGroup, MainTracer, Tracer,
+    };
+
+    #[test]
+    fn writes_stacks_of_live_groups() {
+        let parent_group = TestGroup::register(105);
+        let child_group = TestGroup::register(104);
+        let (parent, child) = (parent_group.id(), child_group.id());
+        set_group_tags(
+            parent,
+            component_tags("in".to_string(), "kafka".to_string(), "source".to_string()),
+        );
+        set_group_tags(child, vec![("pool".to_string(), "consumer;0".to_string())]);
+        hierarchy::set_parent(child, parent);
+        MainTracer.trace_allocation(100, child);
+
+        let mut output = Vec::new();
+        write_folded_stacks(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output
+            .lines()
+            .any(|line| line == "source;kafka;in;consumer_0 100"));
+
+        MainTracer.trace_deallocation(100, child);
+    }
+}
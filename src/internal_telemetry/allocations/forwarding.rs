@@ -0,0 +1,95 @@
+//! The bytes of allocation groups that were moved to another group, while their allocations still carry their ID.
+//!
+//! Moving bytes between groups, with [`transfer_bytes`](super::transfer_bytes) or by merging groups, only moves their
+//! counters: each allocation stores the ID of the group it was made in, which its deallocation is traced with. So that
+//! the group the bytes were moved to is debited when they are deallocated, each group remembers the group it forwards
+//! bytes to and how many bytes are still forwarded, and the tracer settles its deallocations against them first. A
+//! group only forwards to a single group at a time, and its ID is not reused until every forwarded byte is settled.
+//!
+//! The group and the bytes are packed into a single atomic, so that a deallocation always settles against the group
+//! that the bytes it settles were forwarded to.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use arr_macro::arr;
+
+use super::NUM_GROUPS;
+
+/// The number of bits of a forwarding that hold the forwarded bytes, below the slot index of the group they are
+/// forwarded to.
+const FORWARDED_BYTES_BITS: u32 = 56;
+
+const FORWARDED_BYTES_MASK: u64 = (1 << FORWARDED_BYTES_BITS) - 1;
+
+/// The group that each group forwards bytes to, and the number of bytes still forwarded, packed together.
+static GROUP_FORWARDING: [AtomicU64; NUM_GROUPS] = arr![AtomicU64::new(0); 128];
+
+const fn pack(target_idx: usize, bytes: u64) -> u64 {
+    ((target_idx as u64) << FORWARDED_BYTES_BITS) | (bytes & FORWARDED_BYTES_MASK)
+}
+
+const fn unpack(forwarding: u64) -> (usize, u64) {
+    (
+        (forwarding >> FORWARDED_BYTES_BITS) as usize,
+        forwarding & FORWARDED_BYTES_MASK,
+    )
+}
+
+/// Forwards `bytes` of the group of `source_idx` to the group of `target_idx`, returning whether they were forwarded.
+///
+/// The bytes are not forwarded if the source group still forwards bytes to another group.
+pub(super) fn forward(source_idx: usize, target_idx: usize, bytes: u64) -> bool {
+    GROUP_FORWARDING[source_idx]
+        .fetch_update(Ordering::AcqRel, Ordering::Acquire, |forwarding| {
+            let (forwarded_to, forwarded_bytes) = unpack(forwarding);
+            (forwarded_bytes == 0 || forwarded_to == target_idx)
+                .then(|| pack(target_idx, forwarded_bytes.saturating_add(bytes)))
+        })
+        .is_ok()
+}
+
+/// Settles a deallocation of `bytes` traced with the group of `source_idx` against the bytes it forwards.
+///
+/// Returns the slot index of the group the settled bytes are debited from instead, along with their number, or `None`
+/// if the group forwards no bytes, which is checked with a single load.
+#[inline(always)]
+pub(super) fn settle_deallocation(source_idx: usize, bytes: u64) -> Option<(usize, u64)> {
+    let mut settled = None;
+    let _ = GROUP_FORWARDING[source_idx].fetch_update(
+        Ordering::AcqRel,
+        Ordering::Acquire,
+        |forwarding| {
+            let (forwarded_to, forwarded_bytes) = unpack(forwarding);
+            if forwarded_bytes == 0 {
+                return None;
+            }
+            let settled_bytes = forwarded_bytes.min(bytes);
+            settled = Some((forwarded_to, settled_bytes));
+            Some(pack(forwarded_to, forwarded_bytes - settled_bytes))
+        },
+    );
+    settled
+}
+
+/// Gets the number of bytes of the given group that are still forwarded to another group.
+pub(super) fn forwarded_bytes(group_idx: usize) -> u64 {
+    unpack(GROUP_FORWARDING[group_idx].load(Ordering::Acquire)).1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settles_deallocations_against_forwarded_bytes() {
+        assert!(forward(65, 64, 100));
+        // A group only forwards to a single group at a time.
+        assert!(!forward(65, 63, 10));
+        assert!(forward(65, 64, 20));
+
+        assert_eq!(settle_deallocation(65, 100), Some((64, 100)));
+        assert_eq!(settle_deallocation(65, 100), Some((64, 20)));
+        assert_eq!(settle_deallocation(65, 100), None);
+        assert_eq!(forwarded_bytes(65), 0);
+    }
+}
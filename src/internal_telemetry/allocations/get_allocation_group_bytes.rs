@@ -0,0 +1,55 @@
+use ::value::Value;
+use vrl::prelude::*;
+use vrl::state::TypeState;
+
+use super::{current_allocated_bytes, current_allocation_group};
+
+fn get_allocation_group_bytes() -> Resolved {
+    let allocated_bytes = current_allocated_bytes(current_allocation_group());
+    Ok(Value::Integer(
+        i64::try_from(allocated_bytes).unwrap_or(i64::MAX),
+    ))
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct GetAllocationGroupBytes;
+
+impl Function for GetAllocationGroupBytes {
+    fn identifier(&self) -> &'static str {
+        "get_allocation_group_bytes"
+    }
+
+    fn parameters(&self) -> &'static [Parameter] {
+        &[]
+    }
+
+    fn examples(&self) -> &'static [Example] {
+        &[Example {
+            title: "Check whether the component uses more than 100MB of memory",
+            source: r#"get_allocation_group_bytes() > 100_000_000"#,
+            result: Ok("false"),
+        }]
+    }
+
+    fn compile(
+        &self,
+        _state: &TypeState,
+        _ctx: &mut FunctionCompileContext,
+        _arguments: ArgumentList,
+    ) -> Compiled {
+        Ok(GetAllocationGroupBytesFn.as_expr())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct GetAllocationGroupBytesFn;
+
+impl FunctionExpression for GetAllocationGroupBytesFn {
+    fn resolve(&self, _ctx: &mut Context) -> Resolved {
+        get_allocation_group_bytes()
+    }
+
+    fn type_def(&self, _: &TypeState) -> TypeDef {
+        TypeDef::integer().infallible()
+    }
+}
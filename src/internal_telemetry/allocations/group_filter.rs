@@ -0,0 +1,127 @@
+//! The filter of which components have the allocations of their allocation groups tracked.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use arr_macro::arr;
+use glob::Pattern;
+use once_cell::sync::OnceCell;
+
+use super::{AllocationGroupId, NUM_GROUPS};
+
+/// Which components have the allocations of their allocation groups tracked, by glob patterns on their ID.
+///
+/// A component is tracked if its ID matches any of the allowed patterns, or if there are none, and it matches none of
+/// the denied patterns. This is checked when its allocation group is registered, against the `component_id` tag the
+/// group is registered with. Allocation groups without that tag, such as the root allocation group, are always tracked.
+#[derive(Clone, Debug, Default)]
+pub struct AllocationGroupFilter {
+    /// The patterns of the IDs of the components to track, such as `kafka_*`. Every component is allowed if empty.
+    pub allow: Vec<Pattern>,
+
+    /// The patterns of the IDs of the components not to track, even if they are allowed.
+    pub deny: Vec<Pattern>,
+}
+
+impl AllocationGroupFilter {
+    /// Whether the allocation group with the given tags is tracked.
+    fn is_tracked(&self, tags: &[(String, String)]) -> bool {
+        let component_id = match tags.iter().find(|(key, _)| key == "component_id") {
+            Some((_, component_id)) => component_id,
+            None => return true,
+        };
+        (self.allow.is_empty()
+            || self
+                .allow
+                .iter()
+                .any(|pattern| pattern.matches(component_id)))
+            && !self
+                .deny
+                .iter()
+                .any(|pattern| pattern.matches(component_id))
+    }
+}
+
+/// The filter of which components are tracked, set at initialization.
+static GROUP_FILTER: OnceCell<AllocationGroupFilter> = OnceCell::new();
+
+/// Whether each allocation group is not tracked, as matched when it was registered.
+static GROUP_UNTRACKED: [AtomicBool; NUM_GROUPS] = arr![AtomicBool::new(false); 128];
+
+/// Sets the filter of which components are tracked.
+///
+/// Only the first call has any effect. Allocation groups registered before are tracked.
+pub(super) fn set_group_filter(filter: AllocationGroupFilter) {
+    let _ = GROUP_FILTER.set(filter);
+}
+
+/// Matches a newly registered allocation group against the filter, so that the tracer skips it if it is not tracked.
+pub(super) fn match_group(group_idx: usize, tags: &[(String, String)]) {
+    let untracked = GROUP_FILTER
+        .get()
+        .map_or(false, |filter| !filter.is_tracked(tags));
+    GROUP_UNTRACKED[group_idx].store(untracked, Ordering::Relaxed);
+}
+
+/// Gets the index of the slot that the given allocation group is traced into, if it is tracked.
+///
+/// The (de)allocations of untracked groups are skipped by the tracer, which is what allows the allocator to keep
+/// storing their group ID: the deallocation of an allocation that was skipped is skipped as well.
+#[inline(always)]
+pub(super) fn tracked_slot_index(group_id: AllocationGroupId) -> Option<usize> {
+    let group_idx = group_id.traced_slot_index();
+    (!GROUP_UNTRACKED[group_idx].load(Ordering::Relaxed)).then_some(group_idx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal_telemetry::allocations::{
+        group_mem_totals, tests::test_group_id, MainTracer, Tracer, THREAD_LOCAL_REFS,
+    };
+
+    fn component_id(value: &str) -> Vec<(String, String)> {
+        vec![("component_id".to_string(), value.to_string())]
+    }
+
+    fn patterns(patterns: &[&str]) -> Vec<Pattern> {
+        patterns
+            .iter()
+            .map(|pattern| Pattern::new(pattern).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn matches_component_ids() {
+        let filter = AllocationGroupFilter {
+            allow: patterns(&["kafka_*", "http"]),
+            deny: patterns(&["kafka_debug"]),
+        };
+
+        assert!(filter.is_tracked(&component_id("kafka_in")));
+        assert!(filter.is_tracked(&component_id("http")));
+        assert!(!filter.is_tracked(&component_id("kafka_debug")));
+        assert!(!filter.is_tracked(&component_id("file")));
+        assert!(filter.is_tracked(&[]));
+    }
+
+    #[test]
+    fn denied_groups_never_accumulate_bytes() {
+        let filter = AllocationGroupFilter {
+            deny: patterns(&["noisy_*"]),
+            ..Default::default()
+        };
+        let group_id = test_group_id(76);
+        let group_idx = group_id.as_raw() as usize;
+        GROUP_UNTRACKED[group_idx].store(
+            !filter.is_tracked(&component_id("noisy_source")),
+            Ordering::Relaxed,
+        );
+
+        MainTracer.trace_allocation(100, group_id);
+        MainTracer.trace_reallocation(100, 200, group_id);
+        MainTracer.trace_deallocation(200, group_id);
+
+        let totals = group_mem_totals(&THREAD_LOCAL_REFS.lock().unwrap(), group_idx);
+        assert!(totals.is_empty(), "{:?}", totals);
+    }
+}
@@ -0,0 +1,103 @@
+//! A summary of the bytes allocated by each allocation group, that operators can request from a live process by sending
+//! it `SIGUSR1`.
+//!
+//! Only async-signal-safe code can run in a signal handler, which rules out allocating, locking and logging. The handler
+//! only sets a flag instead, and the processor thread writes the summary the next time it polls the flag.
+
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+use once_cell::sync::{Lazy, OnceCell};
+
+use super::AllocationDiffTable;
+
+/// How often the processor checks whether a heap summary was requested, while it waits for the next emission.
+pub(super) const REQUEST_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Whether a heap summary was requested since the processor last wrote one.
+static SUMMARY_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Where heap summaries are written, as set when the signal handler is installed.
+static SUMMARY_OUTPUT: OnceCell<HeapSummaryOutput> = OnceCell::new();
+
+/// The table that heap summaries are rendered with, so that each summary shows the change since the previous one.
+static SUMMARY_TABLE: Lazy<Mutex<AllocationDiffTable>> = Lazy::new(Default::default);
+
+/// Where the heap summaries requested with `SIGUSR1` are written.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HeapSummaryOutput {
+    /// Logs each summary at the info level.
+    Log,
+
+    /// Writes each summary to the given file, replacing the previous summary.
+    File(PathBuf),
+}
+
+extern "C" fn request_heap_summary(_signal: libc::c_int) {
+    SUMMARY_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+/// Installs the `SIGUSR1` handler that requests a heap summary, which is written to the given output.
+pub(super) fn install_signal_handler(output: HeapSummaryOutput) -> nix::Result<()> {
+    let _ = SUMMARY_OUTPUT.set(output);
+    let action = SigAction::new(
+        SigHandler::Handler(request_heap_summary),
+        SaFlags::SA_RESTART,
+        SigSet::empty(),
+    );
+    // SAFETY: The handler only stores to an atomic, which is async-signal-safe.
+    unsafe { sigaction(Signal::SIGUSR1, &action) }.map(|_| ())
+}
+
+/// Writes a heap summary if one was requested since the last call.
+///
+/// This must only be called by the processor thread, with allocation tracing suspended.
+pub(super) fn write_requested_heap_summary() {
+    if !SUMMARY_REQUESTED.swap(false, Ordering::Relaxed) {
+        return;
+    }
+    if let Some(output) = SUMMARY_OUTPUT.get() {
+        write_heap_summary(output);
+    }
+}
+
+fn write_heap_summary(output: &HeapSummaryOutput) {
+    let summary = SUMMARY_TABLE.lock().unwrap().refresh();
+    match output {
+        HeapSummaryOutput::Log => {
+            info!(message = "Heap summary requested.", %summary);
+        }
+        HeapSummaryOutput::File(path) => {
+            if let Err(error) = std::fs::write(path, summary) {
+                error!(
+                    message = "Failed to write heap summary.",
+                    path = %path.display(),
+                    %error,
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_heap_summary_to_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("heap_summary.txt");
+
+        write_heap_summary(&HeapSummaryOutput::File(path.clone()));
+
+        let summary = std::fs::read_to_string(path).unwrap();
+        assert!(summary.starts_with("component"));
+    }
+}
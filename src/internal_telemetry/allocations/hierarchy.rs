@@ -0,0 +1,134 @@
+//! Parent-child relationships between allocation groups, for reporting the bytes of a group and all of its descendants.
+
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use arr_macro::arr;
+
+use super::{AllocationGroupId, NUM_GROUPS};
+
+/// The raw ID of the parent of each allocation group, or zero if it has none.
+///
+/// Zero is the slot of untraced allocations, which is never registered, so it can't be anyone's parent.
+static GROUP_PARENTS: [AtomicU8; NUM_GROUPS] = arr![AtomicU8::new(0); 128];
+
+/// The bytes currently allocated by each allocation group and all of its descendants, as of the processor's last
+/// emission.
+static GROUP_AGGREGATE_MEM: [AtomicU64; NUM_GROUPS] = arr![AtomicU64::new(0); 128];
+
+/// Makes `parent` the parent of the given allocation group.
+///
+/// A group can't be its own parent, and neither can the shared allocation groups have one.
+pub(super) fn set_parent(group_id: AllocationGroupId, parent: AllocationGroupId) {
+    if group_id != parent && !group_id.is_shared() {
+        if let (Some(group_idx), Some(_)) = (group_id.slot_index(), parent.slot_index()) {
+            GROUP_PARENTS[group_idx].store(parent.as_raw(), Ordering::Relaxed);
+        }
+    }
+}
+
+/// Removes the parent of the given allocation group, and makes its children top-level groups.
+///
+/// This is done when the ID of a group is reused, so that its new group neither inherits the parent of the previous
+/// one nor receives the bytes of its children.
+pub(super) fn clear_relationships(group_id: AllocationGroupId) {
+    let group_idx = match group_id.slot_index() {
+        Some(group_idx) => group_idx,
+        None => return,
+    };
+    let raw_group_id = group_id.as_raw();
+    GROUP_PARENTS[group_idx].store(0, Ordering::Relaxed);
+    for parent in &GROUP_PARENTS {
+        let _ = parent.compare_exchange(raw_group_id, 0, Ordering::Relaxed, Ordering::Relaxed);
+    }
+}
+
+/// Gets the parent of the given allocation group, if it has one.
+pub fn allocation_group_parent(group_id: AllocationGroupId) -> Option<AllocationGroupId> {
+    match GROUP_PARENTS[group_id.slot_index()?].load(Ordering::Relaxed) {
+        0 => None,
+        parent => Some(AllocationGroupId::from_raw(parent)),
+    }
+}
+
+/// Gets the bytes currently allocated by the given allocation group and all of its descendants, as of the processor's
+/// last emission.
+pub fn aggregate_allocated_bytes(group_id: AllocationGroupId) -> u64 {
+    group_id.slot_index().map_or(0, |group_idx| {
+        GROUP_AGGREGATE_MEM[group_idx].load(Ordering::Relaxed)
+    })
+}
+
+/// Sums the bytes allocated by each allocation group into the aggregates of the group and all of its ancestors.
+///
+/// `live_bytes` and `parents` are indexed by the raw group ID. Each ancestor is only visited once per group, so parents
+/// that form a cycle don't count bytes more than once.
+fn aggregate_live_bytes(live_bytes: &[u64], parents: &[u8]) -> Vec<u64> {
+    let mut aggregates = vec![0; live_bytes.len()];
+    for (group_idx, &bytes) in live_bytes.iter().enumerate() {
+        if bytes == 0 {
+            continue;
+        }
+        let mut visited = vec![false; live_bytes.len()];
+        let mut ancestor_idx = group_idx;
+        while !visited[ancestor_idx] {
+            visited[ancestor_idx] = true;
+            aggregates[ancestor_idx] += bytes;
+            ancestor_idx = match parents[ancestor_idx] as usize {
+                0 => break,
+                parent_idx => parent_idx,
+            };
+        }
+    }
+    aggregates
+}
+
+/// Updates the aggregate allocated bytes of every allocation group, returning the groups whose aggregate changed along
+/// with their new aggregate.
+pub(super) fn update_aggregates(live_bytes: &[u64]) -> Vec<(usize, u64)> {
+    let parents = GROUP_PARENTS
+        .iter()
+        .map(|parent| parent.load(Ordering::Relaxed))
+        .collect::<Vec<_>>();
+    aggregate_live_bytes(live_bytes, &parents)
+        .into_iter()
+        .enumerate()
+        .filter(|(group_idx, aggregate)| {
+            GROUP_AGGREGATE_MEM[*group_idx].swap(*aggregate, Ordering::Relaxed) != *aggregate
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal_telemetry::allocations::tests::test_group_id;
+
+    #[test]
+    fn aggregates_descendants() {
+        // 1 <- 2 <- 3, and 4 on its own.
+        let parents = [0, 0, 1, 2, 0];
+        let live_bytes = [0, 10, 20, 30, 40];
+        assert_eq!(
+            aggregate_live_bytes(&live_bytes, &parents),
+            vec![0, 60, 50, 30, 40]
+        );
+    }
+
+    #[test]
+    fn aggregates_cycles_once() {
+        let parents = [0, 2, 1];
+        let live_bytes = [0, 10, 20];
+        assert_eq!(aggregate_live_bytes(&live_bytes, &parents), vec![0, 30, 30]);
+    }
+
+    #[test]
+    fn clears_relationships_of_reused_groups() {
+        let parent = test_group_id(116);
+        let child = test_group_id(115);
+        set_parent(child, parent);
+        assert_eq!(allocation_group_parent(child), Some(parent));
+
+        clear_relationships(parent);
+        assert_eq!(allocation_group_parent(child), None);
+    }
+}
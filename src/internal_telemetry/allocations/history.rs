@@ -0,0 +1,186 @@
+//! Recent allocated bytes readings of each allocation group.
+
+use std::{
+    collections::VecDeque,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+
+use super::{without_allocation_tracing, AllocationGroupId};
+
+/// The number of readings kept for each allocation group by default.
+pub(super) const DEFAULT_HISTORY_LEN: usize = 60;
+
+/// The largest number of readings that can be kept for each allocation group, which is an hour of readings at the
+/// default reporting interval.
+pub(super) const MAX_HISTORY_LEN: usize = 3600;
+
+/// The number of readings kept for each allocation group, as set at initialization.
+static HISTORY_LEN: AtomicUsize = AtomicUsize::new(DEFAULT_HISTORY_LEN);
+
+/// Sets the number of readings kept for each allocation group, clamped to [`MAX_HISTORY_LEN`], returning whether it had
+/// to be clamped.
+pub(super) fn set_history_len(history_len: usize) -> bool {
+    HISTORY_LEN.store(history_len.min(MAX_HISTORY_LEN), Ordering::Relaxed);
+    history_len > MAX_HISTORY_LEN
+}
+
+/// The readings of every allocation group that currently has allocated bytes attributed to it.
+///
+/// Only the processor thread adds or removes entries, while allocation tracing is suspended.
+static GROUP_HISTORY: Lazy<DashMap<AllocationGroupId, AllocationGroupHistory>> =
+    Lazy::new(DashMap::new);
+
+/// The most recent readings of the bytes currently allocated by an allocation group, oldest first.
+#[derive(Clone, Debug, Default)]
+pub struct AllocationGroupHistory {
+    readings: VecDeque<(Instant, u64)>,
+}
+
+impl AllocationGroupHistory {
+    /// Returns the readings, oldest first, as the time each was taken and the bytes allocated at that time.
+    pub fn readings(&self) -> impl Iterator<Item = (Instant, u64)> + '_ {
+        self.readings.iter().copied()
+    }
+
+    fn record(&mut self, at: Instant, allocated_bytes: u64, history_len: usize) {
+        while self.readings.len() >= history_len {
+            if self.readings.pop_front().is_none() {
+                return;
+            }
+        }
+        self.readings.push_back((at, allocated_bytes));
+    }
+}
+
+/// Gets the recent readings of the given allocation group, if it has any.
+pub fn allocation_group_history(group_id: AllocationGroupId) -> Option<AllocationGroupHistory> {
+    GROUP_HISTORY
+        .get(&group_id)
+        .map(|history| history.value().clone())
+}
+
+/// Gets the recent readings of the given allocation group, oldest first, as the time each was taken and the bytes
+/// allocated at that time.
+///
+/// This is empty if the group has no history. The readings are copied out with allocation tracing suspended, so that
+/// diagnostics rendering them, such as a sparkline of recent memory, don't get their copy attributed to their own group.
+pub fn group_history(group_id: AllocationGroupId) -> Vec<(Instant, u64)> {
+    let mut readings = Vec::new();
+    without_allocation_tracing(|| {
+        if let Some(history) = GROUP_HISTORY.get(&group_id) {
+            readings = history.readings().collect();
+        }
+    });
+    readings
+}
+
+/// Records a reading of the given allocation group, unless it has no history yet and `allow_new` is `false`.
+pub(super) fn record_allocation_group_reading(
+    group_id: AllocationGroupId,
+    at: Instant,
+    allocated_bytes: u64,
+    allow_new: bool,
+) {
+    if !allow_new && !GROUP_HISTORY.contains_key(&group_id) {
+        return;
+    }
+    GROUP_HISTORY.entry(group_id).or_default().record(
+        at,
+        allocated_bytes,
+        HISTORY_LEN.load(Ordering::Relaxed),
+    );
+}
+
+/// Gets the number of heap bytes used by the history of all allocation groups.
+pub(super) fn heap_bytes() -> usize {
+    GROUP_HISTORY
+        .iter()
+        .map(|history| {
+            std::mem::size_of::<(AllocationGroupId, AllocationGroupHistory)>()
+                + history.readings.capacity() * std::mem::size_of::<(Instant, u64)>()
+        })
+        .sum()
+}
+
+pub(super) fn remove_allocation_group_history(group_id: AllocationGroupId) {
+    GROUP_HISTORY.remove(&group_id);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::internal_telemetry::allocations::tests::test_group_id;
+
+    #[test]
+    fn keeps_most_recent_readings() {
+        let start = Instant::now();
+        let mut history = AllocationGroupHistory::default();
+        for reading in 0..DEFAULT_HISTORY_LEN as u64 + 5 {
+            history.record(
+                start + Duration::from_secs(reading),
+                reading,
+                DEFAULT_HISTORY_LEN,
+            );
+        }
+
+        let readings = history.readings().collect::<Vec<_>>();
+        assert_eq!(readings.len(), DEFAULT_HISTORY_LEN);
+        assert_eq!(readings.first(), Some(&(start + Duration::from_secs(5), 5)));
+        assert_eq!(
+            readings.last(),
+            Some(&(
+                start + Duration::from_secs(DEFAULT_HISTORY_LEN as u64 + 4),
+                DEFAULT_HISTORY_LEN as u64 + 4
+            ))
+        );
+    }
+
+    #[test]
+    fn drops_readings_beyond_a_shortened_length() {
+        let start = Instant::now();
+        let mut history = AllocationGroupHistory::default();
+        for reading in 0..10 {
+            history.record(start, reading, 10);
+        }
+        history.record(start, 10, 3);
+
+        let readings = history
+            .readings()
+            .map(|(_, allocated_bytes)| allocated_bytes)
+            .collect::<Vec<_>>();
+        assert_eq!(readings, vec![8, 9, 10]);
+    }
+
+    #[test]
+    fn only_records_new_groups_when_allowed() {
+        let group_id = test_group_id(119);
+        let now = Instant::now();
+
+        record_allocation_group_reading(group_id, now, 10, false);
+        assert!(allocation_group_history(group_id).is_none());
+
+        record_allocation_group_reading(group_id, now, 10, true);
+        record_allocation_group_reading(group_id, now, 20, false);
+        let readings = allocation_group_history(group_id)
+            .unwrap()
+            .readings()
+            .map(|(_, allocated_bytes)| allocated_bytes)
+            .collect::<Vec<_>>();
+        assert_eq!(readings, vec![10, 20]);
+        assert_eq!(
+            group_history(group_id),
+            allocation_group_history(group_id)
+                .unwrap()
+                .readings()
+                .collect::<Vec<_>>()
+        );
+
+        remove_allocation_group_history(group_id);
+    }
+}
@@ -0,0 +1,96 @@
+//! Histograms of the time the wrapped allocator takes to allocate, per allocation group.
+//!
+//! Timing every allocation is not free, so this is only compiled in with the `allocation-latency` feature.
+
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use arr_macro::arr;
+
+use super::{AllocationMetricSink, NUM_GROUPS};
+
+/// The number of buckets of each histogram.
+///
+/// Bucket `i` counts the allocations that took less than 2^`i` microseconds, except for the last one, which counts
+/// every allocation that took longer than the others.
+const LATENCY_BUCKETS: usize = 16;
+
+/// The number of allocations of each group in each bucket since the processor's last emission.
+static GROUP_ALLOCATION_LATENCY: [[AtomicU64; LATENCY_BUCKETS]; NUM_GROUPS] =
+    arr![arr![AtomicU64::new(0); 16]; 128];
+
+fn bucket_index(latency: Duration) -> usize {
+    let micros = u64::try_from(latency.as_micros()).unwrap_or(u64::MAX);
+    ((u64::BITS - micros.leading_zeros()) as usize).min(LATENCY_BUCKETS - 1)
+}
+
+/// Records the time the wrapped allocator took to allocate for the given group.
+#[inline(always)]
+pub(super) fn record_allocation_latency(group_idx: usize, latency: Duration) {
+    GROUP_ALLOCATION_LATENCY[group_idx][bucket_index(latency)].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Takes the number of allocations in each bucket of the given group since the last call.
+fn take_allocation_latency(group_idx: usize) -> [u64; LATENCY_BUCKETS] {
+    let mut buckets = [0; LATENCY_BUCKETS];
+    for (bucket, count) in buckets.iter_mut().zip(&GROUP_ALLOCATION_LATENCY[group_idx]) {
+        *bucket = count.swap(0, Ordering::Relaxed);
+    }
+    buckets
+}
+
+/// Emits the allocation latency histogram of the given group as cumulative counters, labeled with the upper bound of
+/// their bucket in microseconds like Prometheus histograms are.
+pub(super) fn emit_allocation_latency(
+    metric_sink: &dyn AllocationMetricSink,
+    group_idx: usize,
+    labels: &[(String, String)],
+) {
+    let mut cumulative_count = 0;
+    for (bucket_idx, count) in take_allocation_latency(group_idx).into_iter().enumerate() {
+        cumulative_count += count;
+        if cumulative_count == 0 {
+            continue;
+        }
+        let upper_bound = if bucket_idx == LATENCY_BUCKETS - 1 {
+            "+Inf".to_string()
+        } else {
+            (1_u64 << bucket_idx).to_string()
+        };
+        let mut labels = labels.to_vec();
+        labels.push(("le".to_string(), upper_bound));
+        metric_sink.record_counter(
+            "component_allocation_latency_microseconds_total",
+            cumulative_count,
+            &labels,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_by_powers_of_two_microseconds() {
+        assert_eq!(bucket_index(Duration::from_nanos(500)), 0);
+        assert_eq!(bucket_index(Duration::from_micros(1)), 1);
+        assert_eq!(bucket_index(Duration::from_micros(3)), 2);
+        assert_eq!(bucket_index(Duration::from_micros(4)), 3);
+        assert_eq!(bucket_index(Duration::from_secs(10)), LATENCY_BUCKETS - 1);
+    }
+
+    #[test]
+    fn takes_recorded_latencies() {
+        record_allocation_latency(110, Duration::from_nanos(200));
+        record_allocation_latency(110, Duration::from_nanos(300));
+        record_allocation_latency(110, Duration::from_micros(5));
+
+        let buckets = take_allocation_latency(110);
+        assert_eq!(buckets[0], 2);
+        assert_eq!(buckets[3], 1);
+        assert_eq!(take_allocation_latency(110), [0; LATENCY_BUCKETS]);
+    }
+}
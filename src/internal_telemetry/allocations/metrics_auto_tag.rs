@@ -0,0 +1,118 @@
+//! Labeling of every metric with the allocation group it is registered from.
+//!
+//! This ties the metrics of a component to its allocation metrics without any extra code in the component, at the cost
+//! of building a new key for every metric registration, which is why it is behind the `metrics-auto-tag` feature.
+
+use metrics::{Counter, Gauge, Histogram, Key, KeyName, Label, Recorder, SharedString, Unit};
+
+use super::{current_allocation_group, AllocationGroupId};
+
+/// The label holding the raw ID of the allocation group a metric was registered from.
+const ALLOCATION_GROUP_ID_LABEL: &str = "allocation_group_id";
+
+/// A [`Recorder`] that adds the `allocation_group_id` label to every metric registered from an allocation group.
+///
+/// Metrics registered outside of any allocation group, or while allocation tracing is suspended, are registered
+/// unchanged.
+pub struct AllocationGroupRecorder {
+    inner: Box<dyn Recorder>,
+}
+
+impl AllocationGroupRecorder {
+    /// Wraps the given recorder.
+    pub fn new(inner: Box<dyn Recorder>) -> Self {
+        Self { inner }
+    }
+}
+
+fn tagged_key(key: &Key) -> Key {
+    let group_id = current_allocation_group();
+    if group_id == AllocationGroupId::ROOT {
+        return key.clone();
+    }
+    key.with_extra_labels(vec![Label::new(
+        ALLOCATION_GROUP_ID_LABEL,
+        group_id.as_raw().to_string(),
+    )])
+}
+
+impl Recorder for AllocationGroupRecorder {
+    fn describe_counter(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_counter(key, unit, description);
+    }
+
+    fn describe_gauge(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_gauge(key, unit, description);
+    }
+
+    fn describe_histogram(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_histogram(key, unit, description);
+    }
+
+    fn register_counter(&self, key: &Key) -> Counter {
+        self.inner.register_counter(&tagged_key(key))
+    }
+
+    fn register_gauge(&self, key: &Key) -> Gauge {
+        self.inner.register_gauge(&tagged_key(key))
+    }
+
+    fn register_histogram(&self, key: &Key) -> Histogram {
+        self.inner.register_histogram(&tagged_key(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::internal_telemetry::allocations::{in_task_allocation_group, tests::test_group_id};
+
+    #[derive(Clone, Default)]
+    struct KeyRecorder {
+        keys: Arc<Mutex<Vec<Key>>>,
+    }
+
+    impl Recorder for KeyRecorder {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, key: &Key) -> Counter {
+            self.keys.lock().unwrap().push(key.clone());
+            Counter::noop()
+        }
+
+        fn register_gauge(&self, key: &Key) -> Gauge {
+            self.keys.lock().unwrap().push(key.clone());
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, key: &Key) -> Histogram {
+            self.keys.lock().unwrap().push(key.clone());
+            Histogram::noop()
+        }
+    }
+
+    #[tokio::test]
+    async fn labels_metrics_with_allocation_group() {
+        let inner = KeyRecorder::default();
+        let recorder = AllocationGroupRecorder::new(Box::new(inner.clone()));
+
+        recorder.register_counter(&Key::from_name("outside"));
+        in_task_allocation_group(test_group_id(111), async {
+            recorder.register_counter(&Key::from_name("inside"));
+        })
+        .await;
+
+        let keys = inner.keys.lock().unwrap();
+        assert_eq!(keys[0].labels().count(), 0);
+        assert_eq!(
+            keys[1].labels().collect::<Vec<_>>(),
+            vec![&Label::new(ALLOCATION_GROUP_ID_LABEL, "111")]
+        );
+    }
+}
@@ -1,58 +1,449 @@
 //! Allocation tracking exposed via internal telemetry.
 
+mod adaptive_interval;
+mod alloc_error;
 mod allocator;
+mod budget;
+mod cgroup;
+mod config_file;
+#[cfg(feature = "allocation-tracing-debug-frees")]
+mod debug_frees;
+mod diff_report;
+mod diff_table;
+mod event_log;
+mod folded_stacks;
+mod forwarding;
+mod get_allocation_group_bytes;
+mod group_filter;
+#[cfg(all(unix, feature = "allocation-tracing-sigusr1"))]
+mod heap_summary;
+mod hierarchy;
+mod history;
+#[cfg(feature = "allocation-latency")]
+mod latency;
+#[cfg(feature = "metrics-auto-tag")]
+mod metrics_auto_tag;
+#[cfg(feature = "otel")]
+mod otel;
+mod panic_hook;
+mod per_event;
+mod pprof;
+mod process_memory;
+mod prometheus;
+#[cfg(unix)]
+mod proxy;
+mod registry;
+mod report;
+mod sink;
+mod size_classes;
+mod snapshot;
+#[cfg(feature = "allocation-tracing-span-groups")]
+mod span_groups;
+mod tag_aggregates;
+mod task_groups;
+mod thread_stacks;
+mod thresholds;
 use std::{
+    future::Future,
+    panic::{self, AssertUnwindSafe},
     sync::{
-        atomic::{AtomicBool, AtomicU64, Ordering},
+        atomic::{fence, AtomicBool, AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering},
         Mutex,
     },
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use arr_macro::arr;
-use metrics::{counter, decrement_gauge, increment_gauge};
+use once_cell::sync::{Lazy, OnceCell};
 use rand_distr::num_traits::ToPrimitive;
+use tokio::sync::Notify;
+use tracing::field::{Field, Visit};
+use tracing::span::Record;
 
+#[cfg(not(feature = "allocation-tracing-benches"))]
 use self::allocator::Tracer;
+use self::report::memory_report;
 
-pub(crate) use self::allocator::{
-    without_allocation_tracing, AllocationGroupId, AllocationLayer, GroupedTraceableAllocator,
+pub use self::allocator::{
+    allocation_overhead, current_allocation_group, enter_task_allocation_group,
+    exit_task_allocation_group, header_overhead, in_task_allocation_group, with_allocation_group,
+    AllocationGroupToken, AllocationTrackedFuture, ScopedAllocationGroup, TrackAllocationsExt,
+    WeakAllocationGroupToken, NET_ALLOCATED_BYTES_FIELD,
+};
+pub(crate) use self::allocator::{without_allocation_tracing, GroupedTraceableAllocator};
+#[cfg(not(feature = "allocation-tracing-benches"))]
+pub(crate) use self::allocator::{AllocationGroupId, AllocationLayer};
+// The benchmarks drive the tracer and the tracing layer directly, as the tracing allocator can't be enabled in a
+// process that already allocated.
+pub use self::adaptive_interval::AdaptiveReportingInterval;
+#[cfg(feature = "allocation-tracing-benches")]
+pub use self::allocator::{AllocationGroupId, AllocationLayer, Tracer};
+pub use self::budget::{
+    admit_events_within_budget, any_budget_enforced, budget_enforcement,
+    take_memory_pressure_event, MemoryPressureEvent,
+};
+pub use self::cgroup::{CgroupMemory, CgroupMemoryTracker};
+pub use self::config_file::{
+    apply_allocation_tracing_config, set_allocation_tracing_config_overrides,
+};
+pub use self::diff_report::{DiffReport, GroupDiff};
+pub use self::diff_table::AllocationDiffTable;
+pub use self::event_log::{allocation_event_log, AllocationLogEvent, AllocationOp};
+pub use self::folded_stacks::write_folded_stacks;
+pub use self::group_filter::AllocationGroupFilter;
+#[cfg(all(unix, feature = "allocation-tracing-sigusr1"))]
+pub use self::heap_summary::HeapSummaryOutput;
+pub use self::hierarchy::{aggregate_allocated_bytes, allocation_group_parent};
+pub use self::history::{allocation_group_history, group_history, AllocationGroupHistory};
+#[cfg(feature = "metrics-auto-tag")]
+pub use self::metrics_auto_tag::AllocationGroupRecorder;
+pub use self::per_event::{AllocationHistogram, EventAllocationScope, PerEventAllocationTracker};
+pub use self::pprof::encode_allocation_profile_pprof;
+pub use self::process_memory::{process_memory, ProcessMemory};
+pub use self::prometheus::{
+    encode_allocation_metrics_openmetrics, encode_allocation_metrics_prometheus,
+};
+#[cfg(unix)]
+pub use self::proxy::{AllocationEvent, AllocationGroupProxySource, AllocationProxyConfig};
+pub use self::registry::AllocationGroupRegistry;
+pub use self::report::{
+    gather_all_memory_reports, set_allocation_group_config_hash, ComponentMemoryReport,
+};
+pub use self::sink::{AllocationMetricSink, MetricsCrateSink};
+pub use self::snapshot::{AllocationGroupSnapshot, GroupSnapshot};
+#[cfg(feature = "allocation-tracing-span-groups")]
+pub(crate) use self::span_groups::span_name_group;
+pub use self::tag_aggregates::DEFAULT_AGGREGATION_TAG;
+pub use self::task_groups::{with_task_id, AllocationFallback};
+pub use self::thread_stacks::{
+    record_thread_start, record_thread_stop, spawn_accounted_thread, DEFAULT_THREAD_STACK_SIZE,
+};
+pub use self::thresholds::{
+    AllocationBudgetExceededAction, AllocationThresholdOverride, AllocationThresholds,
+    DEFAULT_RESUME_RATIO,
 };
 
 const NUM_GROUPS: usize = 128;
+
+/// The number of allocation groups registered since the processor's last emission that had to share the overflow
+/// allocation group, as every other slot was taken.
+static GROUPS_OVERFLOWED: AtomicU64 = AtomicU64::new(0);
+
+/// Fraction of `NUM_GROUPS` above which the processor warns that the group capacity is about to run out.
+const GROUP_CAPACITY_WARNING_RATIO: f64 = 0.9;
+
 // Allocations are not tracked during startup.
 // We use the Relaxed ordering for both stores and loads of this atomic as no other threads exist when
 // this code is running, and all future threads will have a happens-after relationship with
 // this thread -- the main thread -- ensuring that they see the latest value of TRACK_ALLOCATIONS.
 pub static TRACK_ALLOCATIONS: AtomicBool = AtomicBool::new(false);
 
+/// Whether allocation tracing only counts the entries into allocation groups, as set at initialization.
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// Whether the OpenMetrics exposition of allocation group statistics carries exemplars, as set at initialization.
+static EXEMPLARS: AtomicBool = AtomicBool::new(false);
+
+/// The span field whose value is recorded as the trace ID of the allocation group of the span.
+const TRACE_ID_FIELD: &str = "trace_id";
+
+/// The number of times each allocation group was entered since the processor's last emission, in dry run mode.
+static GROUP_ENTERED: [AtomicU64; NUM_GROUPS] = arr![AtomicU64::new(0); 128];
+
+/// Counts an entry into the given allocation group, if allocation tracing is in dry run mode.
+#[inline]
+fn record_allocation_group_entered(group_id: AllocationGroupId) {
+    if let (true, Some(group_idx)) = (DRY_RUN.load(Ordering::Relaxed), group_id.slot_index()) {
+        GROUP_ENTERED[group_idx].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// The wrapped size in bytes below which allocations are only counted in [`GROUP_SMALL_ALLOC_BYTES`], as set at
+/// initialization.
+static MIN_TRACKED_SIZE_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// The number of bytes of the allocations of each allocation group that were too small to be traced, since the
+/// processor's last emission.
+static GROUP_SMALL_ALLOC_BYTES: [AtomicU64; NUM_GROUPS] = arr![AtomicU64::new(0); 128];
+
+/// How finely the allocations of an allocation group are traced.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AllocationFidelity {
+    /// Allocations smaller than the minimum tracked size are only counted by their bytes, like in every other group.
+    Default,
+
+    /// Every allocation is traced, regardless of the minimum tracked size.
+    Full,
+}
+
+/// Whether each allocation group traces every allocation, regardless of the minimum tracked size.
+static GROUP_FULL_FIDELITY: [AtomicBool; NUM_GROUPS] = arr![AtomicBool::new(false); 128];
+
+/// Sets how finely the allocations of the given allocation group are traced.
+///
+/// This lets a single component be investigated at full fidelity at runtime, without paying the overhead of tracing
+/// every small allocation of every other group. The fidelity is reset to the default when the group is registered.
+pub fn set_group_fidelity(group_id: AllocationGroupId, fidelity: AllocationFidelity) {
+    if let Some(group_idx) = group_id.slot_index() {
+        GROUP_FULL_FIDELITY[group_idx]
+            .store(fidelity == AllocationFidelity::Full, Ordering::Relaxed);
+    }
+}
+
+/// Gets whether an allocation of the given wrapped size in the given allocation group is traced, or only counted as a
+/// small allocation.
+#[inline(always)]
+fn is_traced_allocation(
+    wrapped_size: usize,
+    min_tracked_size: usize,
+    group_id: AllocationGroupId,
+) -> bool {
+    // The fidelity of the group is only read for small allocations, which are rare unless a minimum is set.
+    wrapped_size >= min_tracked_size
+        || group_id.slot_index().map_or(false, |group_idx| {
+            GROUP_FULL_FIDELITY[group_idx].load(Ordering::Relaxed)
+        })
+}
+
+/// Whether the bytes of the allocations that are not attributed to any allocation group are counted, as set at
+/// initialization.
+static COUNT_UNTRACKED_BYTES: AtomicBool = AtomicBool::new(false);
+
+/// The number of bytes allocated without being attributed to any allocation group, while they are counted.
+static UNTRACKED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// Counts an allocation that is not attributed to any allocation group, as tracking allocations is disabled or the
+/// active allocation group is suspended.
+///
+/// This must be called outside of the suspended allocation group, which it doesn't need: it only adds to an atomic.
+#[inline(always)]
+fn record_untracked_allocation(object_size: usize) {
+    if COUNT_UNTRACKED_BYTES.load(Ordering::Relaxed) {
+        UNTRACKED_BYTES.fetch_add(object_size as u64, Ordering::Relaxed);
+    }
+}
+
+/// Gets the number of bytes allocated without being attributed to any allocation group, since they are counted.
+///
+/// These are only counted when allocation tracing is initialized to count them, and explain some of the difference
+/// between the bytes allocated by every allocation group and the resident memory of the process.
+pub fn untracked_bytes() -> u64 {
+    UNTRACKED_BYTES.load(Ordering::Relaxed)
+}
+
+/// The number of allocations that the wrapped allocator failed to make in each allocation group, since the processor's
+/// last emission.
+static GROUP_ALLOCATION_FAILURES: [AtomicU64; NUM_GROUPS] = arr![AtomicU64::new(0); 128];
+
+/// Counts an allocation of the given allocation group that is too small to be traced.
+#[inline(always)]
+fn record_small_allocation(object_size: usize, group_id: AllocationGroupId) {
+    if let Some(group_idx) = group_id.slot_index() {
+        GROUP_SMALL_ALLOC_BYTES[group_idx].fetch_add(object_size as u64, Ordering::Relaxed);
+    }
+}
+
 /// Track allocations and deallocations separately.
 struct GroupMemStatsStorage {
     allocations: [AtomicU64; NUM_GROUPS],
     deallocations: [AtomicU64; NUM_GROUPS],
+    /// The number of allocator calls, which can be high even when few bytes are allocated.
+    allocation_events: [AtomicU64; NUM_GROUPS],
+    deallocation_events: [AtomicU64; NUM_GROUPS],
+    /// The number of objects allocated, which unlike the allocation events leaves out reallocations, so that the
+    /// number of live allocations is the difference with the deallocation events.
+    object_allocations: [AtomicU64; NUM_GROUPS],
+}
+
+impl GroupMemStatsStorage {
+    const fn new() -> Self {
+        Self {
+            allocations: arr![AtomicU64::new(0); 128],
+            deallocations: arr![AtomicU64::new(0); 128],
+            allocation_events: arr![AtomicU64::new(0); 128],
+            deallocation_events: arr![AtomicU64::new(0); 128],
+            object_allocations: arr![AtomicU64::new(0); 128],
+        }
+    }
+}
+
+/// The statistics of a thread, split into two buffers.
+///
+/// Threads only write to the active buffer, while the processor drains the other one. Flipping the active buffer before
+/// draining gives the processor a near-consistent view of every group, without any locking on the allocation path.
+struct DoubleBufferedMetrics {
+    buffers: [GroupMemStatsStorage; 2],
+    /// The sequence lock that [`AllocationGroupSnapshot`] reads the buffers with, which is odd while the thread writes
+    /// to them.
+    ///
+    /// Each thread only writes to its own buffers, so each has a generation of its own rather than every allocation
+    /// contending on a single one. As the thread is also the only one writing its generation, the generation is stored
+    /// rather than incremented atomically, which keeps the allocation path free of read-modify-write instructions.
+    generation: AtomicU64,
+}
+
+impl DoubleBufferedMetrics {
+    const fn new() -> Self {
+        Self {
+            buffers: [GroupMemStatsStorage::new(), GroupMemStatsStorage::new()],
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// Writes to the active buffer, incrementing the generation before and after.
+    ///
+    /// This must only be called by the thread the buffers belong to.
+    #[inline(always)]
+    fn write(&self, write: impl FnOnce(&GroupMemStatsStorage)) {
+        let generation = self.generation.load(Ordering::Relaxed);
+        self.generation.store(generation + 1, Ordering::Relaxed);
+        // Readers that see a write to the buffers see the odd generation too.
+        fence(Ordering::Release);
+        write(&self.buffers[ACTIVE_BUFFER.load(Ordering::Relaxed) as usize]);
+        self.generation.store(generation + 2, Ordering::Release);
+    }
+}
+
+/// The index of the buffer of [`DoubleBufferedMetrics`] that threads write to.
+static ACTIVE_BUFFER: AtomicU8 = AtomicU8::new(0);
+
+/// How long the processor waits after flipping the active buffer for in-flight writes to the previous one to land.
+const BUFFER_QUIESCE_PERIOD: Duration = Duration::from_millis(1);
+
+/// Makes threads write to the other buffer, returning the index of the buffer that was active before.
+fn flip_active_buffer() -> usize {
+    ACTIVE_BUFFER.fetch_xor(1, Ordering::Relaxed) as usize
 }
 
+/// The memory statistics of a single group, summed over every thread.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct GroupMemCounts {
+    allocations: u64,
+    deallocations: u64,
+    allocation_events: u64,
+    deallocation_events: u64,
+    object_allocations: u64,
+}
+
+impl GroupMemCounts {
+    const fn is_empty(&self) -> bool {
+        self.allocations == 0
+            && self.deallocations == 0
+            && self.allocation_events == 0
+            && self.deallocation_events == 0
+            && self.object_allocations == 0
+    }
+
+    /// Gets the number of allocations that are live, which saturates at zero once a group is reset while allocations
+    /// made before are deallocated.
+    const fn live_allocations(&self) -> u64 {
+        self.object_allocations
+            .saturating_sub(self.deallocation_events)
+    }
+}
+
+/// Whether each group was reset with [`reset_group`] since the processor's last emission.
+static GROUP_RESET: [AtomicBool; NUM_GROUPS] = arr![AtomicBool::new(false); 128];
+
+/// The highest number of live allocations of each group since it was registered or reset, as sampled by the processor.
+///
+/// The live allocations are counted in the statistics of each thread, so that the allocation path doesn't write to
+/// memory shared with other threads. The peak is therefore sampled once per reporting interval, like the peak bytes of
+/// the group, and shorter spikes are not accounted for.
+static GROUP_LIVE_COUNT_PEAK: [AtomicU64; NUM_GROUPS] = arr![AtomicU64::new(0); 128];
+
+/// Records the number of live allocations of the given group, returning the highest number recorded.
+fn record_live_count_peak(group_idx: usize, live_count: u64) -> u64 {
+    GROUP_LIVE_COUNT_PEAK[group_idx]
+        .fetch_max(live_count, Ordering::Relaxed)
+        .max(live_count)
+}
+
+const DEFAULT_REPORTING_INTERVAL_MS: u64 = 5000;
+
 // Reporting interval in milliseconds.
-pub static REPORTING_INTERVAL_MS: AtomicU64 = AtomicU64::new(5000);
+pub static REPORTING_INTERVAL_MS: AtomicU64 = AtomicU64::new(DEFAULT_REPORTING_INTERVAL_MS);
+
+/// Whether allocation tracing was initialized, so that the processor thread is only ever spawned once.
+static ALLOCATION_TRACING_INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+const PROCESSOR_THREAD_NAME: &str = "vector-alloc-processor";
+
+const PROCESSOR_SUPERVISOR_THREAD_NAME: &str = "vector-alloc-supervisor";
+
+/// The number of bytes allocated to check that allocations are traced.
+const CANARY_ALLOCATION_SIZE: usize = 64;
+
+const DEFAULT_METRIC_PREFIX: &str = "vector_allocations_";
+
+/// The prefix of the metric names in the Prometheus exposition of allocation group statistics, set at initialization.
+static METRIC_PREFIX: OnceCell<String> = OnceCell::new();
+
+fn metric_prefix() -> &'static str {
+    METRIC_PREFIX
+        .get()
+        .map_or(DEFAULT_METRIC_PREFIX, String::as_str)
+}
+
+/// Removes the characters that are not allowed in a metric name from the given prefix, returning whether any were.
+///
+/// This is done in place: the prefix is allocated before allocation tracking is enabled, so it must not be reallocated
+/// afterwards.
+fn sanitize_metric_prefix(prefix: &mut String) -> bool {
+    let original_len = prefix.len();
+    prefix.retain(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':');
+    let leading_digits = prefix.bytes().take_while(u8::is_ascii_digit).count();
+    prefix.drain(..leading_digits);
+    prefix.len() != original_len
+}
 
 /// A registry for tracking each thread's group memory statistics.
-static THREAD_LOCAL_REFS: Mutex<Vec<&'static GroupMemStatsStorage>> = Mutex::new(Vec::new());
+static THREAD_LOCAL_REFS: Mutex<Vec<&'static DoubleBufferedMetrics>> = Mutex::new(Vec::new());
+
+/// Group memory statistics that the processor has already drained from each thread's statistics.
+///
+/// These are only updated while holding the [`THREAD_LOCAL_REFS`] lock, so that readers holding the lock always see
+/// a given byte either in a thread's statistics or here, but never in both or neither.
+static GROUP_MEM_TOTALS: GroupMemStatsStorage = GroupMemStatsStorage::new();
+
+/// Moves the statistics of the given group from the given buffer of every thread's statistics to [`GROUP_MEM_TOTALS`].
+fn drain_group_stats(group_idx: usize, buffer_idx: usize) -> GroupMemCounts {
+    let mut diff = GroupMemCounts::default();
+    let thread_local_refs = THREAD_LOCAL_REFS.lock().unwrap();
+    for stats in thread_local_refs
+        .iter()
+        .map(|metrics| &metrics.buffers[buffer_idx])
+    {
+        diff.allocations += stats.allocations[group_idx].swap(0, Ordering::Relaxed);
+        diff.deallocations += stats.deallocations[group_idx].swap(0, Ordering::Relaxed);
+        diff.allocation_events += stats.allocation_events[group_idx].swap(0, Ordering::Relaxed);
+        diff.deallocation_events += stats.deallocation_events[group_idx].swap(0, Ordering::Relaxed);
+        diff.object_allocations += stats.object_allocations[group_idx].swap(0, Ordering::Relaxed);
+    }
+    GROUP_MEM_TOTALS.allocations[group_idx].fetch_add(diff.allocations, Ordering::Relaxed);
+    GROUP_MEM_TOTALS.deallocations[group_idx].fetch_add(diff.deallocations, Ordering::Relaxed);
+    GROUP_MEM_TOTALS.allocation_events[group_idx]
+        .fetch_add(diff.allocation_events, Ordering::Relaxed);
+    GROUP_MEM_TOTALS.deallocation_events[group_idx]
+        .fetch_add(diff.deallocation_events, Ordering::Relaxed);
+    GROUP_MEM_TOTALS.object_allocations[group_idx]
+        .fetch_add(diff.object_allocations, Ordering::Relaxed);
+    diff
+}
 
 /// Group memory statistics per thread.
 struct GroupMemStats {
-    stats: &'static GroupMemStatsStorage,
+    stats: &'static DoubleBufferedMetrics,
 }
 
 impl GroupMemStats {
-    /// Allocates a [`GroupMemStatsStorage`], and updates the global [`THREAD_LOCAL_REFS`] registry
+    /// Allocates a [`DoubleBufferedMetrics`], and updates the global [`THREAD_LOCAL_REFS`] registry
     /// with a reference to this newly allocated memory.
     pub fn new() -> Self {
         let mut mutex = THREAD_LOCAL_REFS.lock().unwrap();
-        let stats_ref: &'static GroupMemStatsStorage = Box::leak(Box::new(GroupMemStatsStorage {
-            allocations: arr![AtomicU64::new(0) ; 128],
-            deallocations: arr![AtomicU64::new(0) ; 128],
-        }));
+        let stats_ref: &'static DoubleBufferedMetrics =
+            Box::leak(Box::new(DoubleBufferedMetrics::new()));
         let group_mem_stats = GroupMemStats { stats: stats_ref };
         mutex.push(stats_ref);
         group_mem_stats
@@ -64,25 +455,431 @@ thread_local! {
 }
 
 struct GroupInfo {
-    component_kind: String,
-    component_type: String,
-    component_id: String,
+    tags: Vec<(String, String)>,
+    /// The human-readable name of the group, which log messages about the group prefer over its ID.
+    name: Option<String>,
+    /// The trace ID last recorded on the span of the group, which exemplars of its metrics link to.
+    trace_id: Option<String>,
 }
 
 impl GroupInfo {
     const fn new() -> Self {
+        Self::with_tags(Vec::new())
+    }
+
+    const fn with_tags(tags: Vec<(String, String)>) -> Self {
         Self {
-            component_id: String::new(),
-            component_kind: String::new(),
-            component_type: String::new(),
+            tags,
+            name: None,
+            trace_id: None,
+        }
+    }
+
+    /// Adds the tags of another allocation group to this one.
+    ///
+    /// Identical tags are only kept once. Tags that share a key but differ in value are combined into a single
+    /// comma-separated value, so that the metric labels of the group stay unique.
+    fn merge_tags(&mut self, other_tags: &[(String, String)]) {
+        for (key, value) in other_tags {
+            match self
+                .tags
+                .iter_mut()
+                .find(|(existing_key, _)| existing_key == key)
+            {
+                Some((_, existing_value)) => {
+                    if !existing_value.split(',').any(|existing| existing == value) {
+                        existing_value.push(',');
+                        existing_value.push_str(value);
+                    }
+                }
+                None => self.tags.push((key.clone(), value.clone())),
+            }
         }
     }
+    /// Gets the number of heap bytes used by the tags.
+    fn heap_bytes(&self) -> usize {
+        self.tags.capacity() * std::mem::size_of::<(String, String)>()
+            + self
+                .tags
+                .iter()
+                .map(|(key, value)| key.capacity() + value.capacity())
+                .sum::<usize>()
+            + self.name.as_ref().map_or(0, String::capacity)
+    }
+}
+
+/// Builds the tags identifying the allocation group of a component.
+fn component_tags(
+    component_id: String,
+    component_type: String,
+    component_kind: String,
+) -> Vec<(String, String)> {
+    vec![
+        ("component_kind".to_string(), component_kind),
+        ("component_type".to_string(), component_type),
+        ("component_id".to_string(), component_id),
+    ]
+}
+
+/// The target of the log lines about the usage of individual allocation groups, so that log pipelines can route them.
+const GROUP_USAGE_LOG_TARGET: &str = "vector::internal_telemetry::allocations::group_usage";
+
+/// Gets the component ID, type, and kind of an allocation group from its tags, for the fields of log lines.
+///
+/// Tags that are missing, such as those of groups registered with other tags than [`component_tags`], are `unknown`.
+fn component_log_fields(tags: &[(String, String)]) -> [&str; 3] {
+    ["component_id", "component_type", "component_kind"].map(|key| {
+        tags.iter()
+            .find(|(tag_key, _)| tag_key == key)
+            .map_or("unknown", |(_, value)| value.as_str())
+    })
 }
 
 static GROUP_INFO: [Mutex<GroupInfo>; NUM_GROUPS] = arr![Mutex::new(GroupInfo::new()); 128];
 
+/// Sets the human-readable name of the given allocation group, until it is registered again.
+fn set_group_name(group_id: AllocationGroupId, name: &str) {
+    if let Some(group_idx) = group_id.slot_index() {
+        GROUP_INFO[group_idx].lock().unwrap().name = Some(name.to_string());
+    }
+}
+
+/// Gets the human-readable name of the given allocation group, if it was set.
+fn group_name(group_id: AllocationGroupId) -> Option<String> {
+    GROUP_INFO[group_id.slot_index()?]
+        .lock()
+        .unwrap()
+        .name
+        .clone()
+}
+
+/// Gets how log messages refer to the given allocation group: its name if it was set, or else its ID.
+fn group_log_name(group_id: AllocationGroupId) -> String {
+    group_name(group_id).unwrap_or_else(|| group_id.as_raw().to_string())
+}
+
+/// Builds the metric labels for the given allocation group.
+fn group_labels(group_idx: usize, namespace: &str) -> Vec<(String, String)> {
+    let group_info = GROUP_INFO[group_idx].lock().unwrap();
+    let mut labels = group_info.tags.clone();
+    labels.push(("instance".to_string(), namespace.to_string()));
+    if let Some(parent) =
+        hierarchy::allocation_group_parent(AllocationGroupId::from_raw(group_idx as u8))
+    {
+        labels.push(("parent".to_string(), parent.as_raw().to_string()));
+    }
+    #[cfg(tokio_unstable)]
+    if let Some(task_id) = *GROUP_TASK_IDS[group_idx].lock().unwrap() {
+        labels.push(("tokio_task_id".to_string(), task_id.to_string()));
+    }
+    if let Some(task_id) = task_groups::group_task_id(group_idx) {
+        labels.push(("task_id".to_string(), task_id.to_string()));
+    }
+    labels
+}
+
+/// The Tokio task that most recently entered each allocation group.
+///
+/// This is recorded when a span carrying the allocation group is entered rather than on every allocation, and lets
+/// the allocation metrics be cross-referenced with `tokio-console`.
+#[cfg(tokio_unstable)]
+static GROUP_TASK_IDS: [Mutex<Option<tokio::task::Id>>; NUM_GROUPS] = arr![Mutex::new(None); 128];
+
+/// Records the Tokio task, if any, that is entering the given allocation group.
+#[cfg(tokio_unstable)]
+pub(crate) fn record_allocation_group_task(group_id: AllocationGroupId) {
+    if let Some(task_id) = tokio::task::try_id() {
+        if let Some(group_idx) = group_id.slot_index() {
+            *GROUP_TASK_IDS[group_idx].lock().unwrap() = Some(task_id);
+        }
+    }
+}
+
+/// The number of allocated bytes each allocation group says it could free voluntarily, such as the bytes of a cache.
+static GROUP_RECLAIMABLE: [AtomicU64; NUM_GROUPS] = arr![AtomicU64::new(0); 128];
+
+/// Sets the number of bytes the allocation group of the given token could free under memory pressure.
+///
+/// This is only a hint: it doesn't change the allocated bytes of the group, and the component should set it back to
+/// zero once it actually frees the memory.
+pub fn set_reclaimable_hint(token: &AllocationGroupToken, bytes: u64) {
+    if let Some(group_idx) = token.id().slot_index() {
+        GROUP_RECLAIMABLE[group_idx].store(bytes, Ordering::Relaxed);
+    }
+}
+
+/// Gets the number of bytes the given allocation group says it could free under memory pressure.
+pub fn reclaimable_bytes(group_id: AllocationGroupId) -> u64 {
+    group_id.slot_index().map_or(0, |group_idx| {
+        GROUP_RECLAIMABLE[group_idx].load(Ordering::Relaxed)
+    })
+}
+
+/// Gets the number of bytes currently allocated by the given allocation group that it could not free voluntarily.
+///
+/// This is the hard memory requirement of the group, for judging how much memory pressure it is under.
+pub fn hard_allocated_bytes(group_id: AllocationGroupId) -> u64 {
+    current_allocated_bytes(group_id).saturating_sub(reclaimable_bytes(group_id))
+}
+
+/// Number of allocation groups with an entry in [`GROUP_INFO`], including the root allocation group.
+static ACTIVE_GROUPS: AtomicUsize = AtomicUsize::new(1);
+
+/// Allocation group IDs that were deregistered but may still have allocated bytes attributed to them, with the number
+/// of consecutive emissions they have been drained for.
+///
+/// The processor moves them to [`FREE_GROUP_IDS`] once their allocated bytes have stayed drained to zero for
+/// [`RELEASED_GROUP_TOMBSTONE_EMISSIONS`] emissions, so that a reused ID never reports the (de)allocations of its
+/// previous group under its new tags.
+static RELEASED_GROUP_IDS: Mutex<Vec<(AllocationGroupId, u8)>> = Mutex::new(Vec::new());
+
+/// The number of consecutive emissions a deregistered allocation group must have no allocated bytes for before its ID
+/// is reused.
+///
+/// A thread can still be in a deregistered group, such as while the span it is attached to is being torn down, so a
+/// group that drained once may allocate again. Waiting for several emissions lets such stragglers show up first.
+const RELEASED_GROUP_TOMBSTONE_EMISSIONS: u8 = 3;
+
+/// Allocation group IDs that can be handed out again by [`acquire_allocation_group_id`].
+static FREE_GROUP_IDS: Mutex<Vec<AllocationGroupId>> = Mutex::new(Vec::new());
+
+/// The number of times the allocation group in each slot was deregistered.
+///
+/// This tells weak references to a deregistered group apart from references to the group that later reuses its slot.
+static GROUP_GENERATIONS: [AtomicU32; NUM_GROUPS] = arr![AtomicU32::new(0); 128];
+
+/// Gets the generation of the slot of the given allocation group, which changes every time its group is deregistered.
+fn group_generation(group_id: AllocationGroupId) -> u32 {
+    group_id.slot_index().map_or(0, |group_idx| {
+        GROUP_GENERATIONS[group_idx].load(Ordering::Acquire)
+    })
+}
+
+/// The number of bytes used by allocation tracing itself, as of the processor's last emission.
+static TRACKING_OVERHEAD_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// The number of bytes allocation tracing may use before it stops registering allocation groups and recording their
+/// history.
+static MAX_TRACKING_OVERHEAD_BYTES: AtomicU64 = AtomicU64::new(u64::MAX);
+
+/// Computes the number of bytes used by allocation tracing itself.
+fn compute_tracking_overhead_bytes() -> u64 {
+    // The statistics of every thread, and the totals drained from them.
+    let thread_stats_bytes = THREAD_LOCAL_REFS.lock().unwrap().len()
+        * std::mem::size_of::<DoubleBufferedMetrics>()
+        + std::mem::size_of::<GroupMemStatsStorage>();
+    let group_info_bytes = std::mem::size_of_val(&GROUP_INFO)
+        + GROUP_INFO
+            .iter()
+            .map(|group_info| group_info.lock().unwrap().heap_bytes())
+            .sum::<usize>();
+    (thread_stats_bytes + group_info_bytes + history::heap_bytes()) as u64
+}
+
+/// Gets the number of bytes used by allocation tracing itself, as of the processor's last emission.
+pub fn tracking_overhead_bytes() -> u64 {
+    TRACKING_OVERHEAD_BYTES.load(Ordering::Relaxed)
+}
+
+fn tracking_overhead_exceeded() -> bool {
+    tracking_overhead_bytes() >= MAX_TRACKING_OVERHEAD_BYTES.load(Ordering::Relaxed)
+}
+
+/// Configuration for the allocation tracing subsystem.
+pub struct AllocationTracingConfig {
+    /// Value of the `instance` label attached to every allocation metric.
+    ///
+    /// Group IDs are only unique within a single process, so when several Vector instances report to the same
+    /// metrics backend this label keeps their series apart. Defaults to the hostname and PID of this process.
+    pub namespace: String,
+
+    /// Prefix of the metric names in the Prometheus exposition of allocation group statistics.
+    ///
+    /// Characters that are not allowed in a metric name are removed. Metrics reported through the `internal_metrics`
+    /// source are namespaced by that source instead. Defaults to `vector_allocations_`.
+    pub metric_prefix: String,
+
+    /// Whether to panic if (de)allocations are not traced when allocation tracing is initialized.
+    ///
+    /// Allocations are only traced when the tracing allocator is the global allocator, and tracking allocations is
+    /// enabled, so that a misconfiguration otherwise goes unnoticed: every allocation group just reports no bytes.
+    /// When tracking allocations is enabled, this is always checked, but only logged as an error unless this is set.
+    pub validate: bool,
+
+    /// Whether to only count how many times each allocation group is entered, instead of the bytes it allocates.
+    ///
+    /// This is for checking that allocation groups are registered and entered where expected, without the overhead of
+    /// tracking allocations, which should not be enabled along with this. Instead of the byte metrics, the processor
+    /// emits the `allocation_group_entered_total` counter for each group. Disabled by default.
+    pub dry_run: bool,
+
+    /// Whether to attach exemplars to the OpenMetrics exposition of allocation group statistics.
+    ///
+    /// The allocated bytes gauge of a group whose span had a `trace_id` field recorded carries an exemplar with that
+    /// trace ID, linking the memory of the group to a trace. Strict OpenMetrics parsers only accept exemplars on
+    /// counters and histogram buckets, so this is disabled by default.
+    pub exemplars: bool,
+
+    /// The number of bytes allocation tracing may use for its own bookkeeping.
+    ///
+    /// Once allocation tracing uses this many bytes, newly registered allocation groups are folded into the root
+    /// allocation group, and the history of allocation groups that have none yet is not recorded. Allocation tracing
+    /// uses a fixed 8 KiB for the statistics of each thread that allocates, plus about 3 KiB per reading kept in the
+    /// history of all allocation groups, plus the tags of each allocation group. Unlimited by default.
+    pub max_overhead_bytes: Option<u64>,
+
+    /// The number of readings of its allocated bytes kept in the history of each allocation group.
+    ///
+    /// A reading is taken after each reporting interval, and the oldest one is dropped once the history is full, so
+    /// this bounds the memory used by the history. It is capped at 3600 readings. Defaults to `60`.
+    pub history_len: usize,
+
+    /// The number of allocated bytes above which the processor warns about an allocation group, and the action taken
+    /// against the group while it exceeds them.
+    pub thresholds: AllocationThresholds,
+
+    /// The fraction of the cgroup memory limit below which the processor warns about the remaining headroom.
+    ///
+    /// The memory of the cgroup is only reported on Linux, where the cgroup limit is what applies to containers rather
+    /// than the memory used by the process. Defaults to `0.1`.
+    pub cgroup_headroom_warning_ratio: f64,
+
+    /// Called by the processor with the statistics of every allocation group after each reporting interval.
+    ///
+    /// This lets embedders export the statistics wherever they want. The callback runs on the processor thread with
+    /// allocation tracing suspended, so it must not block for long, or it delays the statistics of the next interval.
+    pub on_sample: Option<Box<dyn Fn(&[GroupAllocationStats]) + Send>>,
+
+    /// The sink that the processor emits the metrics of every allocation group to after each reporting interval.
+    ///
+    /// Defaults to [`MetricsCrateSink`], which emits them through the `metrics` crate. The metrics exported through
+    /// OpenTelemetry by the `otel` feature are not emitted to the sink.
+    pub metric_sink: Box<dyn AllocationMetricSink>,
+
+    /// Whether to skip setting the gauges of an allocation group whose values did not change since the last interval.
+    ///
+    /// Counters and the allocated bytes gauge are only updated when a group allocates or deallocates, but the other
+    /// gauges of every group are set again after each interval. Skipping them reduces the emission volume of
+    /// mostly-idle deployments with many registered groups, at the cost of downstream staleness detection: a group that
+    /// stays idle for longer than the expiration of internal metrics stops being reported, as if it was gone. Disabled
+    /// by default.
+    pub skip_unchanged_groups: bool,
+
+    /// The tag to sum the allocated bytes of allocation groups by, usually [`DEFAULT_AGGREGATION_TAG`].
+    ///
+    /// When set, the processor emits the `aggregated_allocated_bytes` gauge for each value of the tag, set to the sum of
+    /// the bytes allocated by the groups with that value, instead of the metrics of the bytes allocated and the
+    /// allocator calls made by each group. This matches how operators think of a component that registers several
+    /// groups, with fewer metrics. Allocation groups are not aggregated by default.
+    pub aggregate_by_tag: Option<String>,
+
+    /// Whether to still emit the metrics of the bytes allocated by each allocation group when they are aggregated with
+    /// `aggregate_by_tag`. Disabled by default.
+    pub emit_aggregated_group_metrics: bool,
+
+    /// Which components have the allocations of their allocation groups tracked.
+    ///
+    /// The allocation groups of the other components are still registered, but the tracer skips their allocations,
+    /// which saves their tracing overhead and keeps their metrics out of targeted investigations. Every component is
+    /// tracked by default.
+    pub group_filter: AllocationGroupFilter,
+
+    /// The size in bytes below which allocations are not traced.
+    ///
+    /// This is compared with the size of an allocation including the bookkeeping of allocation tracing. Smaller
+    /// allocations skip the statistics of their allocation group, and are only counted by the
+    /// `allocation_small_bytes_total` counter, which saves their tracing overhead in workloads with many tiny
+    /// allocations. Their deallocations, and reallocations to any size, are not traced either. Groups can trace every
+    /// allocation regardless with [`set_group_fidelity`]. Defaults to `0`, which traces every allocation.
+    pub min_tracked_size_bytes: usize,
+
+    /// Whether to count the bytes of the allocations that are not attributed to any allocation group.
+    ///
+    /// These are the allocations made while tracking allocations is disabled, or within
+    /// [`without_allocation_tracing`], which are otherwise not accounted for at all. They are counted by the
+    /// `untracked_bytes_total` counter, regardless of the allocation group they are made in, at the cost of an atomic
+    /// addition on each of them. Disabled by default.
+    pub count_untracked_bytes: bool,
+
+    /// What the allocations made outside of any allocation group are attributed to.
+    ///
+    /// Defaults to [`AllocationFallback::Root`], the root allocation group.
+    pub fallback: AllocationFallback,
+
+    /// The bounds within which the reporting interval adapts to how fast the process allocates.
+    ///
+    /// When set, the processor starts at the configured reporting interval, halves it after an interval in which the
+    /// process allocated at least the busy rate, and doubles it after an interval in which it allocated less than a
+    /// quarter of it. The interval is fixed by default.
+    pub adaptive_interval: Option<AdaptiveReportingInterval>,
+
+    /// Proxy that applies the allocations reported by other processes to local allocation groups.
+    ///
+    /// The proxy is bound before allocation tracking is enabled, and accepts connections once allocation tracing is
+    /// initialized.
+    #[cfg(unix)]
+    pub proxy: Option<AllocationGroupProxySource>,
+
+    /// Where to write a summary of the bytes allocated by each allocation group when the process receives `SIGUSR1`.
+    ///
+    /// This lets operators debugging a live process request a summary on demand, out-of-band from the reporting
+    /// interval. The summary also shows the change of each group since the previous one. No signal handler is
+    /// installed by default.
+    #[cfg(all(unix, feature = "allocation-tracing-sigusr1"))]
+    pub heap_summary_on_sigusr1: Option<HeapSummaryOutput>,
+}
+
+impl Default for AllocationTracingConfig {
+    fn default() -> Self {
+        let hostname = crate::get_hostname().unwrap_or_else(|_| "unknown".to_string());
+        Self {
+            namespace: format!("{}-{}", hostname, std::process::id()),
+            metric_prefix: DEFAULT_METRIC_PREFIX.to_string(),
+            validate: false,
+            dry_run: false,
+            exemplars: false,
+            max_overhead_bytes: None,
+            history_len: history::DEFAULT_HISTORY_LEN,
+            thresholds: AllocationThresholds::default(),
+            cgroup_headroom_warning_ratio: cgroup::DEFAULT_HEADROOM_WARNING_RATIO,
+            on_sample: None,
+            metric_sink: Box::new(MetricsCrateSink),
+            skip_unchanged_groups: false,
+            aggregate_by_tag: None,
+            emit_aggregated_group_metrics: false,
+            group_filter: AllocationGroupFilter::default(),
+            min_tracked_size_bytes: 0,
+            count_untracked_bytes: false,
+            fallback: AllocationFallback::default(),
+            adaptive_interval: None,
+            #[cfg(unix)]
+            proxy: None,
+            #[cfg(all(unix, feature = "allocation-tracing-sigusr1"))]
+            heap_summary_on_sigusr1: None,
+        }
+    }
+}
+
+/// The VRL functions that expose allocation tracing to VRL programs.
+pub fn vrl_functions() -> Vec<Box<dyn vrl::Function>> {
+    vec![Box::new(get_allocation_group_bytes::GetAllocationGroupBytes) as _]
+}
+
+/// The allocator that traces allocations into allocation groups.
+///
+/// It must be the `#[global_allocator]` to have any effect: allocation groups are entered and exited for the whole
+/// thread, so an instance that is used locally only ever sees a fraction of the allocations made in a group, and
+/// deallocations of memory it didn't allocate. This can't be checked at compile time, so initializing allocation tracing
+/// checks it at runtime instead, by tracing a canary allocation made through the global allocator.
+///
+/// ```ignore
+/// #[global_allocator]
+/// static ALLOC: Allocator<tikv_jemallocator::Jemalloc> =
+///     get_grouped_tracing_allocator(tikv_jemallocator::Jemalloc);
+/// ```
 pub type Allocator<A> = GroupedTraceableAllocator<A, MainTracer>;
 
+/// Wraps the given allocator in an [`Allocator`], which must be used as the `#[global_allocator]`.
 pub const fn get_grouped_tracing_allocator<A>(allocator: A) -> Allocator<A> {
     GroupedTraceableAllocator::new(allocator, MainTracer)
 }
@@ -92,121 +889,2572 @@ pub struct MainTracer;
 impl Tracer for MainTracer {
     #[inline(always)]
     fn trace_allocation(&self, object_size: usize, group_id: AllocationGroupId) {
+        let group_idx = match group_filter::tracked_slot_index(group_id) {
+            Some(group_idx) => group_idx,
+            None => return,
+        };
         // Handle the case when thread local destructor is ran.
         let _ = GROUP_MEM_STATS.try_with(|t| {
-            t.stats.allocations[group_id.as_raw() as usize]
-                .fetch_add(object_size as u64, Ordering::Relaxed)
+            t.stats.write(|stats| {
+                stats.allocations[group_idx].fetch_add(object_size as u64, Ordering::Relaxed);
+                stats.allocation_events[group_idx].fetch_add(1, Ordering::Relaxed);
+                stats.object_allocations[group_idx].fetch_add(1, Ordering::Relaxed);
+            });
         });
+        size_classes::record_allocation_size(group_idx, object_size);
+        event_log::record(group_idx, object_size, AllocationOp::Alloc);
     }
 
     #[inline(always)]
     fn trace_deallocation(&self, object_size: usize, source_group_id: AllocationGroupId) {
+        let group_idx = match group_filter::tracked_slot_index(source_group_id) {
+            Some(group_idx) => group_idx,
+            None => return,
+        };
+        // The bytes that were moved to another group are debited from that group instead.
+        let forwarded = forwarding::settle_deallocation(group_idx, object_size as u64);
+        let own_bytes =
+            object_size as u64 - forwarded.map_or(0, |(_, settled_bytes)| settled_bytes);
         // Handle the case when thread local destructor is ran.
         let _ = GROUP_MEM_STATS.try_with(|t| {
-            t.stats.deallocations[source_group_id.as_raw() as usize]
-                .fetch_add(object_size as u64, Ordering::Relaxed)
+            t.stats.write(|stats| {
+                stats.deallocations[group_idx].fetch_add(own_bytes, Ordering::Relaxed);
+                stats.deallocation_events[group_idx].fetch_add(1, Ordering::Relaxed);
+                if let Some((target_idx, settled_bytes)) = forwarded {
+                    stats.deallocations[target_idx].fetch_add(settled_bytes, Ordering::Relaxed);
+                }
+            });
         });
+        event_log::record(group_idx, object_size, AllocationOp::Dealloc);
     }
-}
 
-/// Initializes allocation tracing.
-pub fn init_allocation_tracing() {
-    for group in &GROUP_INFO {
-        let mut writer = group.lock().unwrap();
-        *writer = GroupInfo {
-            component_id: "root".to_string(),
-            component_kind: "root".to_string(),
-            component_type: "root".to_string(),
-        };
-    }
-    let alloc_processor = thread::Builder::new().name("vector-alloc-processor".to_string());
-    alloc_processor
-        .spawn(|| {
-            without_allocation_tracing(|| loop {
-                for (group_idx, group) in GROUP_INFO.iter().enumerate() {
-                    let mut allocations_diff = 0;
-                    let mut deallocations_diff = 0;
-                    let mutex = THREAD_LOCAL_REFS.lock().unwrap();
-                    for idx in 0..mutex.len() {
-                        allocations_diff +=
-                            mutex[idx].allocations[group_idx].swap(0, Ordering::Relaxed);
-                        deallocations_diff +=
-                            mutex[idx].deallocations[group_idx].swap(0, Ordering::Relaxed);
-                    }
-                    if allocations_diff == 0 && deallocations_diff == 0 {
-                        continue;
-                    }
-                    let mem_used_diff = allocations_diff as i64 - deallocations_diff as i64;
-                    let group_info = group.lock().unwrap();
-                    if allocations_diff > 0 {
-                        counter!(
-                            "component_allocated_bytes_total",
-                            allocations_diff,
-                            "component_kind" => group_info.component_kind.clone(),
-                            "component_type" => group_info.component_type.clone(),
-                            "component_id" => group_info.component_id.clone());
-                    }
-                    if deallocations_diff > 0 {
-                        counter!(
-                            "component_deallocated_bytes_total",
-                            deallocations_diff,
-                            "component_kind" => group_info.component_kind.clone(),
-                            "component_type" => group_info.component_type.clone(),
-                            "component_id" => group_info.component_id.clone());
-                    }
-                    if mem_used_diff > 0 {
-                        increment_gauge!(
-                            "component_allocated_bytes",
-                            mem_used_diff.to_f64().expect("failed to convert mem_used from int to float"),
-                            "component_kind" => group_info.component_kind.clone(),
-                            "component_type" => group_info.component_type.clone(),
-                            "component_id" => group_info.component_id.clone());
-                    }
-                    if mem_used_diff < 0 {
-                        decrement_gauge!(
-                            "component_allocated_bytes",
-                            -mem_used_diff.to_f64().expect("failed to convert mem_used from int to float"),
-                            "component_kind" => group_info.component_kind.clone(),
-                            "component_type" => group_info.component_type.clone(),
-                            "component_id" => group_info.component_id.clone());
-                    }
+    fn trace_allocation_failure(&self, object_size: usize, group_id: AllocationGroupId) {
+        if let Some(group_idx) = group_id.slot_index() {
+            GROUP_ALLOCATION_FAILURES[group_idx].fetch_add(1, Ordering::Relaxed);
+        }
+        alloc_error::report_allocation_failure(object_size, group_id);
+    }
+
+    #[cfg(feature = "allocation-latency")]
+    #[inline(always)]
+    fn trace_allocation_latency(&self, latency: Duration, group_id: AllocationGroupId) {
+        if let Some(group_idx) = group_id.slot_index() {
+            latency::record_allocation_latency(group_idx, latency);
+        }
+    }
+
+    #[inline(always)]
+    fn trace_reallocation(
+        &self,
+        old_size: usize,
+        new_size: usize,
+        source_group_id: AllocationGroupId,
+    ) {
+        let group_idx = match group_filter::tracked_slot_index(source_group_id) {
+            Some(group_idx) => group_idx,
+            None => return,
+        };
+        // The bytes freed by shrinking an allocation that were moved to another group are debited from that group.
+        let forwarded = (new_size < old_size)
+            .then(|| forwarding::settle_deallocation(group_idx, (old_size - new_size) as u64))
+            .flatten();
+        // Handle the case when thread local destructor is ran.
+        let _ = GROUP_MEM_STATS.try_with(|t| {
+            t.stats.write(|stats| {
+                if new_size >= old_size {
+                    stats.allocations[group_idx]
+                        .fetch_add((new_size - old_size) as u64, Ordering::Relaxed);
+                } else {
+                    let settled_bytes = forwarded.map_or(0, |(_, settled_bytes)| settled_bytes);
+                    stats.deallocations[group_idx].fetch_add(
+                        (old_size - new_size) as u64 - settled_bytes,
+                        Ordering::Relaxed,
+                    );
                 }
-                thread::sleep(Duration::from_millis(
-                    REPORTING_INTERVAL_MS.load(Ordering::Relaxed),
-                ));
-            })
-        })
-        .unwrap();
+                if let Some((target_idx, settled_bytes)) = forwarded {
+                    stats.deallocations[target_idx].fetch_add(settled_bytes, Ordering::Relaxed);
+                }
+                // A reallocation is a single allocator call, which leaves the number of live allocations unchanged.
+                stats.allocation_events[group_idx].fetch_add(1, Ordering::Relaxed);
+            });
+        });
+        event_log::record(group_idx, old_size, AllocationOp::Dealloc);
+        event_log::record(group_idx, new_size, AllocationOp::Alloc);
+    }
 }
 
-/// Acquires an allocation group ID.
+/// The values of the gauges that the processor sets for each allocation group after every interval.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct GroupGaugeValues {
+    reclaimable_bytes: u64,
+    live_allocations_peak: u64,
+}
+
+/// Records the gauge values of a group, returning whether they should be emitted.
 ///
-/// This creates an allocation group which allows callers to enter/exit the allocation group context, associating all
-/// (de)allocations within the context with that group. An allocation group ID must be "attached" to
-/// a [`tracing::Span`] to achieve this" we utilize the logical invariants provided by spans --
-/// entering, exiting, and how spans exist as a stack -- in order to handle keeping the "current
-/// allocation group" accurate across all threads.
-pub fn acquire_allocation_group_id(
-    component_id: String,
-    component_type: String,
-    component_kind: String,
-) -> AllocationGroupId {
-    if let Some(group_id) = AllocationGroupId::register() {
-        if let Some(group_lock) = GROUP_INFO.get(group_id.as_raw() as usize) {
-            let mut writer = group_lock.lock().unwrap();
-            *writer = GroupInfo {
-                component_id,
-                component_kind,
-                component_type,
-            };
+/// When `skip_unchanged` is set, values identical to those last emitted are not emitted again.
+fn should_emit_gauges(
+    emitted: &mut Option<GroupGaugeValues>,
+    values: GroupGaugeValues,
+    skip_unchanged: bool,
+) -> bool {
+    if skip_unchanged && *emitted == Some(values) {
+        return false;
+    }
+    *emitted = Some(values);
+    true
+}
+
+/// The labels of the allocation groups during an emission, which are built at most once per group.
+struct EmissionLabels {
+    namespace: String,
+    labels: Vec<Option<Vec<(String, String)>>>,
+}
+
+impl EmissionLabels {
+    fn new(namespace: String) -> Self {
+        Self {
+            namespace,
+            labels: vec![None; NUM_GROUPS],
+        }
+    }
+
+    /// Gets the labels of the given allocation group, building them on first use.
+    fn get(&mut self, group_idx: usize) -> &[(String, String)] {
+        let namespace = &self.namespace;
+        self.labels[group_idx].get_or_insert_with(|| group_labels(group_idx, namespace))
+    }
+}
+
+/// The state the processor keeps from one emission to the next.
+struct Processor {
+    namespace: String,
+    metric_sink: Box<dyn AllocationMetricSink>,
+    on_sample: Option<Box<dyn Fn(&[GroupAllocationStats]) + Send>>,
+    skip_unchanged_groups: bool,
+    emit_group_metrics: bool,
+    adaptive_interval: Option<AdaptiveReportingInterval>,
+    threshold_monitor: thresholds::ThresholdMonitor,
+    tag_aggregates: Option<tag_aggregates::TagAggregates>,
+    cgroup_memory_tracker: CgroupMemoryTracker,
+    #[cfg(feature = "otel")]
+    otel_metrics: otel::OtelAllocationMetrics,
+    instance_labels: Vec<(String, String)>,
+    capacity_warning_emitted: bool,
+    /// The untracked bytes as of the last emission, as the counter is never reset.
+    emitted_untracked_bytes: u64,
+    /// The labels each group's allocated bytes gauge was last emitted with.
+    emitted_labels: Vec<Option<Vec<(String, String)>>>,
+    /// The values each group's gauges were last set to.
+    emitted_gauges: Vec<Option<GroupGaugeValues>>,
+}
+
+impl Processor {
+    /// Emits the statistics of the last reporting interval.
+    fn emit(&mut self) {
+        let now = Instant::now();
+        let drained_buffer = flip_active_buffer();
+        thread::sleep(BUFFER_QUIESCE_PERIOD);
+        let mut labels = EmissionLabels::new(self.namespace.clone());
+        let mut live_bytes = vec![0; NUM_GROUPS];
+        let mut interval_allocated_bytes = 0;
+        for group_idx in 0..NUM_GROUPS {
+            let diff = drain_group_stats(group_idx, drained_buffer);
+            interval_allocated_bytes += diff.allocations;
+            live_bytes[group_idx] = self.emit_group(group_idx, &diff, now, &mut labels);
+        }
+        if let Some(adaptive_interval) = &self.adaptive_interval {
+            let interval = Duration::from_millis(REPORTING_INTERVAL_MS.load(Ordering::Relaxed));
+            let next_interval = adaptive_interval.next_interval(interval, interval_allocated_bytes);
+            REPORTING_INTERVAL_MS.store(next_interval.as_millis() as u64, Ordering::Relaxed);
+        }
+        self.emit_aggregates(&live_bytes, &mut labels);
+        if let Some(on_sample) = &self.on_sample {
+            on_sample(&allocation_snapshot());
+        }
+        reclaim_released_group_ids();
+        task_groups::release_idle_task_groups();
+        #[cfg(feature = "allocation-tracing-debug-frees")]
+        debug_frees::warn_double_frees();
+        self.emit_process_metrics();
+        self.emit_group_capacity();
+        SAMPLE_EMITTED.notify_waiters();
+    }
+
+    /// Emits the statistics of an allocation group, returning the number of bytes it currently allocates.
+    fn emit_group(
+        &mut self,
+        group_idx: usize,
+        diff: &GroupMemCounts,
+        now: Instant,
+        labels: &mut EmissionLabels,
+    ) -> u64 {
+        let allocations = GROUP_MEM_TOTALS.allocations[group_idx].load(Ordering::Relaxed);
+        let mut live_bytes = 0;
+        // The slot of untraced allocations never has any, so only registered groups are recorded.
+        if allocations > 0 {
+            let deallocations = GROUP_MEM_TOTALS.deallocations[group_idx].load(Ordering::Relaxed);
+            live_bytes = allocations.saturating_sub(deallocations);
+            report::record_peak_bytes(group_idx, live_bytes);
+            history::record_allocation_group_reading(
+                AllocationGroupId::from_raw(group_idx as u8),
+                now,
+                live_bytes,
+                !tracking_overhead_exceeded(),
+            );
+            if self.threshold_monitor.is_enabled() {
+                self.enforce_threshold(group_idx, live_bytes, now, labels);
+            }
+            self.emit_group_gauges(group_idx, diff, labels);
+        }
+        self.emit_group_counters(group_idx, labels);
+        let was_reset = GROUP_RESET[group_idx].swap(false, Ordering::Relaxed);
+        if (!diff.is_empty() || was_reset) && self.emit_group_metrics {
+            self.emit_group_bytes(group_idx, diff, allocations, live_bytes, was_reset, labels);
+        }
+        live_bytes
+    }
 
-            return group_id;
+    /// Checks an allocation group against its threshold, and enforces the action configured for exceeding it.
+    fn enforce_threshold(
+        &mut self,
+        group_idx: usize,
+        allocated_bytes: u64,
+        now: Instant,
+        labels: &mut EmissionLabels,
+    ) {
+        let tags = GROUP_INFO[group_idx].lock().unwrap().tags.clone();
+        self.threshold_monitor
+            .check(group_idx, &tags, allocated_bytes, now);
+        let action = self
+            .threshold_monitor
+            .exceeded_action(group_idx, &tags, allocated_bytes);
+        let newly_enforced = budget::set_budget_enforcement(group_idx, action);
+        if newly_enforced && action == Some(AllocationBudgetExceededAction::EmitEvent) {
+            let [component_id, _, _] = component_log_fields(&tags);
+            budget::queue_memory_pressure_event(
+                group_idx,
+                MemoryPressureEvent {
+                    component_id: component_id.to_string(),
+                    allocated_bytes,
+                    threshold_bytes: self
+                        .threshold_monitor
+                        .threshold_bytes(&tags)
+                        .unwrap_or_default(),
+                },
+            );
+        }
+        if action == Some(AllocationBudgetExceededAction::EmitMetric) {
+            self.metric_sink.record_counter(
+                "allocation_budget_exceeded_total",
+                1,
+                labels.get(group_idx),
+            );
         }
     }
 
-    // TODO: Technically, `NUM_GROUPS` is lower (128) than the upper bound for the
-    // `AllocationGroupId::register` call itself (253), so we can hardcode `NUM_GROUPS` here knowing
-    // it's the lower of the two values and will trigger first.. but this may not always be true.
-    info!("Maximum number of registrable allocation group IDs reached ({}). Allocations for component '{}' will be attributed to the root allocation group.", NUM_GROUPS, component_id);
-    AllocationGroupId::ROOT
+    /// Sets the gauges of an allocation group, unless they are unchanged and skipped.
+    fn emit_group_gauges(
+        &mut self,
+        group_idx: usize,
+        diff: &GroupMemCounts,
+        labels: &mut EmissionLabels,
+    ) {
+        let gauges = GroupGaugeValues {
+            reclaimable_bytes: GROUP_RECLAIMABLE[group_idx].load(Ordering::Relaxed),
+            live_allocations_peak: record_live_count_peak(
+                group_idx,
+                GROUP_MEM_TOTALS.object_allocations[group_idx]
+                    .load(Ordering::Relaxed)
+                    .saturating_sub(
+                        GROUP_MEM_TOTALS.deallocation_events[group_idx].load(Ordering::Relaxed),
+                    ),
+            ),
+        };
+        if should_emit_gauges(
+            &mut self.emitted_gauges[group_idx],
+            gauges,
+            self.skip_unchanged_groups && diff.is_empty(),
+        ) {
+            let labels = labels.get(group_idx);
+            self.metric_sink.record_gauge(
+                "component_reclaimable_bytes",
+                gauges.reclaimable_bytes as f64,
+                labels,
+            );
+            self.metric_sink.record_gauge(
+                "live_allocations_peak",
+                gauges.live_allocations_peak as f64,
+                labels,
+            );
+        }
+    }
+
+    /// Emits the counters an allocation group accumulates between emissions, which are reset as they are emitted.
+    fn emit_group_counters(&mut self, group_idx: usize, labels: &mut EmissionLabels) {
+        for (name, counters) in [
+            ("allocation_group_entered_total", &GROUP_ENTERED),
+            ("allocation_failures_total", &GROUP_ALLOCATION_FAILURES),
+            ("allocation_small_bytes_total", &GROUP_SMALL_ALLOC_BYTES),
+        ] {
+            let value = counters[group_idx].swap(0, Ordering::Relaxed);
+            if value > 0 {
+                self.metric_sink
+                    .record_counter(name, value, labels.get(group_idx));
+            }
+        }
+    }
+
+    /// Emits the (de)allocated bytes of an allocation group during the last reporting interval.
+    fn emit_group_bytes(
+        &mut self,
+        group_idx: usize,
+        diff: &GroupMemCounts,
+        allocations: u64,
+        live_bytes: u64,
+        was_reset: bool,
+        labels: &mut EmissionLabels,
+    ) {
+        let labels = labels.get(group_idx);
+        let mut mem_used_diff = diff.allocations as i64 - diff.deallocations as i64;
+        let emitted_labels = &mut self.emitted_labels[group_idx];
+        if emitted_labels.as_deref() != Some(labels) {
+            // The tags of the group changed, so its gauge moves over to the new labels, which only had the bytes
+            // allocated with them counted so far.
+            if let Some(previous_labels) = emitted_labels.replace(labels.to_vec()) {
+                self.metric_sink
+                    .record_gauge("component_allocated_bytes", 0.0, &previous_labels);
+                mem_used_diff = live_bytes as i64;
+            }
+        }
+        // The bytes a group allocated before it was reset can still be deallocated after, so its gauge is set to its
+        // allocated bytes, which saturate at zero, rather than moved below them.
+        if was_reset
+            || GROUP_MEM_TOTALS.deallocations[group_idx].load(Ordering::Relaxed) > allocations
+        {
+            self.metric_sink
+                .record_gauge("component_allocated_bytes", live_bytes as f64, labels);
+            mem_used_diff = 0;
+        }
+        #[cfg(feature = "otel")]
+        self.otel_metrics.record(labels, diff);
+        emit_group_diff(&*self.metric_sink, labels, diff, mem_used_diff);
+        size_classes::emit_allocation_size_classes(&*self.metric_sink, group_idx, labels);
+        #[cfg(feature = "allocation-latency")]
+        latency::emit_allocation_latency(&*self.metric_sink, group_idx, labels);
+    }
+
+    /// Emits the bytes allocated by the allocation groups along with their children, and by the values of the tag the
+    /// groups are aggregated by.
+    fn emit_aggregates(&mut self, live_bytes: &[u64], labels: &mut EmissionLabels) {
+        for (group_idx, aggregate_bytes) in hierarchy::update_aggregates(live_bytes) {
+            self.metric_sink.record_gauge(
+                "component_and_children_allocated_bytes",
+                aggregate_bytes as f64,
+                labels.get(group_idx),
+            );
+        }
+        if let Some(tag_aggregates) = &mut self.tag_aggregates {
+            for (value, aggregate_bytes) in tag_aggregates.update(live_bytes) {
+                self.metric_sink.record_gauge(
+                    "aggregated_allocated_bytes",
+                    aggregate_bytes as f64,
+                    &[
+                        (tag_aggregates.tag().to_string(), value),
+                        ("instance".to_string(), self.namespace.clone()),
+                    ],
+                );
+            }
+        }
+    }
+
+    /// Emits the memory of the whole process, and the bytes allocation tracing itself uses.
+    fn emit_process_metrics(&mut self) {
+        let overhead_bytes = compute_tracking_overhead_bytes();
+        TRACKING_OVERHEAD_BYTES.store(overhead_bytes, Ordering::Relaxed);
+        self.metric_sink.record_gauge(
+            "allocation_tracking_overhead_bytes",
+            overhead_bytes as f64,
+            &self.instance_labels,
+        );
+        let memory = process_memory();
+        if let Some(resident_bytes) = memory.resident_bytes {
+            self.metric_sink.record_gauge(
+                "process_resident_memory_bytes",
+                resident_bytes as f64,
+                &self.instance_labels,
+            );
+        }
+        if let Some(virtual_bytes) = memory.virtual_bytes {
+            self.metric_sink.record_gauge(
+                "process_virtual_memory_bytes",
+                virtual_bytes as f64,
+                &self.instance_labels,
+            );
+        }
+        self.cgroup_memory_tracker
+            .report(&*self.metric_sink, &self.instance_labels);
+        thread_stacks::emit_thread_stack_reserved_bytes(&*self.metric_sink, &self.instance_labels);
+        let untracked_bytes = untracked_bytes();
+        if untracked_bytes > self.emitted_untracked_bytes {
+            self.metric_sink.record_counter(
+                "untracked_bytes_total",
+                untracked_bytes - self.emitted_untracked_bytes,
+                &self.instance_labels,
+            );
+            self.emitted_untracked_bytes = untracked_bytes;
+        }
+    }
+
+    /// Emits how many allocation groups are in use, warning once their capacity is almost exhausted.
+    fn emit_group_capacity(&mut self) {
+        let active_groups = ACTIVE_GROUPS.load(Ordering::Relaxed);
+        self.metric_sink.record_gauge(
+            "allocation_groups_active",
+            active_groups as f64,
+            &self.instance_labels,
+        );
+        let groups_overflowed = GROUPS_OVERFLOWED.swap(0, Ordering::Relaxed);
+        if groups_overflowed > 0 {
+            self.metric_sink.record_counter(
+                "allocation_groups_overflowed_total",
+                groups_overflowed,
+                &self.instance_labels,
+            );
+        }
+        let capacity_exceeded =
+            active_groups as f64 >= NUM_GROUPS as f64 * GROUP_CAPACITY_WARNING_RATIO;
+        if capacity_exceeded && !self.capacity_warning_emitted {
+            warn!(
+                message = "Allocation group capacity almost exhausted. Allocations of newly registered components may be attributed to the overflow allocation group.",
+                active_groups,
+                capacity = NUM_GROUPS,
+            );
+        }
+        self.capacity_warning_emitted = capacity_exceeded;
+    }
+}
+
+/// Initializes allocation tracing, returning a handle to shut its processor down.
+///
+/// The configuration is moved into the processor thread, which never deallocates it, even once shut down, so it is
+/// safe to build it before allocation tracking is enabled. Only the first call has any effect: the handles returned by
+/// later calls do nothing.
+pub fn init_allocation_tracing(config: AllocationTracingConfig) -> AllocationProcessorHandle {
+    if ALLOCATION_TRACING_INITIALIZED.swap(true, Ordering::Relaxed) {
+        warn!("Allocation tracing is already initialized.");
+        return AllocationProcessorHandle { supervisor: None };
+    }
+
+    let AllocationTracingConfig {
+        namespace,
+        mut metric_prefix,
+        validate,
+        dry_run,
+        exemplars,
+        max_overhead_bytes,
+        history_len,
+        thresholds,
+        cgroup_headroom_warning_ratio,
+        on_sample,
+        metric_sink,
+        skip_unchanged_groups,
+        aggregate_by_tag,
+        emit_aggregated_group_metrics,
+        group_filter,
+        min_tracked_size_bytes,
+        count_untracked_bytes,
+        fallback,
+        adaptive_interval,
+        #[cfg(unix)]
+        proxy,
+        #[cfg(all(unix, feature = "allocation-tracing-sigusr1"))]
+        heap_summary_on_sigusr1,
+    } = config;
+    MIN_TRACKED_SIZE_BYTES.store(min_tracked_size_bytes, Ordering::Relaxed);
+    EXEMPLARS.store(exemplars, Ordering::Relaxed);
+    COUNT_UNTRACKED_BYTES.store(count_untracked_bytes, Ordering::Relaxed);
+    if let Some(max_overhead_bytes) = max_overhead_bytes {
+        MAX_TRACKING_OVERHEAD_BYTES.store(max_overhead_bytes, Ordering::Relaxed);
+    }
+    if history::set_history_len(history_len) {
+        warn!(
+            message = "Allocation group history length is too large, and was capped.",
+            history_len,
+            max_history_len = history::MAX_HISTORY_LEN,
+        );
+    }
+    if sanitize_metric_prefix(&mut metric_prefix) {
+        warn!(
+            message = "Allocation metric prefix contains characters not allowed in metric names, which were removed.",
+            metric_prefix = %metric_prefix,
+        );
+    }
+    // Only the first initialization gets this far, so the prefix cannot already be set.
+    let _ = METRIC_PREFIX.set(metric_prefix);
+    event_log::init_from_env();
+    #[cfg(all(unix, feature = "allocation-tracing-sigusr1"))]
+    if let Some(output) = heap_summary_on_sigusr1 {
+        if let Err(error) = heap_summary::install_signal_handler(output) {
+            error!(message = "Failed to install the heap summary signal handler.", %error);
+        }
+    }
+    panic_hook::install_panic_hook();
+    alloc_error::install_alloc_error_hook();
+
+    *GROUP_INFO[AllocationGroupId::OVERFLOW.traced_slot_index()]
+        .lock()
+        .unwrap() = GroupInfo::with_tags(component_tags(
+        "overflow".to_string(),
+        "overflow".to_string(),
+        "overflow".to_string(),
+    ));
+    for group in &GROUP_INFO {
+        let mut writer = group.lock().unwrap();
+        if writer.tags.is_empty() {
+            *writer = GroupInfo::with_tags(component_tags(
+                "root".to_string(),
+                "root".to_string(),
+                "root".to_string(),
+            ));
+        }
+    }
+    if dry_run {
+        DRY_RUN.store(true, Ordering::Relaxed);
+        info!("Allocation tracing is in dry run mode. Only the entries into allocation groups are counted.");
+    } else if validate || TRACK_ALLOCATIONS.load(Ordering::Relaxed) {
+        validate_allocation_tracking(validate);
+    } else {
+        warn!("Allocation tracing is initialized, but tracking allocations is not enabled.");
+    }
+    // The filter is only set once the canary was traced, so that a filter not allowing it doesn't fail validation.
+    group_filter::set_group_filter(group_filter);
+    task_groups::init_task_fallback(fallback);
+
+    thresholds::set_thresholds(thresholds.clone());
+
+    let supervisor = thread::Builder::new()
+        .name(PROCESSOR_SUPERVISOR_THREAD_NAME.to_string())
+        .spawn(move || {
+            let mut processor = Processor {
+                instance_labels: vec![("instance".to_string(), namespace.clone())],
+                namespace,
+                metric_sink,
+                on_sample,
+                skip_unchanged_groups,
+                emit_group_metrics: aggregate_by_tag.is_none() || emit_aggregated_group_metrics,
+                adaptive_interval,
+                threshold_monitor: thresholds::ThresholdMonitor::new(thresholds),
+                tag_aggregates: aggregate_by_tag.map(tag_aggregates::TagAggregates::new),
+                cgroup_memory_tracker: CgroupMemoryTracker::new(cgroup_headroom_warning_ratio),
+                #[cfg(feature = "otel")]
+                otel_metrics: otel::OtelAllocationMetrics::new(),
+                capacity_warning_emitted: false,
+                emitted_untracked_bytes: 0,
+                emitted_labels: vec![None; NUM_GROUPS],
+                emitted_gauges: vec![None; NUM_GROUPS],
+            };
+            supervise_processor(move || processor.emit());
+        })
+        .unwrap();
+
+    #[cfg(unix)]
+    {
+        if let Some(proxy) = proxy {
+            if let Err(error) = proxy.spawn() {
+                error!(message = "Failed to start allocation proxy.", %error);
+            }
+        }
+    }
+
+    AllocationProcessorHandle {
+        supervisor: Some(supervisor),
+    }
+}
+
+/// Whether the processor was asked to shut down, after a final emission.
+static PROCESSOR_SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+/// The processor thread, so that it can be woken up to shut down.
+static PROCESSOR_THREAD: Mutex<Option<thread::Thread>> = Mutex::new(None);
+
+/// A handle to the allocation processor, returned by [`init_allocation_tracing`].
+pub struct AllocationProcessorHandle {
+    supervisor: Option<thread::JoinHandle<()>>,
+}
+
+impl AllocationProcessorHandle {
+    /// Shuts the allocation processor down, once it has emitted the statistics of the interval in progress.
+    ///
+    /// This blocks until the final emission is done, so it should be called once every component has stopped, for
+    /// their last (de)allocations to be emitted.
+    pub fn shutdown(self) {
+        let supervisor = match self.supervisor {
+            Some(supervisor) => supervisor,
+            None => return,
+        };
+        PROCESSOR_SHUTDOWN.store(true, Ordering::Release);
+        if let Some(processor) = &*PROCESSOR_THREAD.lock().unwrap() {
+            processor.unpark();
+        }
+        supervisor.thread().unpark();
+        if supervisor.join().is_err() {
+            error!("Allocation processor supervisor thread panicked.");
+        }
+    }
+}
+
+/// Emits the (de)allocations of an allocation group during the last reporting interval.
+///
+/// The allocated bytes gauge changes by `mem_used_diff`, which differs from the bytes of `diff` when the labels of the
+/// group changed.
+fn emit_group_diff(
+    metric_sink: &dyn AllocationMetricSink,
+    labels: &[(String, String)],
+    diff: &GroupMemCounts,
+    mem_used_diff: i64,
+) {
+    if diff.allocations > 0 {
+        metric_sink.record_counter("component_allocated_bytes_total", diff.allocations, labels);
+    }
+    if diff.deallocations > 0 {
+        metric_sink.record_counter(
+            "component_deallocated_bytes_total",
+            diff.deallocations,
+            labels,
+        );
+    }
+    for (op, events) in [
+        ("alloc", diff.allocation_events),
+        ("dealloc", diff.deallocation_events),
+    ] {
+        if events > 0 {
+            let mut labels = labels.to_vec();
+            labels.push(("op".to_string(), op.to_string()));
+            metric_sink.record_counter("component_allocation_events_total", events, &labels);
+        }
+    }
+    if mem_used_diff > 0 {
+        metric_sink.increment_gauge(
+            "component_allocated_bytes",
+            mem_used_diff
+                .to_f64()
+                .expect("failed to convert mem_used from int to float"),
+            labels,
+        );
+    }
+    if mem_used_diff < 0 {
+        metric_sink.decrement_gauge(
+            "component_allocated_bytes",
+            -mem_used_diff
+                .to_f64()
+                .expect("failed to convert mem_used from int to float"),
+            labels,
+        );
+    }
+}
+
+/// Runs the processor's emissions on a thread of their own, spawning it again if it ever exits.
+///
+/// The panic of a single emission is already caught by [`run_emissions`], so the thread only exits if it panics
+/// outside of an emission. The emissions then resume after a reporting interval.
+fn supervise_processor(mut emit: impl FnMut() + Send) {
+    loop {
+        let joined = thread::scope(|scope| {
+            let emit = &mut emit;
+            thread::Builder::new()
+                .name(PROCESSOR_THREAD_NAME.to_string())
+                .spawn_scoped(scope, move || {
+                    *PROCESSOR_THREAD.lock().unwrap() = Some(thread::current());
+                    without_allocation_tracing(|| run_emissions(emit, wait_for_next_emission))
+                })
+                .map(|processor| processor.join())
+        });
+        match joined {
+            Ok(Ok(())) => {
+                // The state of the processor includes its configuration, which was allocated before allocation
+                // tracking was enabled, so it must never be deallocated.
+                std::mem::forget(emit);
+                return;
+            }
+            Ok(Err(_)) => error!("Allocation processor thread panicked. Restarting it."),
+            Err(error) => error!(
+                message = "Failed to spawn allocation processor thread. Retrying.",
+                %error,
+            ),
+        }
+        // Once the processor is shut down, this returns at once, and the thread spawned again only makes the final
+        // emission.
+        wait_for_next_emission();
+    }
+}
+
+/// Runs the processor's emissions until `wait` returns false, and then a final one.
+///
+/// The panic of an emission is caught and logged, so that a bug in emitting the statistics of one interval doesn't
+/// stop the statistics of every later one from being emitted.
+fn run_emissions(mut emit: impl FnMut(), mut wait: impl FnMut() -> bool) {
+    let mut keep_running = true;
+    loop {
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(&mut emit)) {
+            let panic_message = payload
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("unknown");
+            error!(
+                message = "Allocation processor emission panicked. Statistics of this interval may be incomplete.",
+                %panic_message,
+            );
+        }
+        if !keep_running {
+            return;
+        }
+        keep_running = wait();
+    }
+}
+
+/// Waits for the next reporting interval, returning whether the processor should keep running.
+///
+/// This returns early if the processor is asked to shut down.
+fn wait_for_next_emission() -> bool {
+    let deadline =
+        Instant::now() + Duration::from_millis(REPORTING_INTERVAL_MS.load(Ordering::Relaxed));
+    loop {
+        if PROCESSOR_SHUTDOWN.load(Ordering::Acquire) {
+            return false;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        // Signal handlers can't unpark the processor, so requests for a heap summary are polled for.
+        #[cfg(all(unix, feature = "allocation-tracing-sigusr1"))]
+        let timeout = {
+            heap_summary::write_requested_heap_summary();
+            (deadline - now).min(heap_summary::REQUEST_POLL_INTERVAL)
+        };
+        #[cfg(not(all(unix, feature = "allocation-tracing-sigusr1")))]
+        let timeout = deadline - now;
+        // Parking can wake up spuriously, so the deadline and the shutdown are checked again.
+        thread::park_timeout(timeout);
+    }
+}
+
+/// Checks that a canary allocation made in an allocation group of its own is traced, as well as its deallocation.
+///
+/// This catches the tracing allocator not being the global allocator, in which case the canary is allocated by
+/// another allocator that doesn't trace it.
+///
+/// # Panics
+///
+/// Panics if the allocation is not traced and `strict` is set. Otherwise, an error is logged.
+fn validate_allocation_tracking(strict: bool) {
+    let registration = register_allocation_group(component_tags(
+        "allocation_tracing_canary".to_string(),
+        "canary".to_string(),
+        "internal".to_string(),
+    ));
+    if registration.id.is_shared() {
+        warn!("Allocation tracking could not be validated, as no allocation group is available.");
+        return;
+    }
+
+    // The canary is smaller than the minimum tracked size it may be initialized with, which must not skip it.
+    set_group_fidelity(registration.id, AllocationFidelity::Full);
+    let token = AllocationGroupToken::from(registration.id);
+    token.enter();
+    let canary = std::hint::black_box(Vec::<u8>::with_capacity(CANARY_ALLOCATION_SIZE));
+    token.exit();
+    let allocated_bytes = token.current_allocated_bytes();
+    drop(canary);
+    let leaked_bytes = token.current_allocated_bytes();
+    token.drain_and_deregister();
+
+    if allocated_bytes < CANARY_ALLOCATION_SIZE as u64 {
+        assert!(
+            !strict,
+            "Allocation tracking is not working: an allocation of {} bytes was not traced. Make sure \
+             `get_grouped_tracing_allocator` is used as the `#[global_allocator]` and `TRACK_ALLOCATIONS` is set \
+             before initializing allocation tracing.",
+            CANARY_ALLOCATION_SIZE,
+        );
+        error!(
+            message = "Allocation tracking is not working: a canary allocation was not traced. Make sure `get_grouped_tracing_allocator` is used as the `#[global_allocator]`.",
+            canary_bytes = CANARY_ALLOCATION_SIZE,
+            allocated_bytes,
+        );
+    } else if leaked_bytes > 0 {
+        warn!(
+            message =
+                "Allocation tracking is not working correctly: deallocations are not fully traced.",
+            leaked_bytes,
+        );
+    }
+}
+
+/// The registration of an allocation group.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AllocationGroupRegistration {
+    /// The ID of the allocation group.
+    pub id: AllocationGroupId,
+
+    /// The tags the metrics of the allocation group are labeled with.
+    pub tags: Vec<(String, String)>,
+}
+
+/// Registers an allocation group with the given tags.
+///
+/// The tags are stored before the group ID is handed out, so every (de)allocation made in the group is reported with
+/// them, starting with the processor's very next emission. If no more allocation groups can be registered, the
+/// registration of the overflow allocation group, which every such component shares, is returned instead. When
+/// allocation tracing uses more bytes than the configured limit, the registration of the root allocation group is.
+pub fn register_allocation_group(tags: Vec<(String, String)>) -> AllocationGroupRegistration {
+    if tracking_overhead_exceeded() {
+        info!(
+            message = "Allocation tracing overhead limit reached. Allocations will be attributed to the root allocation group.",
+            overhead_bytes = tracking_overhead_bytes(),
+            internal_log_rate_limit = true,
+        );
+        return allocation_group_registration(AllocationGroupId::ROOT)
+            .expect("root allocation group is always registered");
+    }
+
+    let group_id = FREE_GROUP_IDS
+        .lock()
+        .unwrap()
+        .pop()
+        .or_else(AllocationGroupId::register);
+    if let Some(group_id) = group_id {
+        if let (Some(group_idx), false) = (group_id.slot_index(), group_id.is_shared()) {
+            set_group_tags(group_id, tags.clone());
+            group_filter::match_group(group_idx, &tags);
+            report::set_registered(group_id, true);
+            set_group_fidelity(group_id, AllocationFidelity::Default);
+            budget::set_budget_enforcement(group_idx, None);
+            ACTIVE_GROUPS.fetch_add(1, Ordering::Relaxed);
+
+            return AllocationGroupRegistration { id: group_id, tags };
+        }
+    }
+
+    let component_id = tags
+        .iter()
+        .find(|(key, _)| key == "component_id")
+        .map_or("", |(_, value)| value.as_str());
+    info!("Maximum number of registrable allocation group IDs reached ({}). Allocations for component '{}' will be attributed to the overflow allocation group.", NUM_GROUPS, component_id);
+    overflow_registration()
+}
+
+/// Gets the registration of the overflow allocation group, for a component registered once every other slot is taken.
+fn overflow_registration() -> AllocationGroupRegistration {
+    GROUPS_OVERFLOWED.fetch_add(1, Ordering::Relaxed);
+    allocation_group_registration(AllocationGroupId::OVERFLOW)
+        .expect("overflow allocation group always has a slot")
+}
+
+/// Gets the registration of the given allocation group.
+pub fn allocation_group_registration(
+    group_id: AllocationGroupId,
+) -> Option<AllocationGroupRegistration> {
+    group_id
+        .slot_index()
+        .map(|group_idx| &GROUP_INFO[group_idx])
+        .map(|group_info| AllocationGroupRegistration {
+            id: group_id,
+            tags: group_info.lock().unwrap().tags.clone(),
+        })
+}
+
+/// Updates the tags of an allocation group from the fields recorded on the span it is attached to.
+///
+/// Only the fields named like a tag of the group are taken into account, such as a `component_id` that is only known
+/// after the span was created, so that other span fields don't become metric labels. A `trace_id` field is kept as the
+/// trace ID that the exemplars of the group link to instead.
+pub(crate) fn record_allocation_group_tags(group_id: AllocationGroupId, values: &Record<'_>) {
+    struct TagVisitor<'a> {
+        tags: &'a mut [(String, String)],
+        trace_id: &'a mut Option<String>,
+    }
+
+    impl<'a> Visit for TagVisitor<'a> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            if field.name() == TRACE_ID_FIELD {
+                *self.trace_id = Some(value.to_string());
+            }
+            if let Some((_, tag_value)) = self.tags.iter_mut().find(|(key, _)| key == field.name())
+            {
+                *tag_value = value.to_string();
+            }
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            if field.name() == TRACE_ID_FIELD {
+                *self.trace_id = Some(format!("{:?}", value));
+            }
+            if let Some((_, tag_value)) = self.tags.iter_mut().find(|(key, _)| key == field.name())
+            {
+                *tag_value = format!("{:?}", value);
+            }
+        }
+    }
+
+    without_allocation_tracing(|| {
+        if let Some(group_idx) = group_id.slot_index() {
+            let mut group_info = GROUP_INFO[group_idx].lock().unwrap();
+            let GroupInfo { tags, trace_id, .. } = &mut *group_info;
+            values.record(&mut TagVisitor { tags, trace_id });
+        }
+    });
+}
+
+fn set_group_tags(group_id: AllocationGroupId, tags: Vec<(String, String)>) {
+    let group_idx = match group_id.slot_index() {
+        Some(group_idx) => group_idx,
+        None => return,
+    };
+    *GROUP_INFO[group_idx].lock().unwrap() = GroupInfo::with_tags(tags);
+    hierarchy::clear_relationships(group_id);
+    GROUP_RECLAIMABLE[group_idx].store(0, Ordering::Relaxed);
+    GROUP_LIVE_COUNT_PEAK[group_idx].store(0, Ordering::Relaxed);
+    #[cfg(tokio_unstable)]
+    {
+        *GROUP_TASK_IDS[group_idx].lock().unwrap() = None;
+    }
+}
+
+/// Acquires an allocation group ID.
+///
+/// This creates an allocation group which allows callers to enter/exit the allocation group context, associating all
+/// (de)allocations within the context with that group. An allocation group ID must be "attached" to
+/// a [`tracing::Span`] to achieve this" we utilize the logical invariants provided by spans --
+/// entering, exiting, and how spans exist as a stack -- in order to handle keeping the "current
+/// allocation group" accurate across all threads.
+pub fn acquire_allocation_group_id(
+    component_id: String,
+    component_type: String,
+    component_kind: String,
+) -> AllocationGroupId {
+    register_allocation_group(component_tags(component_id, component_type, component_kind)).id
+}
+
+/// Acquires an allocation group ID for a component, with additional tags.
+///
+/// The tags are appended to those identifying the component, and every tag is a label of the metrics of the group, in
+/// every way they are emitted. In multi-tenant setups, this is how memory is broken down by a dimension other than the
+/// component, such as a `pipeline` tag naming the logical pipeline that the component belongs to.
+pub fn acquire_allocation_group_id_with_tags(
+    component_id: String,
+    component_type: String,
+    component_kind: String,
+    tags: Vec<(String, String)>,
+) -> AllocationGroupId {
+    let mut group_tags = component_tags(component_id, component_type, component_kind);
+    group_tags.extend(tags);
+    register_allocation_group(group_tags).id
+}
+
+/// Acquires an allocation group ID whose group is a child of the `parent` allocation group.
+///
+/// This is for components made up of sub-components, such as a source with an internal consumer pool: the bytes of the
+/// child are reported on their own, and also as part of the aggregate of the parent and all of its descendants, under
+/// `component_and_children_allocated_bytes`. If no more allocation groups can be registered, the root allocation group
+/// is returned, without a parent.
+pub fn acquire_allocation_group_id_with_parent(
+    tags: Vec<(String, String)>,
+    parent: AllocationGroupId,
+) -> AllocationGroupId {
+    let group_id = register_allocation_group(tags).id;
+    hierarchy::set_parent(group_id, parent);
+    group_id
+}
+
+/// Gets the total memory statistics of the given allocation group.
+///
+/// This includes the (de)allocations that the processor has not yet reported. The caller must hold the
+/// [`THREAD_LOCAL_REFS`] lock, which is passed in as `thread_local_refs`.
+fn group_mem_totals(
+    thread_local_refs: &[&'static DoubleBufferedMetrics],
+    group_idx: usize,
+) -> GroupMemCounts {
+    let mut totals = GroupMemCounts {
+        allocations: GROUP_MEM_TOTALS.allocations[group_idx].load(Ordering::Relaxed),
+        deallocations: GROUP_MEM_TOTALS.deallocations[group_idx].load(Ordering::Relaxed),
+        allocation_events: GROUP_MEM_TOTALS.allocation_events[group_idx].load(Ordering::Relaxed),
+        deallocation_events: GROUP_MEM_TOTALS.deallocation_events[group_idx]
+            .load(Ordering::Relaxed),
+        object_allocations: GROUP_MEM_TOTALS.object_allocations[group_idx].load(Ordering::Relaxed),
+    };
+    for stats in thread_local_refs
+        .iter()
+        .flat_map(|metrics| metrics.buffers.iter())
+    {
+        totals.allocations += stats.allocations[group_idx].load(Ordering::Relaxed);
+        totals.deallocations += stats.deallocations[group_idx].load(Ordering::Relaxed);
+        totals.allocation_events += stats.allocation_events[group_idx].load(Ordering::Relaxed);
+        totals.deallocation_events += stats.deallocation_events[group_idx].load(Ordering::Relaxed);
+        totals.object_allocations += stats.object_allocations[group_idx].load(Ordering::Relaxed);
+    }
+    totals
+}
+
+/// Gets the fraction of its threshold that the given allocation group currently allocates, if it has a threshold.
+///
+/// This is `0.0` when the group allocates nothing and `1.0` at its threshold, and keeps growing past it. Groups only
+/// have a threshold once allocation tracing is initialized with one that applies to them.
+pub fn memory_pressure_ratio(group_id: AllocationGroupId) -> Option<f64> {
+    let mut ratio = None;
+    without_allocation_tracing(|| {
+        if let Some(group_idx) = group_id.slot_index() {
+            let tags = GROUP_INFO[group_idx].lock().unwrap().tags.clone();
+            ratio = thresholds::memory_pressure_ratio(&tags, current_allocated_bytes(group_id));
+        }
+    });
+    ratio
+}
+
+/// Gets the number of bytes currently allocated by the given allocation group.
+///
+/// This includes the (de)allocations that the processor has not yet reported.
+pub fn current_allocated_bytes(group_id: AllocationGroupId) -> u64 {
+    let group_idx = match group_id.slot_index() {
+        Some(group_idx) => group_idx,
+        None => return 0,
+    };
+    let mut allocated_bytes = 0;
+    without_allocation_tracing(|| {
+        let thread_local_refs = THREAD_LOCAL_REFS.lock().unwrap();
+        let totals = group_mem_totals(&thread_local_refs, group_idx);
+        allocated_bytes = totals.allocations.saturating_sub(totals.deallocations);
+    });
+    allocated_bytes
+}
+
+/// Resets the statistics of the given allocation group, as if it had never allocated.
+///
+/// This is meant for components that keep their allocation group across a reload, so that the memory they use after
+/// the reload isn't conflated with the memory they used before. The bytes, counts and peaks of the group are zeroed
+/// together, while holding the lock the processor drains statistics with. Allocations made before the reset can still
+/// be deallocated after it, which the allocated bytes and live count of the group don't underflow from: they saturate
+/// at zero instead, so they undercount until the group allocates more than those deallocations again.
+pub fn reset_group(group_id: AllocationGroupId) {
+    let group_idx = match group_id.slot_index() {
+        Some(group_idx) => group_idx,
+        None => return,
+    };
+    without_allocation_tracing(|| {
+        let thread_local_refs = THREAD_LOCAL_REFS.lock().unwrap();
+        for stats in thread_local_refs
+            .iter()
+            .flat_map(|metrics| metrics.buffers.iter())
+            .chain(Some(&GROUP_MEM_TOTALS))
+        {
+            stats.allocations[group_idx].store(0, Ordering::Relaxed);
+            stats.deallocations[group_idx].store(0, Ordering::Relaxed);
+            stats.allocation_events[group_idx].store(0, Ordering::Relaxed);
+            stats.deallocation_events[group_idx].store(0, Ordering::Relaxed);
+            stats.object_allocations[group_idx].store(0, Ordering::Relaxed);
+        }
+        GROUP_LIVE_COUNT_PEAK[group_idx].store(0, Ordering::Relaxed);
+        report::reset_peak_bytes(group_idx);
+        GROUP_RESET[group_idx].store(true, Ordering::Relaxed);
+    });
+}
+
+/// Gets the tags of the allocation group that is active on the current thread, for annotating log lines with the
+/// component memory is being allocated for.
+///
+/// Returns `None` when no allocation group other than the root allocation group is active.
+pub fn current_allocation_group_tags() -> Option<Vec<(String, String)>> {
+    // The active allocation group must be read before it is suspended, as the root group is active while it is.
+    let group_id = current_allocation_group();
+    if group_id == AllocationGroupId::ROOT {
+        return None;
+    }
+
+    let mut tags = None;
+    without_allocation_tracing(|| {
+        tags = allocation_group_registration(group_id).map(|registration| registration.tags);
+    });
+    tags
+}
+
+/// Allocation statistics of a single allocation group.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GroupAllocationStats {
+    /// The ID of the allocation group.
+    pub group_id: AllocationGroupId,
+
+    /// The tags the allocation group was registered with.
+    pub tags: Vec<(String, String)>,
+
+    /// The parent of the allocation group, if it was registered as the child of another group.
+    pub parent: Option<AllocationGroupId>,
+
+    /// The total number of bytes allocated by the allocation group.
+    pub allocated_bytes: u64,
+
+    /// The total number of bytes deallocated by the allocation group.
+    pub deallocated_bytes: u64,
+}
+
+impl GroupAllocationStats {
+    /// Gets the number of bytes currently allocated by the allocation group.
+    pub const fn current_allocated_bytes(&self) -> u64 {
+        self.allocated_bytes.saturating_sub(self.deallocated_bytes)
+    }
+}
+
+/// Captures the statistics of every allocation group that has allocated or deallocated any bytes.
+///
+/// Each group is read independently, so this is not a consistent point-in-time view across groups: use
+/// [`AllocationGroupSnapshot::capture`] for one.
+pub fn allocation_snapshot() -> Vec<GroupAllocationStats> {
+    let mut snapshot = Vec::new();
+    without_allocation_tracing(|| {
+        let totals = {
+            let thread_local_refs = THREAD_LOCAL_REFS.lock().unwrap();
+            (1..NUM_GROUPS)
+                .map(|group_idx| group_mem_totals(&thread_local_refs, group_idx))
+                .collect::<Vec<_>>()
+        };
+        snapshot = totals
+            .into_iter()
+            .zip(1..NUM_GROUPS)
+            .filter(|(totals, _)| !totals.is_empty())
+            .map(|(totals, group_idx)| GroupAllocationStats {
+                group_id: AllocationGroupId::from_raw(group_idx as u8),
+                tags: GROUP_INFO[group_idx].lock().unwrap().tags.clone(),
+                parent: hierarchy::allocation_group_parent(AllocationGroupId::from_raw(
+                    group_idx as u8,
+                )),
+                allocated_bytes: totals.allocations,
+                deallocated_bytes: totals.deallocations,
+            })
+            .collect();
+    });
+    snapshot
+}
+
+/// Woken up by the processor after each of its emissions, for [`next_sample`].
+static SAMPLE_EMITTED: Lazy<Notify> = Lazy::new(Notify::new);
+
+/// Waits for the processor's next emission, and captures the statistics of every allocation group right after it.
+///
+/// This lets tests and tools wait for the statistics to be emitted rather than sleep for an emission interval. The
+/// emission that is waited for is the first one after this is called, rather than after the future is first polled.
+/// The processor never waits for its callers: an emission only wakes up the futures that are already waiting for it.
+pub fn next_sample() -> impl Future<Output = Vec<GroupAllocationStats>> {
+    let emitted = SAMPLE_EMITTED.notified();
+    async move {
+        emitted.await;
+        allocation_snapshot()
+    }
+}
+
+/// Iterates over the statistics of every allocation group that currently has allocated bytes.
+///
+/// Each group is read when the iterator reaches it, and no lock is held between items, so this is a weakly-consistent
+/// view: groups can change while the iteration is in progress, and the items are not a point-in-time view across
+/// groups.
+pub fn iter_live_groups() -> impl Iterator<Item = GroupAllocationStats> {
+    (1..NUM_GROUPS).filter_map(|group_idx| {
+        let mut stats = None;
+        without_allocation_tracing(|| {
+            let totals = {
+                let thread_local_refs = THREAD_LOCAL_REFS.lock().unwrap();
+                group_mem_totals(&thread_local_refs, group_idx)
+            };
+            if totals.allocations > totals.deallocations {
+                stats = Some(GroupAllocationStats {
+                    group_id: AllocationGroupId::from_raw(group_idx as u8),
+                    tags: GROUP_INFO[group_idx].lock().unwrap().tags.clone(),
+                    parent: hierarchy::allocation_group_parent(AllocationGroupId::from_raw(
+                        group_idx as u8,
+                    )),
+                    allocated_bytes: totals.allocations,
+                    deallocated_bytes: totals.deallocations,
+                });
+            }
+        });
+        stats
+    })
+}
+
+/// The bytes currently allocated by every allocation group at some point in time.
+#[derive(Clone, Debug)]
+pub struct Baseline {
+    live_bytes: Vec<u64>,
+}
+
+/// The change in the bytes allocated by an allocation group since a [`Baseline`] was captured.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GroupDelta {
+    /// The ID of the allocation group.
+    pub group_id: AllocationGroupId,
+
+    /// The number of bytes allocated since the baseline, or deallocated if negative.
+    pub delta_bytes: i64,
+}
+
+/// Gets the bytes currently allocated by each allocation group, indexed by the raw group ID.
+fn live_bytes() -> Vec<u64> {
+    let thread_local_refs = THREAD_LOCAL_REFS.lock().unwrap();
+    (0..NUM_GROUPS)
+        .map(|group_idx| {
+            let totals = group_mem_totals(&thread_local_refs, group_idx);
+            totals.allocations.saturating_sub(totals.deallocations)
+        })
+        .collect()
+}
+
+/// Captures the bytes currently allocated by every allocation group, to later compare against with [`diff_since`], or
+/// with a later baseline with [`Baseline::diff`].
+pub fn baseline() -> Baseline {
+    let mut baseline = None;
+    without_allocation_tracing(|| {
+        baseline = Some(Baseline {
+            live_bytes: live_bytes(),
+        });
+    });
+    baseline.expect("baseline is always captured")
+}
+
+/// Gets the change in the bytes allocated by every allocation group whose allocated bytes changed since `baseline`.
+pub fn diff_since(baseline: &Baseline) -> Vec<GroupDelta> {
+    let mut deltas = Vec::new();
+    without_allocation_tracing(|| {
+        deltas = live_bytes()
+            .into_iter()
+            .zip(&baseline.live_bytes)
+            .enumerate()
+            .skip(1)
+            .filter(|(_, (current, previous))| current != *previous)
+            .map(|(group_idx, (current, previous))| GroupDelta {
+                group_id: AllocationGroupId::from_raw(group_idx as u8),
+                delta_bytes: current as i64 - *previous as i64,
+            })
+            .collect();
+    });
+    deltas
+}
+
+/// Deregisters an allocation group that was acquired with [`acquire_allocation_group_id`].
+///
+/// The group ID is reused once the bytes still attributed to the group have been deallocated.
+pub(crate) fn deregister_allocation_group(group_id: AllocationGroupId) {
+    if !group_id.is_shared() {
+        report::set_registered(group_id, false);
+        let _ = ACTIVE_GROUPS.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |active| {
+            active.checked_sub(1)
+        });
+        let mut released_group_ids = RELEASED_GROUP_IDS.lock().unwrap();
+        // A group that is deregistered twice, such as by its token and by a merge, must only be reused once.
+        if !released_group_ids
+            .iter()
+            .any(|(released_group_id, _)| *released_group_id == group_id)
+        {
+            if let Some(group_idx) = group_id.slot_index() {
+                GROUP_GENERATIONS[group_idx].fetch_add(1, Ordering::Release);
+            }
+            released_group_ids.push((group_id, 0));
+        }
+    }
+}
+
+/// Makes the deregistered allocation group IDs whose allocated bytes have stayed drained to zero available for reuse.
+///
+/// The tags and relationships of the reclaimed groups are cleared, so that the slots of groups dropped on reload cost
+/// no more than those that were never used.
+fn reclaim_released_group_ids() {
+    let mut released_group_ids = RELEASED_GROUP_IDS.lock().unwrap();
+    let mut free_group_ids = FREE_GROUP_IDS.lock().unwrap();
+    released_group_ids.retain_mut(|(group_id, drained_emissions)| {
+        // The allocations of bytes moved to another group still carry the ID of the group until they are deallocated.
+        if current_allocated_bytes(*group_id) > 0
+            || group_id.slot_index().map_or(false, |group_idx| {
+                forwarding::forwarded_bytes(group_idx) > 0
+            })
+        {
+            *drained_emissions = 0;
+            return true;
+        }
+
+        *drained_emissions += 1;
+        if *drained_emissions < RELEASED_GROUP_TOMBSTONE_EMISSIONS {
+            return true;
+        }
+        history::remove_allocation_group_history(*group_id);
+        hierarchy::clear_relationships(*group_id);
+        if let Some(group_idx) = group_id.slot_index() {
+            *GROUP_INFO[group_idx].lock().unwrap() = GroupInfo::new();
+        }
+        free_group_ids.push(*group_id);
+        false
+    });
+}
+
+/// Moves bytes that are currently allocated from one allocation group to another, such as when the ownership of a
+/// buffer is handed from a source to a sink.
+///
+/// The bytes are deallocated from `from` and allocated to `to` together, both while holding the lock that readers of
+/// the statistics hold and in the same buffer of the current thread, so that neither readers nor the processor ever
+/// observe one side of the transfer without the other. At most the bytes currently allocated by `from` are moved.
+///
+/// The moved allocations still carry the ID of `from`, so the next deallocations traced with it are debited from `to`
+/// instead, until as many bytes as were moved are settled, and the ID of `from` is not reused before then. Which
+/// allocations settle the moved bytes is not tracked, so the split between the groups is exact once every allocation
+/// made in `from` before the move is deallocated. A group only moves bytes to a single group at a time: nothing is
+/// moved while bytes moved to another group are not yet settled.
+pub fn transfer_bytes(from: AllocationGroupId, to: AllocationGroupId, bytes: u64) {
+    let (from_idx, to_idx) = match (from.slot_index(), to.slot_index()) {
+        (Some(from_idx), Some(to_idx)) if from_idx != to_idx => (from_idx, to_idx),
+        _ => return,
+    };
+    without_allocation_tracing(|| {
+        // The statistics of the current thread are registered while holding the lock, so they must be looked up first.
+        let thread_stats = match GROUP_MEM_STATS.try_with(|t| t.stats) {
+            Ok(thread_stats) => thread_stats,
+            Err(_) => return,
+        };
+        let thread_local_refs = THREAD_LOCAL_REFS.lock().unwrap();
+        let from_totals = group_mem_totals(&thread_local_refs, from_idx);
+        let bytes = bytes.min(
+            from_totals
+                .allocations
+                .saturating_sub(from_totals.deallocations),
+        );
+        if bytes == 0 || !forwarding::forward(from_idx, to_idx, bytes) {
+            return;
+        }
+        thread_stats.write(|stats| {
+            stats.deallocations[from_idx].fetch_add(bytes, Ordering::Relaxed);
+            stats.allocations[to_idx].fetch_add(bytes, Ordering::Relaxed);
+        });
+    });
+}
+
+/// Adds bytes allocated, or deallocated if negative, outside of Vector's allocator to an allocation group.
+fn apply_allocation_delta(group_id: AllocationGroupId, delta_bytes: i64) {
+    let group_idx = match group_id.slot_index() {
+        Some(group_idx) => group_idx,
+        None => return,
+    };
+    without_allocation_tracing(|| {
+        let _ = GROUP_MEM_STATS.try_with(|t| {
+            t.stats.write(|stats| {
+                if delta_bytes >= 0 {
+                    stats.allocations[group_idx].fetch_add(delta_bytes as u64, Ordering::Relaxed);
+                } else {
+                    stats.deallocations[group_idx]
+                        .fetch_add(delta_bytes.unsigned_abs(), Ordering::Relaxed);
+                }
+            });
+        });
+    });
+}
+
+/// Merges the `source` allocation group into the `target` allocation group.
+///
+/// The bytes currently allocated by `source` are moved to `target` with [`transfer_bytes`], the tags of both groups are
+/// combined, and `source` is deregistered. The allocations of the moved bytes still carry the ID of `source`, so their
+/// deallocations are debited from `target`, and the ID of `source` is only reused once they are all deallocated. If
+/// `source` still has bytes moved to another group, its bytes stay attributed to it until they are deallocated.
+/// Allocations made in `source` after its bytes have been moved are not carried over, so `source` should no longer be
+/// active when it is merged.
+pub(crate) fn merge_allocation_groups(target: AllocationGroupId, source: AllocationGroupId) {
+    if target == source || source.is_shared() {
+        return;
+    }
+    let (source_idx, target_idx) = match (source.slot_index(), target.slot_index()) {
+        (Some(source_idx), Some(target_idx)) => (source_idx, target_idx),
+        _ => return,
+    };
+
+    transfer_bytes(source, target, current_allocated_bytes(source));
+    without_allocation_tracing(|| {
+        let source_tags = GROUP_INFO[source_idx].lock().unwrap().tags.clone();
+        GROUP_INFO[target_idx]
+            .lock()
+            .unwrap()
+            .merge_tags(&source_tags);
+    });
+    deregister_allocation_group(source);
+}
+
+#[cfg(test)]
+mod tests {
+    use vector_core::event::MetricValue;
+
+    use super::*;
+
+    // Allocations are not tracked in tests, so every test drives `MainTracer` directly and uses an allocation group
+    // ID of its own, to avoid tests observing each other's (de)allocations. The processor is never started in this test
+    // binary, so the statistics a group keeps between emissions are only ever reset by its own test. Tests of the
+    // processor itself are run by the `allocation-tracing` test binary instead. Tests that register, tag or deregister
+    // their group do so through a `TestGroup`, which leaves the slot as it found it.
+    pub(super) fn test_group_id(raw_group_id: u8) -> AllocationGroupId {
+        let group_id = AllocationGroupId::from_raw(raw_group_id);
+        assert!(group_id.slot_index().is_some());
+        group_id
+    }
+
+    pub(super) fn live_allocations(group_idx: usize) -> u64 {
+        group_mem_totals(&THREAD_LOCAL_REFS.lock().unwrap(), group_idx).live_allocations()
+    }
+
+    /// Sets a flag for the duration of a test, restoring its previous value when dropped, even if the test fails.
+    pub(super) struct ScopedFlag {
+        flag: &'static AtomicBool,
+        previous: bool,
+    }
+
+    impl ScopedFlag {
+        pub(super) fn set(flag: &'static AtomicBool, value: bool) -> Self {
+            let previous = flag.swap(value, Ordering::Relaxed);
+            Self { flag, previous }
+        }
+    }
+
+    impl Drop for ScopedFlag {
+        fn drop(&mut self) {
+            self.flag.store(self.previous, Ordering::Relaxed);
+        }
+    }
+
+    /// Registers the allocation group of a test slot for the duration of a test, restoring the statics of the slot
+    /// when dropped, even if the test fails.
+    ///
+    /// Deregistering the group, as its token does, releases its ID for reuse: dropping the fixture takes it back, so that
+    /// the slot of one test is never handed out to another. The fixture must be dropped after the tokens of its group.
+    pub(super) struct TestGroup {
+        group_id: AllocationGroupId,
+    }
+
+    impl TestGroup {
+        pub(super) fn register(raw_group_id: u8) -> Self {
+            let group_id = test_group_id(raw_group_id);
+            set_group_tags(group_id, Vec::new());
+            report::set_registered(group_id, true);
+            Self { group_id }
+        }
+
+        pub(super) const fn id(&self) -> AllocationGroupId {
+            self.group_id
+        }
+
+        /// Creates a token owning the registration of the group.
+        pub(super) fn token(&self) -> AllocationGroupToken {
+            AllocationGroupToken::from(self.group_id)
+        }
+    }
+
+    impl Drop for TestGroup {
+        fn drop(&mut self) {
+            let group_id = self.group_id;
+            let group_idx = group_id.traced_slot_index();
+            RELEASED_GROUP_IDS
+                .lock()
+                .unwrap()
+                .retain(|(released_group_id, _)| *released_group_id != group_id);
+            FREE_GROUP_IDS
+                .lock()
+                .unwrap()
+                .retain(|free_group_id| *free_group_id != group_id);
+
+            reset_group(group_id);
+            GROUP_RESET[group_idx].store(false, Ordering::Relaxed);
+            *GROUP_INFO[group_idx].lock().unwrap() = GroupInfo::new();
+            hierarchy::clear_relationships(group_id);
+            history::remove_allocation_group_history(group_id);
+            report::set_registered(group_id, false);
+            set_group_fidelity(group_id, AllocationFidelity::Default);
+            budget::set_budget_enforcement(group_idx, None);
+            group_filter::match_group(group_idx, &[]);
+            for counter in [
+                &GROUP_ENTERED[group_idx],
+                &GROUP_SMALL_ALLOC_BYTES[group_idx],
+                &GROUP_ALLOCATION_FAILURES[group_idx],
+                &GROUP_RECLAIMABLE[group_idx],
+            ] {
+                counter.store(0, Ordering::Relaxed);
+            }
+        }
+    }
+
+    #[test]
+    fn slot_indexes_are_checked() {
+        assert_eq!(AllocationGroupId::ROOT.slot_index(), Some(1));
+        assert_eq!(
+            AllocationGroupId::from_raw(NUM_GROUPS as u8 - 1).slot_index(),
+            Some(NUM_GROUPS - 1)
+        );
+        assert_eq!(
+            AllocationGroupId::from_raw(NUM_GROUPS as u8).slot_index(),
+            None
+        );
+        assert_eq!(AllocationGroupId::from_raw(u8::MAX).slot_index(), None);
+    }
+
+    #[test]
+    fn ignores_group_ids_without_a_slot() {
+        let group_id = AllocationGroupId::from_raw(NUM_GROUPS as u8);
+        MainTracer.trace_allocation(64, group_id);
+        MainTracer.trace_reallocation(64, 128, group_id);
+        MainTracer.trace_deallocation(128, group_id);
+        MainTracer.trace_allocation_failure(64, group_id);
+        record_small_allocation(8, group_id);
+        assert!(!is_traced_allocation(8, 16, group_id));
+    }
+
+    #[test]
+    fn groups_past_capacity_share_the_overflow_group() {
+        let overflowed = GROUPS_OVERFLOWED.load(Ordering::Relaxed);
+        let registration = overflow_registration();
+        assert_eq!(registration.id, AllocationGroupId::OVERFLOW);
+        assert!(registration.id.is_shared());
+        assert!(GROUPS_OVERFLOWED.load(Ordering::Relaxed) > overflowed);
+
+        // The allocated bytes of the overflow group only ever grow, as it is never reset.
+        let overflow_allocations = || {
+            group_mem_totals(
+                &THREAD_LOCAL_REFS.lock().unwrap(),
+                AllocationGroupId::OVERFLOW.as_raw() as usize,
+            )
+            .allocations
+        };
+        let allocations = overflow_allocations();
+        MainTracer.trace_allocation(4096, AllocationGroupId::from_raw(u8::MAX));
+        assert!(overflow_allocations() >= allocations + 4096);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "dropped without being deregistered")]
+    fn dropping_token_with_allocated_bytes_panics() {
+        // The fixture clears the leaked bytes as the panic unwinds.
+        let group = TestGroup::register(74);
+        let token = group.token();
+        MainTracer.trace_allocation(64, token.id());
+        drop(token);
+    }
+
+    #[test]
+    fn dropping_token_without_allocated_bytes_is_fine() {
+        let group = TestGroup::register(126);
+        let token = group.token();
+        MainTracer.trace_allocation(64, token.id());
+        MainTracer.trace_deallocation(64, token.id());
+        assert_eq!(token.current_allocated_bytes(), 0);
+        drop(token);
+    }
+
+    #[test]
+    fn child_tokens_keep_the_group_registered() {
+        let group = TestGroup::register(70);
+        let token = group.token();
+        let child = token.clone_for_child_task();
+        assert_eq!(child.id(), token.id());
+
+        MainTracer.trace_allocation(64, child.id());
+        // The child still owns the registration, so the bytes it allocated are not leaked by dropping the parent.
+        drop(token);
+        MainTracer.trace_deallocation(64, child.id());
+        drop(child);
+    }
+
+    #[test]
+    fn records_the_parent_of_child_groups() {
+        let parent = test_group_id(69);
+        let group = TestGroup::register(68);
+        let child = AllocationGroupToken::with_parent(group.id(), Some(parent));
+        assert_eq!(child.parent(), Some(parent));
+
+        MainTracer.trace_allocation(64, child.id());
+        let stats = allocation_snapshot()
+            .into_iter()
+            .find(|stats| stats.group_id == child.id())
+            .unwrap();
+        assert_eq!(stats.parent, Some(parent));
+        assert!(group_labels(child.id().as_raw() as usize, "test")
+            .contains(&("parent".to_string(), parent.as_raw().to_string())));
+
+        MainTracer.trace_deallocation(64, child.id());
+        drop(child);
+    }
+
+    #[tokio::test]
+    async fn tracked_futures_enter_their_group_on_every_poll() {
+        let group = TestGroup::register(66);
+        let token = group.token();
+        let task_group_id = test_group_id(57);
+        // Components run as tasks with allocation groups of their own, which a tracked future takes precedence over.
+        let (polled_in, after_polls) = in_task_allocation_group(task_group_id, async {
+            let polled_in = async {
+                let first_poll = current_allocation_group();
+                tokio::task::yield_now().await;
+                (first_poll, current_allocation_group())
+            }
+            .track_allocations(&token)
+            .await;
+            (polled_in, current_allocation_group())
+        })
+        .await;
+
+        assert_eq!(polled_in, (token.id(), token.id()));
+        assert_eq!(after_polls, task_group_id);
+        assert_eq!(current_allocation_group(), AllocationGroupId::ROOT);
+    }
+
+    #[test]
+    fn allocation_overhead_includes_alignment_padding() {
+        use std::alloc::Layout;
+
+        assert_eq!(header_overhead(), 1);
+        let overhead =
+            |size, align| allocation_overhead(Layout::from_size_align(size, align).unwrap());
+        assert_eq!(overhead(7, 1), 1);
+        assert_eq!(overhead(8, 8), 8);
+        assert_eq!(overhead(15, 8), 1);
+    }
+
+    #[test]
+    fn log_fields_fall_back_to_unknown() {
+        let tags = component_tags(
+            "in".to_string(),
+            "demo_logs".to_string(),
+            "source".to_string(),
+        );
+        assert_eq!(component_log_fields(&tags), ["in", "demo_logs", "source"]);
+
+        let tags = vec![("component_id".to_string(), "sidecar".to_string())];
+        assert_eq!(
+            component_log_fields(&tags),
+            ["sidecar", "unknown", "unknown"]
+        );
+    }
+
+    #[test]
+    fn log_messages_prefer_group_names() {
+        let group = TestGroup::register(87);
+        let group_id = group.id();
+        let token = group.token();
+        assert_eq!(token.name(), None);
+        assert_eq!(group_log_name(group_id), "87");
+
+        token.set_name("http_source_1");
+        assert_eq!(token.name().as_deref(), Some("http_source_1"));
+        assert_eq!(group_log_name(group_id), "http_source_1");
+        token.drain_and_deregister();
+    }
+
+    #[test]
+    fn formats_tokens() {
+        let group = TestGroup::register(78);
+        let token = group.token();
+        let id = token.id().as_raw();
+        assert_eq!(
+            format!("{:?}", token),
+            format!(
+                "AllocationGroupToken {{ id: {}, name: None, current_bytes: 0 }}",
+                id
+            )
+        );
+        assert_eq!(token.to_string(), format!("[alloc_group:{} 0 B]", id));
+
+        token.set_name("http_source_1");
+        MainTracer.trace_allocation(1024 * 1024, token.id());
+        assert_eq!(
+            format!("{:?}", token),
+            format!(
+                "AllocationGroupToken {{ id: {}, name: \"http_source_1\", current_bytes: 1048576 }}",
+                id
+            )
+        );
+        assert_eq!(
+            token.to_string(),
+            format!("[alloc_group:{} \"http_source_1\" 1.0 MiB]", id)
+        );
+        MainTracer.trace_deallocation(1024 * 1024, token.id());
+        token.drain_and_deregister();
+    }
+
+    #[test]
+    fn merge_tags_removes_duplicates() {
+        let mut group_info = GroupInfo::with_tags(component_tags(
+            "in".to_string(),
+            "remap".to_string(),
+            "transform".to_string(),
+        ));
+        group_info.merge_tags(&component_tags(
+            "in_copy".to_string(),
+            "remap".to_string(),
+            "transform".to_string(),
+        ));
+
+        assert_eq!(
+            group_info.tags,
+            vec![
+                ("component_kind".to_string(), "transform".to_string()),
+                ("component_type".to_string(), "remap".to_string()),
+                ("component_id".to_string(), "in,in_copy".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn task_allocation_group_takes_precedence() {
+        let group_id = test_group_id(117);
+        let current =
+            in_task_allocation_group(group_id, async { current_allocation_group() }).await;
+        assert_eq!(current, group_id);
+
+        in_task_allocation_group(group_id, async {
+            exit_task_allocation_group().await;
+            assert_eq!(current_allocation_group(), AllocationGroupId::ROOT);
+            enter_task_allocation_group(group_id).await;
+            assert_eq!(current_allocation_group(), group_id);
+        })
+        .await;
+        assert_eq!(current_allocation_group(), AllocationGroupId::ROOT);
+    }
+
+    #[tokio::test]
+    async fn scoped_allocation_groups_take_precedence_over_the_task_group() {
+        use std::alloc::{Layout, System};
+
+        let task_group_id = test_group_id(59);
+        let scoped_group_id = test_group_id(58);
+        let allocator = GroupedTraceableAllocator::new(System, MainTracer);
+        let layout = Layout::from_size_align(4096, 8).unwrap();
+        in_task_allocation_group(task_group_id, async {
+            let _scope = ScopedAllocationGroup::enter(scoped_group_id);
+            assert_eq!(current_allocation_group(), scoped_group_id);
+            unsafe {
+                let ptr = allocator.alloc_traced(layout, 0);
+                assert_eq!(current_allocated_bytes(scoped_group_id), 4096);
+                allocator.dealloc_traced(ptr, layout);
+            }
+        })
+        .await;
+        assert_eq!(current_allocated_bytes(task_group_id), 0);
+    }
+
+    #[test]
+    fn scoped_allocation_groups_can_be_entered_while_suspended() {
+        let group_id = test_group_id(60);
+        without_allocation_tracing(|| {
+            let _scope = ScopedAllocationGroup::enter(group_id);
+        });
+        assert_eq!(current_allocation_group(), AllocationGroupId::ROOT);
+    }
+
+    #[test]
+    fn scoped_allocation_groups_restore_previous_group() {
+        let outer = test_group_id(103);
+        let inner = test_group_id(102);
+        {
+            let _outer = ScopedAllocationGroup::enter(outer);
+            assert_eq!(current_allocation_group(), outer);
+            {
+                let _inner = ScopedAllocationGroup::enter(inner);
+                assert_eq!(current_allocation_group(), inner);
+            }
+            assert_eq!(current_allocation_group(), outer);
+        }
+        assert_eq!(current_allocation_group(), AllocationGroupId::ROOT);
+    }
+
+    #[test]
+    fn with_allocation_group_exits_on_error() {
+        let group_id = test_group_id(91);
+        let parse = |input: &str| -> Result<u32, std::num::ParseIntError> {
+            with_allocation_group(group_id, || {
+                assert_eq!(current_allocation_group(), group_id);
+                let value = input.parse::<u32>()?;
+                Ok(value * 2)
+            })
+        };
+
+        assert_eq!(parse("21"), Ok(42));
+        assert_eq!(current_allocation_group(), AllocationGroupId::ROOT);
+        assert!(parse("not a number").is_err());
+        assert_eq!(current_allocation_group(), AllocationGroupId::ROOT);
+    }
+
+    #[test]
+    fn gets_tags_of_current_group() {
+        let group = TestGroup::register(98);
+        let group_id = group.id();
+        let tags = component_tags("in".to_string(), "file".to_string(), "source".to_string());
+        set_group_tags(group_id, tags.clone());
+
+        assert_eq!(current_allocation_group_tags(), None);
+        let _scope = ScopedAllocationGroup::enter(group_id);
+        assert_eq!(current_allocation_group_tags(), Some(tags));
+    }
+
+    #[test]
+    fn dry_run_counts_entries_without_tracking_bytes() {
+        let group_id = test_group_id(97);
+        let _dry_run = ScopedFlag::set(&DRY_RUN, true);
+        drop(ScopedAllocationGroup::enter(group_id));
+        let _scope = ScopedAllocationGroup::enter(group_id);
+
+        assert_eq!(GROUP_ENTERED[97].swap(0, Ordering::Relaxed), 2);
+        assert_eq!(current_allocated_bytes(group_id), 0);
+    }
+
+    /// A sink that records the metrics emitted to it.
+    #[derive(Default)]
+    struct RecordingSink {
+        metrics: Mutex<Vec<(&'static str, &'static str, f64, Vec<(String, String)>)>>,
+    }
+
+    impl RecordingSink {
+        fn record(
+            &self,
+            kind: &'static str,
+            name: &'static str,
+            value: f64,
+            labels: &[(String, String)],
+        ) {
+            self.metrics
+                .lock()
+                .unwrap()
+                .push((kind, name, value, labels.to_vec()));
+        }
+    }
+
+    impl AllocationMetricSink for RecordingSink {
+        fn record_counter(&self, name: &'static str, value: u64, labels: &[(String, String)]) {
+            self.record("counter", name, value as f64, labels);
+        }
+
+        fn record_gauge(&self, name: &'static str, value: f64, labels: &[(String, String)]) {
+            self.record("gauge", name, value, labels);
+        }
+
+        fn increment_gauge(&self, name: &'static str, value: f64, labels: &[(String, String)]) {
+            self.record("increment_gauge", name, value, labels);
+        }
+
+        fn decrement_gauge(&self, name: &'static str, value: f64, labels: &[(String, String)]) {
+            self.record("decrement_gauge", name, value, labels);
+        }
+    }
+
+    #[test]
+    fn emits_group_diff_to_sink() {
+        let sink = RecordingSink::default();
+        let labels = vec![("component_id".to_string(), "in".to_string())];
+        let with_op = |op: &str| {
+            let mut labels = labels.clone();
+            labels.push(("op".to_string(), op.to_string()));
+            labels
+        };
+        let diff = GroupMemCounts {
+            allocations: 300,
+            deallocations: 100,
+            allocation_events: 3,
+            deallocation_events: 0,
+            object_allocations: 3,
+        };
+        emit_group_diff(&sink, &labels, &diff, 200);
+        emit_group_diff(&sink, &labels, &GroupMemCounts::default(), -50);
+
+        assert_eq!(
+            sink.metrics.into_inner().unwrap(),
+            vec![
+                (
+                    "counter",
+                    "component_allocated_bytes_total",
+                    300.0,
+                    labels.clone()
+                ),
+                (
+                    "counter",
+                    "component_deallocated_bytes_total",
+                    100.0,
+                    labels.clone()
+                ),
+                (
+                    "counter",
+                    "component_allocation_events_total",
+                    3.0,
+                    with_op("alloc")
+                ),
+                (
+                    "increment_gauge",
+                    "component_allocated_bytes",
+                    200.0,
+                    labels.clone()
+                ),
+                (
+                    "decrement_gauge",
+                    "component_allocated_bytes",
+                    50.0,
+                    labels.clone()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn emits_group_diff_through_the_metrics_recorder() {
+        crate::metrics::init_test();
+        let labels = vec![("component_id".to_string(), "recorded".to_string())];
+        let diff = GroupMemCounts {
+            allocations: 300,
+            deallocations: 100,
+            allocation_events: 3,
+            deallocation_events: 1,
+            object_allocations: 3,
+        };
+        emit_group_diff(&MetricsCrateSink, &labels, &diff, 200);
+
+        let mut metrics = crate::metrics::Controller::get()
+            .expect("There must be a controller")
+            .capture_metrics()
+            .into_iter()
+            .filter(|metric| metric.tag_value("component_id").as_deref() == Some("recorded"))
+            .map(|metric| {
+                (
+                    metric.name().to_string(),
+                    metric.tag_value("op"),
+                    metric.value().clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        metrics.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+
+        assert_eq!(
+            metrics,
+            vec![
+                (
+                    "component_allocated_bytes".to_string(),
+                    None,
+                    MetricValue::Gauge { value: 200.0 }
+                ),
+                (
+                    "component_allocated_bytes_total".to_string(),
+                    None,
+                    MetricValue::Counter { value: 300.0 }
+                ),
+                (
+                    "component_allocation_events_total".to_string(),
+                    Some("alloc".to_string()),
+                    MetricValue::Counter { value: 3.0 }
+                ),
+                (
+                    "component_allocation_events_total".to_string(),
+                    Some("dealloc".to_string()),
+                    MetricValue::Counter { value: 1.0 }
+                ),
+                (
+                    "component_deallocated_bytes_total".to_string(),
+                    None,
+                    MetricValue::Counter { value: 100.0 }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn emissions_continue_after_a_panic() {
+        let mut emissions = 0;
+        let mut waits = 0;
+        run_emissions(
+            || {
+                emissions += 1;
+                if emissions == 2 {
+                    panic!("injected emission panic");
+                }
+            },
+            || {
+                waits += 1;
+                waits < 3
+            },
+        );
+
+        // The final emission is made once `wait` returns false.
+        assert_eq!(emissions, 4);
+    }
+
+    #[test]
+    fn full_fidelity_traces_small_allocations_of_its_group_only() {
+        let full_group = TestGroup::register(89);
+        let full_group_id = full_group.id();
+        let default_group_id = test_group_id(88);
+        set_group_fidelity(full_group_id, AllocationFidelity::Full);
+
+        assert!(is_traced_allocation(8, 64, full_group_id));
+        assert!(!is_traced_allocation(8, 64, default_group_id));
+        assert!(is_traced_allocation(64, 64, default_group_id));
+
+        set_group_fidelity(full_group_id, AllocationFidelity::Default);
+        assert!(!is_traced_allocation(8, 64, full_group_id));
+    }
+
+    #[test]
+    fn traces_small_allocations_once_they_grow_past_the_minimum() {
+        use std::alloc::{Layout, System};
+
+        let group_id = test_group_id(62);
+        let allocator = GroupedTraceableAllocator::new(System, MainTracer);
+        let small_layout = Layout::from_size_align(16, 8).unwrap();
+        let _scope = ScopedAllocationGroup::enter(group_id);
+        unsafe {
+            let ptr = allocator.alloc_traced(small_layout, 1024);
+            assert!(!ptr.is_null());
+            assert_eq!(current_allocated_bytes(group_id), 0);
+
+            let ptr = allocator.realloc_traced(ptr, small_layout, 4096, 1024);
+            assert!(!ptr.is_null());
+            assert_eq!(current_allocated_bytes(group_id), 4096);
+
+            allocator.dealloc_traced(ptr, Layout::from_size_align(4096, 8).unwrap());
+        }
+        assert_eq!(current_allocated_bytes(group_id), 0);
+    }
+
+    #[test]
+    fn never_traces_allocations_made_while_suspended() {
+        use std::alloc::{Layout, System};
+
+        let group_id = test_group_id(54);
+        let allocator = GroupedTraceableAllocator::new(System, MainTracer);
+        let small_layout = Layout::from_size_align(16, 8).unwrap();
+        let _scope = ScopedAllocationGroup::enter(group_id);
+        unsafe {
+            let mut ptr = std::ptr::null_mut();
+            without_allocation_tracing(|| ptr = allocator.alloc_traced(small_layout, 0));
+            assert!(!ptr.is_null());
+
+            let ptr = allocator.realloc_traced(ptr, small_layout, 4096, 0);
+            assert!(!ptr.is_null());
+            assert_eq!(current_allocated_bytes(group_id), 0);
+
+            allocator.dealloc_traced(ptr, Layout::from_size_align(4096, 8).unwrap());
+        }
+        assert_eq!(current_allocated_bytes(group_id), 0);
+    }
+
+    #[test]
+    fn counts_allocation_failures_without_allocated_bytes() {
+        use std::alloc::{GlobalAlloc, Layout};
+
+        /// An allocator that fails every allocation.
+        struct FailingAllocator;
+
+        unsafe impl GlobalAlloc for FailingAllocator {
+            unsafe fn alloc(&self, _layout: Layout) -> *mut u8 {
+                std::ptr::null_mut()
+            }
+
+            unsafe fn dealloc(&self, _ptr: *mut u8, _layout: Layout) {
+                unreachable!("no allocation is ever made");
+            }
+        }
+
+        let group_id = test_group_id(86);
+        let allocator = GroupedTraceableAllocator::new(FailingAllocator, MainTracer);
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        // Growing an object fails too, and leaves it in place. Its group ID is read right after it, in the traced path.
+        let mut object = [0u8; 72];
+        {
+            let _scope = ScopedAllocationGroup::enter(group_id);
+            let ptr = unsafe { allocator.alloc(layout) };
+            assert!(ptr.is_null());
+            let ptr = unsafe { allocator.realloc(object.as_mut_ptr(), layout, 128) };
+            assert!(ptr.is_null());
+            let ptr = unsafe { allocator.realloc_traced(object.as_mut_ptr(), layout, 128, 0) };
+            assert!(ptr.is_null());
+        }
+
+        assert_eq!(GROUP_ALLOCATION_FAILURES[86].swap(0, Ordering::Relaxed), 3);
+        assert_eq!(current_allocated_bytes(group_id), 0);
+    }
+
+    #[test]
+    fn counts_untracked_bytes() {
+        use std::alloc::{GlobalAlloc, Layout, System};
+
+        let _count_untracked_bytes = ScopedFlag::set(&COUNT_UNTRACKED_BYTES, true);
+        let allocator = GroupedTraceableAllocator::new(System, MainTracer);
+        let layout = Layout::from_size_align(4096, 8).unwrap();
+        let untracked_bytes_before = untracked_bytes();
+        without_allocation_tracing(|| unsafe {
+            let ptr = allocator.alloc(layout);
+            assert!(!ptr.is_null());
+            allocator.dealloc(ptr, layout);
+        });
+
+        // Other tests may allocate untracked bytes concurrently.
+        assert!(untracked_bytes() >= untracked_bytes_before + 4096);
+    }
+
+    #[test]
+    fn counts_small_allocations_separately() {
+        let group_id = test_group_id(92);
+        record_small_allocation(8, group_id);
+        record_small_allocation(16, group_id);
+
+        assert_eq!(GROUP_SMALL_ALLOC_BYTES[92].swap(0, Ordering::Relaxed), 24);
+        assert_eq!(current_allocated_bytes(group_id), 0);
+    }
+
+    #[test]
+    fn iterates_over_live_groups() {
+        let group_id = test_group_id(118);
+        let is_live = || iter_live_groups().any(|stats| stats.group_id == group_id);
+
+        MainTracer.trace_allocation(100, group_id);
+        assert!(is_live());
+
+        MainTracer.trace_deallocation(100, group_id);
+        assert!(!is_live());
+    }
+
+    #[test]
+    fn diffs_against_baseline() {
+        let group_id = test_group_id(120);
+        MainTracer.trace_allocation(100, group_id);
+        let baseline = baseline();
+
+        MainTracer.trace_allocation(50, group_id);
+        MainTracer.trace_deallocation(80, group_id);
+
+        let delta = diff_since(&baseline)
+            .into_iter()
+            .find(|delta| delta.group_id == group_id);
+        assert_eq!(
+            delta,
+            Some(GroupDelta {
+                group_id,
+                delta_bytes: -30
+            })
+        );
+    }
+
+    #[test]
+    fn sanitizes_metric_prefix() {
+        let mut prefix = DEFAULT_METRIC_PREFIX.to_string();
+        assert!(!sanitize_metric_prefix(&mut prefix));
+        assert_eq!(prefix, DEFAULT_METRIC_PREFIX);
+
+        let mut prefix = "42my-app.vector:".to_string();
+        assert!(sanitize_metric_prefix(&mut prefix));
+        assert_eq!(prefix, "myappvector:");
+    }
+
+    #[test]
+    fn merge_moves_allocated_bytes() {
+        let target_group = TestGroup::register(125);
+        let source_group = TestGroup::register(124);
+        let target = target_group.token();
+        let source = source_group.token();
+        MainTracer.trace_allocation(100, target.id());
+        MainTracer.trace_allocation(50, source.id());
+        let source_id = source.id();
+
+        target.merge(source);
+
+        assert_eq!(target.current_allocated_bytes(), 150);
+        assert_eq!(current_allocated_bytes(source_id), 0);
+        // The allocation made in the source group still carries its ID, which isn't reused until it is freed.
+        assert_eq!(forwarding::forwarded_bytes(source_id.as_raw() as usize), 50);
+        MainTracer.trace_deallocation(50, source_id);
+        assert_eq!(target.current_allocated_bytes(), 100);
+        assert_eq!(current_allocated_bytes(source_id), 0);
+        assert_eq!(forwarding::forwarded_bytes(source_id.as_raw() as usize), 0);
+        MainTracer.trace_deallocation(100, target.id());
+        drop(target);
+    }
+
+    #[test]
+    fn transfers_bytes_between_groups() {
+        let from = test_group_id(81);
+        let to = test_group_id(80);
+        MainTracer.trace_allocation(100, from);
+        MainTracer.trace_allocation(10, to);
+
+        transfer_bytes(from, to, 60);
+        assert_eq!(current_allocated_bytes(from), 40);
+        assert_eq!(current_allocated_bytes(to), 70);
+
+        // Only the bytes the group currently has allocated can be moved out of it.
+        transfer_bytes(from, to, 1000);
+        assert_eq!(current_allocated_bytes(from), 0);
+        assert_eq!(current_allocated_bytes(to), 110);
+
+        // The transferred allocation still carries the ID of the group it was made in, and freeing it debits the group
+        // its bytes were moved to.
+        MainTracer.trace_deallocation(100, from);
+        assert_eq!(current_allocated_bytes(from), 0);
+        assert_eq!(current_allocated_bytes(to), 10);
+        assert_eq!(forwarding::forwarded_bytes(from.as_raw() as usize), 0);
+
+        MainTracer.trace_deallocation(10, to);
+        assert_eq!(current_allocated_bytes(to), 0);
+    }
+
+    #[test]
+    fn reclaims_drained_groups_after_tombstone_period() {
+        let group = TestGroup::register(100);
+        let group_id = group.id();
+        set_group_tags(
+            group_id,
+            vec![("component_id".to_string(), "old".to_string())],
+        );
+        MainTracer.trace_allocation(64, group_id);
+        group.token().drain_and_deregister();
+
+        let is_free = || FREE_GROUP_IDS.lock().unwrap().contains(&group_id);
+        reclaim_released_group_ids();
+        assert!(!is_free());
+
+        MainTracer.trace_deallocation(64, group_id);
+        for _ in 0..RELEASED_GROUP_TOMBSTONE_EMISSIONS {
+            reclaim_released_group_ids();
+        }
+        assert!(is_free());
+        assert_eq!(
+            allocation_group_registration(group_id).map(|registration| registration.tags),
+            Some(Vec::new())
+        );
+    }
+
+    #[test]
+    fn reset_groups_saturate_on_earlier_deallocations() {
+        let group = TestGroup::register(82);
+        let group_id = group.id();
+        MainTracer.trace_allocation(100, group_id);
+        MainTracer.trace_allocation(100, group_id);
+        assert_eq!(current_allocated_bytes(group_id), 200);
+
+        reset_group(group_id);
+        assert_eq!(current_allocated_bytes(group_id), 0);
+        assert_eq!(live_allocations(82), 0);
+        assert!(GROUP_RESET[82].load(Ordering::Relaxed));
+
+        // An allocation made before the reset is deallocated after it.
+        MainTracer.trace_deallocation(100, group_id);
+        assert_eq!(current_allocated_bytes(group_id), 0);
+        assert_eq!(live_allocations(82), 0);
+
+        MainTracer.trace_allocation(300, group_id);
+        assert_eq!(current_allocated_bytes(group_id), 200);
+        MainTracer.trace_deallocation(300, group_id);
+        MainTracer.trace_deallocation(100, group_id);
+    }
+
+    #[test]
+    fn weak_tokens_only_upgrade_while_registered() {
+        let group = TestGroup::register(84);
+        let group_id = group.id();
+        let token = group.token();
+        let weak = token.downgrade();
+
+        let upgraded = weak.upgrade().unwrap();
+        assert_eq!(upgraded.id(), group_id);
+        // The upgraded token doesn't own the registration of the group, so dropping it leaves the group registered.
+        drop(upgraded);
+        assert!(weak.upgrade().is_some());
+
+        token.drain_and_deregister();
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn skips_unchanged_gauges_when_enabled() {
+        let values = GroupGaugeValues {
+            reclaimable_bytes: 10,
+            live_allocations_peak: 2,
+        };
+        let mut emitted = None;
+        assert!(should_emit_gauges(&mut emitted, values, true));
+        assert!(!should_emit_gauges(&mut emitted, values, true));
+        assert!(should_emit_gauges(&mut emitted, values, false));
+
+        let changed = GroupGaugeValues {
+            live_allocations_peak: 3,
+            ..values
+        };
+        assert!(should_emit_gauges(&mut emitted, changed, true));
+        assert_eq!(emitted, Some(changed));
+    }
+
+    #[test]
+    fn registered_tags_are_visible_on_next_emission() {
+        let group = TestGroup::register(123);
+        let group_id = group.id();
+        let tags = component_tags(
+            "in".to_string(),
+            "demo_logs".to_string(),
+            "source".to_string(),
+        );
+        set_group_tags(group_id, tags.clone());
+
+        assert_eq!(
+            allocation_group_registration(group_id),
+            Some(AllocationGroupRegistration {
+                id: group_id,
+                tags: tags.clone(),
+            })
+        );
+        let labels = group_labels(group_id.as_raw() as usize, "test");
+        assert!(tags.iter().all(|tag| labels.contains(tag)));
+    }
+
+    #[test]
+    fn arbitrary_tags_are_preserved_through_emission() {
+        let group = TestGroup::register(101);
+        let group_id = group.id();
+        let mut tags = component_tags(
+            "in".to_string(),
+            "demo_logs".to_string(),
+            "source".to_string(),
+        );
+        tags.push(("pipeline".to_string(), "tenant_a".to_string()));
+        tags.push(("team".to_string(), "observability".to_string()));
+        set_group_tags(group_id, tags.clone());
+        MainTracer.trace_allocation(100, group_id);
+
+        let mut expected_labels = tags;
+        expected_labels.push(("instance".to_string(), "test".to_string()));
+        assert_eq!(
+            group_labels(group_id.as_raw() as usize, "test"),
+            expected_labels
+        );
+        assert!(encode_allocation_metrics_prometheus().contains(
+            r#"component_allocated_bytes{component_kind="source",component_type="demo_logs",component_id="in",pipeline="tenant_a",team="observability"} 100"#
+        ));
+
+        MainTracer.trace_deallocation(100, group_id);
+    }
+
+    #[test]
+    fn reallocation_only_counts_size_difference() {
+        let group_id = test_group_id(112);
+        MainTracer.trace_allocation(100, group_id);
+
+        MainTracer.trace_reallocation(100, 250, group_id);
+        assert_eq!(current_allocated_bytes(group_id), 250);
+
+        MainTracer.trace_reallocation(250, 10, group_id);
+        assert_eq!(current_allocated_bytes(group_id), 10);
+
+        MainTracer.trace_deallocation(10, group_id);
+        assert_eq!(current_allocated_bytes(group_id), 0);
+    }
+
+    #[test]
+    fn reclaimable_bytes_are_not_a_hard_requirement() {
+        let group = TestGroup::register(113);
+        let token = group.token();
+        MainTracer.trace_allocation(100, token.id());
+        set_reclaimable_hint(&token, 40);
+        assert_eq!(hard_allocated_bytes(token.id()), 60);
+
+        // A hint above the allocated bytes doesn't make the requirement negative.
+        set_reclaimable_hint(&token, 200);
+        assert_eq!(hard_allocated_bytes(token.id()), 0);
+
+        MainTracer.trace_deallocation(100, token.id());
+        drop(token);
+    }
+
+    #[test]
+    fn tracks_peak_live_allocations() {
+        let group_id = test_group_id(114);
+        for _ in 0..3 {
+            MainTracer.trace_allocation(8, group_id);
+        }
+        for _ in 0..2 {
+            MainTracer.trace_deallocation(8, group_id);
+        }
+        MainTracer.trace_allocation(8, group_id);
+        assert_eq!(live_allocations(114), 2);
+
+        assert_eq!(record_live_count_peak(114, 3), 3);
+        // The peak is kept until the group is reset.
+        assert_eq!(record_live_count_peak(114, live_allocations(114)), 3);
+        GROUP_LIVE_COUNT_PEAK[114].store(0, Ordering::Relaxed);
+        for _ in 0..2 {
+            MainTracer.trace_deallocation(8, group_id);
+        }
+    }
+
+    #[test]
+    fn records_span_fields_as_tags() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let group = TestGroup::register(106);
+        let group_id = group.id();
+        set_group_tags(
+            group_id,
+            component_tags(
+                "unknown".to_string(),
+                "kafka".to_string(),
+                "source".to_string(),
+            ),
+        );
+        let subscriber = tracing_subscriber::registry().with(AllocationLayer::new());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = info_span!(
+                "component",
+                component_id = tracing::field::Empty,
+                partition = tracing::field::Empty
+            );
+            group_id.attach_to_span(&span);
+            span.record("component_id", &"in");
+            span.record("partition", &3);
+        });
+
+        assert_eq!(
+            allocation_group_registration(group_id).unwrap().tags,
+            component_tags("in".to_string(), "kafka".to_string(), "source".to_string())
+        );
+    }
+
+    #[test]
+    fn records_net_allocated_bytes_on_exit() {
+        use std::sync::Arc;
+
+        use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+        struct NetBytesLayer(Arc<Mutex<Vec<i64>>>);
+
+        impl<S: tracing::Subscriber> Layer<S> for NetBytesLayer {
+            fn on_record(&self, _: &tracing::Id, values: &Record<'_>, _: Context<'_, S>) {
+                struct NetBytesVisitor<'a>(&'a Mutex<Vec<i64>>);
+
+                impl<'a> Visit for NetBytesVisitor<'a> {
+                    fn record_i64(&mut self, field: &Field, value: i64) {
+                        if field.name() == NET_ALLOCATED_BYTES_FIELD {
+                            self.0.lock().unwrap().push(value);
+                        }
+                    }
+
+                    fn record_debug(&mut self, _: &Field, _: &dyn std::fmt::Debug) {}
+                }
+
+                values.record(&mut NetBytesVisitor(&self.0));
+            }
+        }
+
+        let group_id = test_group_id(77);
+        let recorded = Arc::new(Mutex::new(Vec::new()));
+        let subscriber = tracing_subscriber::registry()
+            .with(AllocationLayer::new())
+            .with(NetBytesLayer(Arc::clone(&recorded)));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = info_span!("component", allocation_net_bytes = tracing::field::Empty);
+            group_id.attach_to_span(&span);
+            span.in_scope(|| MainTracer.trace_allocation(100, group_id));
+            span.in_scope(|| MainTracer.trace_deallocation(40, group_id));
+        });
+
+        // The bytes are counted from the first time the span was entered.
+        assert_eq!(*recorded.lock().unwrap(), vec![100, 60]);
+    }
+
+    #[test]
+    fn counts_allocation_events() {
+        let group_id = test_group_id(122);
+        for _ in 0..3 {
+            MainTracer.trace_allocation(8, group_id);
+        }
+        for _ in 0..2 {
+            MainTracer.trace_deallocation(8, group_id);
+        }
+
+        let totals = group_mem_totals(&THREAD_LOCAL_REFS.lock().unwrap(), 122);
+        assert_eq!(
+            totals,
+            GroupMemCounts {
+                allocations: 24,
+                deallocations: 16,
+                allocation_events: 3,
+                deallocation_events: 2,
+                object_allocations: 3,
+            }
+        );
+    }
+
+    /// Traces matching (de)allocations on several threads at once, while the buffers of every thread are drained
+    /// concurrently like the processor does.
+    struct StressTestHarness {
+        threads: usize,
+        iterations: usize,
+        group_ids: Vec<AllocationGroupId>,
+    }
+
+    impl StressTestHarness {
+        fn run(&self) {
+            use rand::{rngs::SmallRng, Rng, SeedableRng};
+            use std::sync::{atomic::AtomicBool, Arc};
+
+            let done = Arc::new(AtomicBool::new(false));
+            let drainer = {
+                let done = Arc::clone(&done);
+                let group_ids = self.group_ids.clone();
+                thread::spawn(move || {
+                    while !done.load(Ordering::Relaxed) {
+                        let drained_buffer = flip_active_buffer();
+                        for group_id in &group_ids {
+                            drain_group_stats(group_id.as_raw() as usize, drained_buffer);
+                        }
+                        thread::yield_now();
+                    }
+                })
+            };
+
+            let workers = (0..self.threads)
+                .map(|thread_idx| {
+                    let group_ids = self.group_ids.clone();
+                    let iterations = self.iterations;
+                    thread::spawn(move || {
+                        let mut rng = SmallRng::seed_from_u64(thread_idx as u64);
+                        for _ in 0..iterations {
+                            let group_id = group_ids[rng.gen_range(0..group_ids.len())];
+                            let object_size = rng.gen_range(1..4096);
+                            MainTracer.trace_allocation(object_size, group_id);
+                            MainTracer.trace_deallocation(object_size, group_id);
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            for worker in workers {
+                worker.join().unwrap();
+            }
+            done.store(true, Ordering::Relaxed);
+            drainer.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn concurrent_tracing_matches_allocations_with_deallocations() {
+        let harness = StressTestHarness {
+            threads: 16,
+            iterations: 100_000,
+            group_ids: (93..=96).map(test_group_id).collect(),
+        };
+        harness.run();
+
+        let thread_local_refs = THREAD_LOCAL_REFS.lock().unwrap();
+        let mut events = 0;
+        for group_id in &harness.group_ids {
+            let group_idx = group_id.as_raw() as usize;
+            let totals = group_mem_totals(&thread_local_refs, group_idx);
+            assert_eq!(totals.allocations, totals.deallocations);
+            assert_eq!(totals.allocation_events, totals.deallocation_events);
+            assert_eq!(totals.live_allocations(), 0);
+            events += totals.allocation_events;
+        }
+        assert_eq!(events, (harness.threads * harness.iterations) as u64);
+    }
 }
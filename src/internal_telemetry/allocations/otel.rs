@@ -0,0 +1,91 @@
+//! Export of allocation group metrics through OpenTelemetry.
+//!
+//! The instruments are created in the global `MeterProvider`. When `OTEL_EXPORTER_OTLP_ENDPOINT` is set, an OTLP
+//! exporter to that endpoint is installed as the global `MeterProvider` first.
+
+use opentelemetry::{
+    global,
+    metrics::{Counter, UpDownCounter},
+    runtime, Context, KeyValue,
+};
+use opentelemetry_otlp::WithExportConfig;
+
+use super::GroupMemCounts;
+
+const OTLP_ENDPOINT_ENV_VAR: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+
+/// The OpenTelemetry instruments allocation group metrics are recorded with.
+pub(super) struct OtelAllocationMetrics {
+    allocated_bytes: UpDownCounter<i64>,
+    allocated_bytes_total: Counter<u64>,
+    deallocated_bytes_total: Counter<u64>,
+}
+
+impl OtelAllocationMetrics {
+    pub(super) fn new() -> Self {
+        install_otlp_exporter();
+
+        let meter = global::meter("vector");
+        Self {
+            allocated_bytes: meter
+                .i64_up_down_counter("component_allocated_bytes")
+                .with_description("The number of bytes currently allocated by the component.")
+                .init(),
+            allocated_bytes_total: meter
+                .u64_counter("component_allocated_bytes_total")
+                .with_description("The total number of bytes allocated by the component.")
+                .init(),
+            deallocated_bytes_total: meter
+                .u64_counter("component_deallocated_bytes_total")
+                .with_description("The total number of bytes deallocated by the component.")
+                .init(),
+        }
+    }
+
+    /// Records the (de)allocations of an allocation group since the last emission.
+    pub(super) fn record(&self, labels: &[(String, String)], diff: &GroupMemCounts) {
+        let cx = Context::current();
+        let attributes = labels
+            .iter()
+            .map(|(key, value)| KeyValue::new(key.clone(), value.clone()))
+            .collect::<Vec<_>>();
+        if diff.allocations > 0 {
+            self.allocated_bytes_total
+                .add(&cx, diff.allocations, &attributes);
+        }
+        if diff.deallocations > 0 {
+            self.deallocated_bytes_total
+                .add(&cx, diff.deallocations, &attributes);
+        }
+        let mem_used_diff = diff.allocations as i64 - diff.deallocations as i64;
+        if mem_used_diff != 0 {
+            self.allocated_bytes.add(&cx, mem_used_diff, &attributes);
+        }
+    }
+}
+
+fn install_otlp_exporter() {
+    let endpoint = match std::env::var(OTLP_ENDPOINT_ENV_VAR) {
+        Ok(endpoint) => endpoint,
+        Err(_) => return,
+    };
+
+    // The processor thread is not part of a Tokio runtime, so the exporter runs on a thread of its own.
+    let provider = opentelemetry_otlp::new_pipeline()
+        .metrics(runtime::TokioCurrentThread)
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint.clone()),
+        )
+        .build();
+    match provider {
+        Ok(provider) => {
+            global::set_meter_provider(provider);
+            info!(message = "Exporting allocation metrics through OTLP.", %endpoint);
+        }
+        Err(error) => {
+            error!(message = "Failed to install OTLP exporter for allocation metrics.", %error);
+        }
+    }
+}
@@ -0,0 +1,115 @@
+//! A panic hook that writes the bytes allocated by each allocation group to stderr, ahead of the panic message.
+//!
+//! A panic can happen anywhere, including in the allocator or while a lock of allocation tracing is held, so the hook
+//! neither allocates nor waits for locks: the statistics are formatted straight to stderr, and the parts guarded by a
+//! lock that is already held are left out.
+
+use std::{
+    io::{self, Write},
+    panic,
+    sync::{atomic::Ordering, MutexGuard, TryLockError, TryLockResult},
+};
+
+use super::{
+    component_log_fields, group_mem_totals, without_allocation_tracing, DoubleBufferedMetrics,
+    GROUP_INFO, THREAD_LOCAL_REFS, TRACK_ALLOCATIONS,
+};
+
+/// The header of the section that the hook writes ahead of the panic message.
+const PANIC_STATS_HEADER: &str = "group memory stats at panic:";
+
+/// Installs the panic hook, which calls the previously installed hook after writing the statistics of each group.
+pub(super) fn install_panic_hook() {
+    let previous_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        // Without tracked allocations, there are no statistics worth writing.
+        if TRACK_ALLOCATIONS.load(Ordering::Relaxed) {
+            without_allocation_tracing(|| {
+                let thread_local_refs = THREAD_LOCAL_REFS.try_lock();
+                // The (de)allocations that the processor has not yet reported are only included if no other thread
+                // holds the lock of the thread-local statistics, which might be the thread that panicked.
+                let thread_local_refs =
+                    held_lock(&thread_local_refs).map_or(&[][..], |refs| refs.as_slice());
+                // There is nowhere left to report a failure to write to stderr.
+                let _ = write_group_stats(&mut io::stderr().lock(), thread_local_refs);
+            });
+        }
+        previous_hook(info);
+    }));
+}
+
+/// Gets what the given lock guards, if it could be taken.
+///
+/// The lock may have been poisoned by an earlier panic, which doesn't stop the hook from reading what it guards.
+pub(super) fn held_lock<'a, T>(result: &'a TryLockResult<MutexGuard<'_, T>>) -> Option<&'a T> {
+    match result {
+        Ok(guard) => Some(&**guard),
+        Err(TryLockError::Poisoned(poisoned)) => Some(&**poisoned.get_ref()),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
+
+/// Writes the bytes allocated by each group that allocated, with the component it belongs to.
+///
+/// This only formats with `write!`, and neither allocates nor blocks.
+fn write_group_stats(
+    writer: &mut impl Write,
+    thread_local_refs: &[&'static DoubleBufferedMetrics],
+) -> io::Result<()> {
+    writeln!(writer, "{}", PANIC_STATS_HEADER)?;
+    for (group_idx, group_info) in GROUP_INFO.iter().enumerate() {
+        let totals = group_mem_totals(thread_local_refs, group_idx);
+        if totals.allocations == 0 {
+            continue;
+        }
+        write!(
+            writer,
+            "  group {}: allocated_bytes={} allocations={} deallocations={}",
+            group_idx,
+            totals.allocations.saturating_sub(totals.deallocations),
+            totals.allocation_events,
+            totals.deallocation_events,
+        )?;
+        let group_info = group_info.try_lock();
+        match held_lock(&group_info) {
+            Some(group_info) => {
+                let [component_id, component_type, component_kind] =
+                    component_log_fields(&group_info.tags);
+                writeln!(
+                    writer,
+                    " component_id={} component_type={} component_kind={}",
+                    component_id, component_type, component_kind,
+                )?;
+            }
+            None => writeln!(writer, " component_id=unknown")?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal_telemetry::allocations::{tests::test_group_id, MainTracer, Tracer};
+
+    #[test]
+    fn writes_stats_of_groups_that_allocated() {
+        let group_id = test_group_id(75);
+        MainTracer.trace_allocation(4096, group_id);
+        MainTracer.trace_allocation(1024, group_id);
+        MainTracer.trace_deallocation(1024, group_id);
+
+        let mut output = Vec::new();
+        write_group_stats(&mut output, &THREAD_LOCAL_REFS.lock().unwrap()).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with(PANIC_STATS_HEADER), "{}", output);
+        assert!(
+            output.contains(
+                "  group 75: allocated_bytes=4096 allocations=2 deallocations=1 component_id="
+            ),
+            "{}",
+            output
+        );
+    }
+}
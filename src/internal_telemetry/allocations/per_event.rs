@@ -0,0 +1,141 @@
+//! Bytes allocated while processing individual events, for capacity planning.
+
+use std::{collections::VecDeque, sync::Mutex};
+
+use once_cell::sync::Lazy;
+
+use super::{
+    current_allocated_bytes, current_allocation_group, without_allocation_tracing,
+    AllocationGroupId,
+};
+
+/// The number of most recent samples the summary is computed from.
+const MAX_SAMPLES: usize = 4096;
+
+/// The most recent samples of the bytes allocated while processing an event, oldest first.
+static SAMPLES: Lazy<Mutex<VecDeque<u64>>> = Lazy::new(|| Mutex::new(VecDeque::new()));
+
+/// Measures the bytes allocated while processing individual events.
+///
+/// Reading the allocated bytes of a group sums the statistics of every thread, so this is meant for sampling some
+/// events rather than measuring every single one.
+pub struct PerEventAllocationTracker;
+
+impl PerEventAllocationTracker {
+    /// Starts measuring the bytes allocated in the current allocation group, until the returned scope is dropped.
+    pub fn start() -> EventAllocationScope {
+        let group_id = current_allocation_group();
+        EventAllocationScope {
+            group_id,
+            start_bytes: current_allocated_bytes(group_id),
+        }
+    }
+
+    /// Summarizes the bytes allocated per event over the most recent samples, if there are any.
+    pub fn summary() -> Option<AllocationHistogram> {
+        let mut summary = None;
+        without_allocation_tracing(|| {
+            let mut samples = SAMPLES.lock().unwrap().iter().copied().collect::<Vec<_>>();
+            samples.sort_unstable();
+            summary = AllocationHistogram::from_sorted(&samples);
+        });
+        summary
+    }
+}
+
+/// The measurement of the bytes allocated while processing an event, which is recorded when dropped.
+#[must_use = "the bytes allocated are measured until the scope is dropped"]
+pub struct EventAllocationScope {
+    group_id: AllocationGroupId,
+    start_bytes: u64,
+}
+
+impl Drop for EventAllocationScope {
+    fn drop(&mut self) {
+        // Processing an event can free more than it allocates, which counts as allocating nothing.
+        let allocated_bytes =
+            current_allocated_bytes(self.group_id).saturating_sub(self.start_bytes);
+        without_allocation_tracing(|| {
+            let mut samples = SAMPLES.lock().unwrap();
+            if samples.len() == MAX_SAMPLES {
+                samples.pop_front();
+            }
+            samples.push_back(allocated_bytes);
+        });
+    }
+}
+
+/// Percentiles of the bytes allocated per event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AllocationHistogram {
+    /// The median number of bytes allocated per event.
+    pub p50: u64,
+
+    /// The number of bytes allocated by 95% of events or fewer.
+    pub p95: u64,
+
+    /// The number of bytes allocated by 99% of events or fewer.
+    pub p99: u64,
+
+    /// The highest number of bytes allocated by any event.
+    pub max: u64,
+}
+
+impl AllocationHistogram {
+    fn from_sorted(samples: &[u64]) -> Option<Self> {
+        let percentile = |percentile: usize| {
+            // The nearest-rank percentile, which is always one of the samples.
+            let rank = (samples.len() * percentile + 99) / 100;
+            samples[rank.max(1) - 1]
+        };
+        let max = *samples.last()?;
+        Some(Self {
+            p50: percentile(50),
+            p95: percentile(95),
+            p99: percentile(99),
+            max,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal_telemetry::allocations::{
+        in_task_allocation_group, tests::test_group_id, MainTracer, Tracer,
+    };
+
+    #[test]
+    fn computes_nearest_rank_percentiles() {
+        let samples = (1..=100).collect::<Vec<_>>();
+        assert_eq!(
+            AllocationHistogram::from_sorted(&samples),
+            Some(AllocationHistogram {
+                p50: 50,
+                p95: 95,
+                p99: 99,
+                max: 100,
+            })
+        );
+        assert_eq!(AllocationHistogram::from_sorted(&[]), None);
+    }
+
+    #[tokio::test]
+    async fn records_bytes_allocated_per_event() {
+        let group_id = test_group_id(109);
+        in_task_allocation_group(group_id, async {
+            MainTracer.trace_allocation(1000, group_id);
+            for event_bytes in [100, 300] {
+                let scope = PerEventAllocationTracker::start();
+                MainTracer.trace_allocation(event_bytes, group_id);
+                drop(scope);
+            }
+        })
+        .await;
+
+        let summary = PerEventAllocationTracker::summary().unwrap();
+        assert_eq!(summary.p50, 100);
+        assert_eq!(summary.max, 300);
+        MainTracer.trace_deallocation(1400, group_id);
+    }
+}
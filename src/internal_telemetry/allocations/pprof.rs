@@ -0,0 +1,168 @@
+//! `pprof` heap profile exposition of allocation group statistics.
+//!
+//! This is not a sampling heap profile: every allocation group is a single location, named after the values of its
+//! tags, whose samples are the bytes it currently has allocated and the bytes it allocated in total. Tools such as
+//! `go tool pprof`, Grafana Phlare, or Pyroscope can still visualize the resulting component-level breakdown.
+
+use std::{collections::HashMap, time::SystemTime};
+
+use prost::Message;
+
+use super::{allocation_snapshot, without_allocation_tracing, GroupAllocationStats};
+
+#[allow(warnings, clippy::all, clippy::pedantic, clippy::nursery)]
+mod proto {
+    include!(concat!(env!("OUT_DIR"), "/perftools.profiles.rs"));
+}
+
+use proto::{Function, Label, Line, Location, Profile, Sample, ValueType};
+
+/// Encodes the statistics of every allocation group as a `pprof` heap profile, in its protobuf format.
+pub fn encode_allocation_profile_pprof() -> Vec<u8> {
+    let mut output = Vec::new();
+    without_allocation_tracing(|| {
+        let time_nanos = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as i64);
+        output = build_profile(&allocation_snapshot(), time_nanos).encode_to_vec();
+    });
+    output
+}
+
+/// The string table of a profile, which every string of the profile is an index into.
+struct StringTable {
+    strings: Vec<String>,
+    indices: HashMap<String, i64>,
+}
+
+impl StringTable {
+    fn new() -> Self {
+        let mut table = Self {
+            strings: Vec::new(),
+            indices: HashMap::new(),
+        };
+        // The first string of the table must always be empty.
+        table.intern("");
+        table
+    }
+
+    fn intern(&mut self, string: &str) -> i64 {
+        if let Some(index) = self.indices.get(string) {
+            return *index;
+        }
+        let index = self.strings.len() as i64;
+        self.strings.push(string.to_string());
+        self.indices.insert(string.to_string(), index);
+        index
+    }
+}
+
+fn build_profile(groups: &[GroupAllocationStats], time_nanos: i64) -> Profile {
+    let mut strings = StringTable::new();
+    let bytes = strings.intern("bytes");
+    let sample_type = vec![
+        ValueType {
+            r#type: strings.intern("inuse_space"),
+            unit: bytes,
+        },
+        ValueType {
+            r#type: strings.intern("alloc_space"),
+            unit: bytes,
+        },
+    ];
+
+    let mut profile = Profile {
+        sample_type,
+        time_nanos,
+        period_type: Some(ValueType {
+            r#type: strings.intern("space"),
+            unit: bytes,
+        }),
+        ..Default::default()
+    };
+    for group in groups {
+        // Group IDs are nonzero, as the IDs of functions and locations must be.
+        let id = u64::from(group.group_id.as_raw());
+        let name = group
+            .tags
+            .iter()
+            .map(|(_, value)| value.as_str())
+            .collect::<Vec<_>>()
+            .join("/");
+        profile.function.push(Function {
+            id,
+            name: strings.intern(&name),
+            ..Default::default()
+        });
+        profile.location.push(Location {
+            id,
+            line: vec![Line {
+                function_id: id,
+                line: 0,
+            }],
+            ..Default::default()
+        });
+        profile.sample.push(Sample {
+            location_id: vec![id],
+            value: vec![
+                group.current_allocated_bytes() as i64,
+                group.allocated_bytes as i64,
+            ],
+            label: group
+                .tags
+                .iter()
+                .map(|(key, value)| Label {
+                    key: strings.intern(key),
+                    str: strings.intern(value),
+                    ..Default::default()
+                })
+                .collect(),
+        });
+    }
+    profile.string_table = strings.strings;
+    profile
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal_telemetry::allocations::{component_tags, AllocationGroupId};
+
+    #[test]
+    fn builds_a_location_per_group() {
+        let groups = [GroupAllocationStats {
+            group_id: AllocationGroupId::from_raw(2),
+            tags: component_tags("in".to_string(), "kafka".to_string(), "source".to_string()),
+            parent: None,
+            allocated_bytes: 1024,
+            deallocated_bytes: 24,
+        }];
+
+        let profile =
+            Profile::decode(build_profile(&groups, 1).encode_to_vec().as_slice()).unwrap();
+        let string = |index: i64| profile.string_table[index as usize].as_str();
+
+        assert_eq!(string(0), "");
+        assert_eq!(string(profile.sample_type[0].r#type), "inuse_space");
+        assert_eq!(string(profile.sample_type[1].r#type), "alloc_space");
+        assert_eq!(string(profile.function[0].name), "source/kafka/in");
+        assert_eq!(profile.location[0].line[0].function_id, 2);
+
+        let sample = &profile.sample[0];
+        assert_eq!(sample.location_id, vec![2]);
+        assert_eq!(sample.value, vec![1000, 1024]);
+        let labels = sample
+            .label
+            .iter()
+            .map(|label| (string(label.key), string(label.str)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            labels,
+            vec![
+                ("component_kind", "source"),
+                ("component_type", "kafka"),
+                ("component_id", "in"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,139 @@
+//! Memory usage of the whole process, as reported by the operating system.
+
+/// The memory used by the process.
+///
+/// Each value is `None` where the platform does not report it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ProcessMemory {
+    /// The number of bytes of the process that are resident in physical memory.
+    pub resident_bytes: Option<u64>,
+
+    /// The number of bytes of virtual memory reserved by the process.
+    pub virtual_bytes: Option<u64>,
+}
+
+/// Reads the memory currently used by the process.
+#[cfg(target_os = "linux")]
+pub fn process_memory() -> ProcessMemory {
+    std::fs::read_to_string("/proc/self/status")
+        .map(|status| parse_proc_status(&status))
+        .unwrap_or_default()
+}
+
+/// Reads the memory currently used by the process.
+#[cfg(target_os = "macos")]
+pub fn process_memory() -> ProcessMemory {
+    let mut info = std::mem::MaybeUninit::<libc::proc_taskinfo>::zeroed();
+    let size = std::mem::size_of::<libc::proc_taskinfo>() as libc::c_int;
+    // SAFETY: `info` is valid for writes of `size` bytes, which is the size `PROC_PIDTASKINFO` fills in.
+    let written = unsafe {
+        libc::proc_pidinfo(
+            libc::getpid(),
+            libc::PROC_PIDTASKINFO,
+            0,
+            info.as_mut_ptr().cast(),
+            size,
+        )
+    };
+    if written != size {
+        return ProcessMemory::default();
+    }
+
+    // SAFETY: `proc_pidinfo` filled in all of `info`.
+    let info = unsafe { info.assume_init() };
+    ProcessMemory {
+        resident_bytes: Some(info.pti_resident_size),
+        virtual_bytes: Some(info.pti_virtual_size),
+    }
+}
+
+/// Reads the memory currently used by the process.
+#[cfg(windows)]
+pub fn process_memory() -> ProcessMemory {
+    use winapi::um::{
+        processthreadsapi::GetCurrentProcess,
+        psapi::{GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS},
+    };
+
+    let mut counters = std::mem::MaybeUninit::<PROCESS_MEMORY_COUNTERS>::zeroed();
+    let size = std::mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32;
+    // SAFETY: `counters` is valid for writes of `size` bytes, and the pseudo handle of the current process does not
+    // need to be closed.
+    let succeeded =
+        unsafe { GetProcessMemoryInfo(GetCurrentProcess(), counters.as_mut_ptr(), size) };
+    if succeeded == 0 {
+        return ProcessMemory::default();
+    }
+
+    // SAFETY: `GetProcessMemoryInfo` filled in all of `counters`.
+    let counters = unsafe { counters.assume_init() };
+    ProcessMemory {
+        resident_bytes: Some(counters.WorkingSetSize as u64),
+        // The virtual size of the process is not part of its memory counters.
+        virtual_bytes: None,
+    }
+}
+
+/// Reads the memory currently used by the process.
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub const fn process_memory() -> ProcessMemory {
+    ProcessMemory {
+        resident_bytes: None,
+        virtual_bytes: None,
+    }
+}
+
+#[cfg(any(target_os = "linux", test))]
+fn parse_proc_status(status: &str) -> ProcessMemory {
+    let mut memory = ProcessMemory::default();
+    for line in status.lines() {
+        let (key, value) = match line.split_once(':') {
+            Some(field) => field,
+            None => continue,
+        };
+        let field = match key {
+            "VmRSS" => &mut memory.resident_bytes,
+            "VmSize" => &mut memory.virtual_bytes,
+            _ => continue,
+        };
+        *field = value
+            .trim()
+            .strip_suffix(" kB")
+            .and_then(|kilobytes| kilobytes.trim().parse::<u64>().ok())
+            .map(|kilobytes| kilobytes * 1024);
+    }
+    memory
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_proc_status() {
+        let status = "Name:\tvector\nVmPeak:\t  20000 kB\nVmSize:\t  18000 kB\nVmRSS:\t    4000 kB\nThreads:\t8\n";
+        assert_eq!(
+            parse_proc_status(status),
+            ProcessMemory {
+                resident_bytes: Some(4000 * 1024),
+                virtual_bytes: Some(18000 * 1024),
+            }
+        );
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[test]
+    fn reads_process_memory() {
+        let memory = process_memory();
+        assert!(memory.resident_bytes.unwrap() > 0);
+        assert!(memory.virtual_bytes.unwrap() >= memory.resident_bytes.unwrap());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn reads_process_memory() {
+        let memory = process_memory();
+        assert!(memory.resident_bytes.unwrap() > 0);
+        assert_eq!(memory.virtual_bytes, None);
+    }
+}
@@ -0,0 +1,293 @@
+//! Prometheus and OpenMetrics text exposition of allocation group statistics.
+
+use std::{fmt::Write, sync::atomic::Ordering};
+
+use super::{
+    allocation_snapshot, metric_prefix, without_allocation_tracing, GroupAllocationStats,
+    EXEMPLARS, GROUP_INFO,
+};
+
+/// The metrics emitted for each allocation group: name without the prefix, type, help text, and how to read the value.
+const METRICS: [(&str, &str, &str, fn(&GroupAllocationStats) -> u64); 3] = [
+    (
+        "component_allocated_bytes",
+        "gauge",
+        "The number of bytes currently allocated by the component.",
+        GroupAllocationStats::current_allocated_bytes,
+    ),
+    (
+        "component_allocated_bytes_total",
+        "counter",
+        "The total number of bytes allocated by the component.",
+        |stats| stats.allocated_bytes,
+    ),
+    (
+        "component_deallocated_bytes_total",
+        "counter",
+        "The total number of bytes deallocated by the component.",
+        |stats| stats.deallocated_bytes,
+    ),
+];
+
+/// Encodes the statistics of every allocation group in the Prometheus text exposition format.
+///
+/// This is meant for embedders that want to serve allocation statistics from their own HTTP endpoint without going
+/// through Vector's internal metrics pipeline. Metric names start with the prefix set in the
+/// [`AllocationTracingConfig`](super::AllocationTracingConfig).
+pub fn encode_allocation_metrics_prometheus() -> String {
+    let mut output = String::new();
+    without_allocation_tracing(|| {
+        output = encode(metric_prefix(), &allocation_snapshot());
+    });
+    output
+}
+
+fn encode(prefix: &str, groups: &[GroupAllocationStats]) -> String {
+    let mut output = String::new();
+    for (name, metric_type, help, value) in METRICS {
+        let name = format!("{}{}", prefix, name);
+        // Writing to a `String` cannot fail.
+        let _ = writeln!(output, "# HELP {} {}", name, help);
+        let _ = writeln!(output, "# TYPE {} {}", name, metric_type);
+        for group in groups {
+            output.push_str(&name);
+            write_labels(&mut output, group);
+            let _ = writeln!(output, " {}", value(group));
+        }
+    }
+    output
+}
+
+/// Encodes the statistics of every allocation group in the OpenMetrics text format.
+///
+/// The metrics are those of [`encode_allocation_metrics_prometheus`]. As OpenMetrics counters, the families of the
+/// counters would be named like the allocated bytes gauge, so they are left untyped instead. When exemplars are enabled
+/// in the [`AllocationTracingConfig`](super::AllocationTracingConfig), the gauge of each group whose span had a
+/// `trace_id` field recorded carries an exemplar with that trace ID.
+pub fn encode_allocation_metrics_openmetrics() -> String {
+    let mut output = String::new();
+    without_allocation_tracing(|| {
+        let exemplars = EXEMPLARS.load(Ordering::Relaxed);
+        let groups = allocation_snapshot();
+        let trace_ids = groups
+            .iter()
+            .map(|group| {
+                exemplars
+                    .then(|| group.group_id.slot_index())
+                    .flatten()
+                    .map(|group_idx| &GROUP_INFO[group_idx])
+                    .and_then(|group_info| group_info.lock().unwrap().trace_id.clone())
+            })
+            .collect::<Vec<_>>();
+        output = encode_openmetrics(metric_prefix(), &groups, &trace_ids);
+    });
+    output
+}
+
+/// Encodes the given groups in the OpenMetrics text format, with an exemplar for each group that has a trace ID.
+///
+/// `trace_ids` is indexed like `groups`.
+fn encode_openmetrics(
+    prefix: &str,
+    groups: &[GroupAllocationStats],
+    trace_ids: &[Option<String>],
+) -> String {
+    let mut output = String::new();
+    for (name, metric_type, help, value) in METRICS {
+        let name = format!("{}{}", prefix, name);
+        let metric_type = if metric_type == "counter" {
+            "unknown"
+        } else {
+            metric_type
+        };
+        let _ = writeln!(output, "# HELP {} {}", name, help);
+        let _ = writeln!(output, "# TYPE {} {}", name, metric_type);
+        for (group, trace_id) in groups.iter().zip(trace_ids) {
+            let value = value(group);
+            output.push_str(&name);
+            write_labels(&mut output, group);
+            let _ = write!(output, " {}", value);
+            if let (true, Some(trace_id)) = (metric_type == "gauge", trace_id) {
+                output.push_str(" # {trace_id=\"");
+                escape_label_value(&mut output, trace_id);
+                let _ = write!(output, "\"}} {}", value);
+            }
+            output.push('\n');
+        }
+    }
+    output.push_str("# EOF\n");
+    output
+}
+
+/// Writes the tags of the group as labels.
+///
+/// A group without tags, such as one that was never registered, is labeled with its ID instead, so that the series
+/// of untagged groups don't collide with each other.
+fn write_labels(output: &mut String, group: &GroupAllocationStats) {
+    output.push('{');
+    if group.tags.is_empty() {
+        let _ = write!(output, "group_id=\"{}\"", group.group_id.as_raw());
+    }
+    for (idx, (key, value)) in group.tags.iter().enumerate() {
+        if idx > 0 {
+            output.push(',');
+        }
+        write_label_name(output, key);
+        output.push_str("=\"");
+        escape_label_value(output, value);
+        output.push('"');
+    }
+    output.push('}');
+}
+
+/// Writes the tag key as a label name, which must match `[a-zA-Z_][a-zA-Z0-9_]*`, replacing any other character with
+/// an underscore.
+fn write_label_name(output: &mut String, key: &str) {
+    if !key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        output.push('_');
+    }
+    for c in key.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            output.push(c);
+        } else {
+            output.push('_');
+        }
+    }
+}
+
+fn escape_label_value(output: &mut String, value: &str) {
+    for c in value.chars() {
+        match c {
+            '\\' => output.push_str("\\\\"),
+            '"' => output.push_str("\\\""),
+            '\n' => output.push_str("\\n"),
+            c => output.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal_telemetry::allocations::AllocationGroupId;
+
+    fn tags(component_id: &str) -> Vec<(String, String)> {
+        vec![
+            ("component_kind".to_string(), "source".to_string()),
+            ("component_id".to_string(), component_id.to_string()),
+        ]
+    }
+
+    #[test]
+    fn encodes_groups() {
+        let groups = [
+            GroupAllocationStats {
+                group_id: AllocationGroupId::from_raw(2),
+                tags: tags("in"),
+                parent: None,
+                allocated_bytes: 1024,
+                deallocated_bytes: 24,
+            },
+            GroupAllocationStats {
+                group_id: AllocationGroupId::from_raw(3),
+                tags: tags("quoted \"in\"\\"),
+                parent: None,
+                allocated_bytes: 10,
+                deallocated_bytes: 0,
+            },
+        ];
+
+        assert_eq!(
+            encode("vector_", &groups),
+            "# HELP vector_component_allocated_bytes The number of bytes currently allocated by the component.\n\
+             # TYPE vector_component_allocated_bytes gauge\n\
+             vector_component_allocated_bytes{component_kind=\"source\",component_id=\"in\"} 1000\n\
+             vector_component_allocated_bytes{component_kind=\"source\",component_id=\"quoted \\\"in\\\"\\\\\"} 10\n\
+             # HELP vector_component_allocated_bytes_total The total number of bytes allocated by the component.\n\
+             # TYPE vector_component_allocated_bytes_total counter\n\
+             vector_component_allocated_bytes_total{component_kind=\"source\",component_id=\"in\"} 1024\n\
+             vector_component_allocated_bytes_total{component_kind=\"source\",component_id=\"quoted \\\"in\\\"\\\\\"} 10\n\
+             # HELP vector_component_deallocated_bytes_total The total number of bytes deallocated by the component.\n\
+             # TYPE vector_component_deallocated_bytes_total counter\n\
+             vector_component_deallocated_bytes_total{component_kind=\"source\",component_id=\"in\"} 24\n\
+             vector_component_deallocated_bytes_total{component_kind=\"source\",component_id=\"quoted \\\"in\\\"\\\\\"} 0\n"
+        );
+    }
+
+    #[test]
+    fn sanitizes_label_names_and_labels_untagged_groups() {
+        let group = |raw_group_id, tags| GroupAllocationStats {
+            group_id: AllocationGroupId::from_raw(raw_group_id),
+            tags,
+            parent: None,
+            allocated_bytes: 10,
+            deallocated_bytes: 0,
+        };
+        let groups = [
+            group(2, Vec::new()),
+            group(3, Vec::new()),
+            group(
+                4,
+                vec![
+                    ("component.id".to_string(), "in".to_string()),
+                    ("1st".to_string(), "a".to_string()),
+                ],
+            ),
+        ];
+
+        let output = encode("vector_", &groups);
+        let gauges = output
+            .lines()
+            .filter(|line| line.starts_with("vector_component_allocated_bytes{"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            gauges,
+            [
+                "vector_component_allocated_bytes{group_id=\"2\"} 10",
+                "vector_component_allocated_bytes{group_id=\"3\"} 10",
+                "vector_component_allocated_bytes{component_id=\"in\",_1st=\"a\"} 10",
+            ]
+        );
+    }
+
+    #[test]
+    fn encodes_openmetrics_exemplars() {
+        let groups = [
+            GroupAllocationStats {
+                group_id: AllocationGroupId::from_raw(2),
+                tags: tags("in"),
+                parent: None,
+                allocated_bytes: 1024,
+                deallocated_bytes: 24,
+            },
+            GroupAllocationStats {
+                group_id: AllocationGroupId::from_raw(3),
+                tags: tags("out"),
+                parent: None,
+                allocated_bytes: 10,
+                deallocated_bytes: 0,
+            },
+        ];
+
+        assert_eq!(
+            encode_openmetrics(
+                "vector_",
+                &groups,
+                &[Some("4bf92f3577b34da6a3ce929d0e0e4736".to_string()), None]
+            ),
+            "# HELP vector_component_allocated_bytes The number of bytes currently allocated by the component.\n\
+             # TYPE vector_component_allocated_bytes gauge\n\
+             vector_component_allocated_bytes{component_kind=\"source\",component_id=\"in\"} 1000 # {trace_id=\"4bf92f3577b34da6a3ce929d0e0e4736\"} 1000\n\
+             vector_component_allocated_bytes{component_kind=\"source\",component_id=\"out\"} 10\n\
+             # HELP vector_component_allocated_bytes_total The total number of bytes allocated by the component.\n\
+             # TYPE vector_component_allocated_bytes_total unknown\n\
+             vector_component_allocated_bytes_total{component_kind=\"source\",component_id=\"in\"} 1024\n\
+             vector_component_allocated_bytes_total{component_kind=\"source\",component_id=\"out\"} 10\n\
+             # HELP vector_component_deallocated_bytes_total The total number of bytes deallocated by the component.\n\
+             # TYPE vector_component_deallocated_bytes_total unknown\n\
+             vector_component_deallocated_bytes_total{component_kind=\"source\",component_id=\"in\"} 24\n\
+             vector_component_deallocated_bytes_total{component_kind=\"source\",component_id=\"out\"} 0\n\
+             # EOF\n"
+        );
+    }
+}
@@ -0,0 +1,421 @@
+//! Allocation statistics reported by other processes over a Unix domain socket.
+//!
+//! Sidecar processes that cannot use Vector's allocator, such as language-specific agents, can report their own
+//! allocations so that they show up next to the allocation groups of Vector's components. Clients connect to the
+//! socket, send the proxy token on the first line, and then send one JSON encoded [`AllocationEvent`] per line.
+//!
+//! Allocation group IDs are scarce, so the proxy registers at most [`AllocationProxyConfig::max_groups`] groups, and
+//! the events of any other group are dropped. Only [`MAX_CONNECTIONS`] connections are read at a time, and further
+//! connections are closed as soon as they are accepted.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::{self, BufRead, BufReader},
+    os::unix::{
+        fs::{FileTypeExt, PermissionsExt},
+        net::{UnixListener, UnixStream},
+    },
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+};
+
+use serde::Deserialize;
+
+use super::{
+    apply_allocation_delta, component_tags, register_allocation_group, without_allocation_tracing,
+    AllocationGroupId,
+};
+
+const PROXY_THREAD_NAME: &str = "vector-alloc-proxy";
+
+/// Permission bits of the socket, which only lets the owner and its group connect.
+const SOCKET_MODE: u32 = 0o660;
+
+/// The number of connections that are read at the same time, each on its own thread.
+const MAX_CONNECTIONS: usize = 16;
+
+/// The number of names of groups that couldn't be registered that are remembered, so that the events of a group that
+/// was rejected don't attempt to register it again.
+const MAX_REJECTED_NAMES: usize = 1024;
+
+/// The configuration of the allocation group proxy.
+#[derive(Clone, Debug)]
+pub struct AllocationProxyConfig {
+    /// Path of the Unix domain socket to listen on.
+    pub socket_path: PathBuf,
+
+    /// Path of the file holding the token clients must send before any allocation event.
+    ///
+    /// The file must not be accessible by other users.
+    pub token_path: PathBuf,
+
+    /// The number of allocation groups the proxy registers for the groups of other processes.
+    pub max_groups: usize,
+}
+
+/// A change in the bytes allocated by a group of another process.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct AllocationEvent {
+    /// The name of the group, which is mapped to a local allocation group.
+    pub group_id_str: String,
+
+    /// The number of bytes allocated, or deallocated if negative.
+    pub delta_bytes: i64,
+}
+
+/// Receives allocation events from other processes and applies them to the statistics of local allocation groups.
+pub struct AllocationGroupProxySource {
+    listener: UnixListener,
+    token: Arc<String>,
+    registry: ProxyRegistry,
+}
+
+/// The local allocation groups of the groups of other processes, by name.
+struct ProxyRegistry {
+    groups: HashMap<String, AllocationGroupId>,
+    rejected: HashSet<String>,
+    max_groups: usize,
+}
+
+impl AllocationGroupProxySource {
+    /// Binds the proxy to its socket.
+    ///
+    /// # Errors
+    ///
+    /// If the token file cannot be read, is accessible by other users, or the socket cannot be bound, an error
+    /// variant will be returned.
+    pub fn bind(config: &AllocationProxyConfig) -> io::Result<Self> {
+        let token_metadata = fs::metadata(&config.token_path)?;
+        if token_metadata.permissions().mode() & 0o007 != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "allocation proxy token file {:?} must not be accessible by other users",
+                    config.token_path
+                ),
+            ));
+        }
+        let token = fs::read_to_string(&config.token_path)?.trim().to_string();
+        if token.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "allocation proxy token file {:?} is empty",
+                    config.token_path
+                ),
+            ));
+        }
+
+        // A socket left behind by a previous run would make binding fail, but any other file at its path is kept.
+        match fs::symlink_metadata(&config.socket_path) {
+            Ok(metadata) if metadata.file_type().is_socket() => {
+                fs::remove_file(&config.socket_path)?;
+            }
+            Ok(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!(
+                        "allocation proxy socket path {:?} exists and is not a socket",
+                        config.socket_path
+                    ),
+                ))
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+            Err(error) => return Err(error),
+        }
+        let listener = UnixListener::bind(&config.socket_path)?;
+        fs::set_permissions(&config.socket_path, fs::Permissions::from_mode(SOCKET_MODE))?;
+
+        Ok(Self {
+            listener,
+            token: Arc::new(token),
+            registry: ProxyRegistry {
+                groups: HashMap::new(),
+                rejected: HashSet::new(),
+                max_groups: config.max_groups,
+            },
+        })
+    }
+
+    /// Accepts connections and applies their events on dedicated threads for the remainder of the program.
+    ///
+    /// Every connection is read on its own thread, up to [`MAX_CONNECTIONS`] at a time, while the events of all
+    /// connections are applied on a single long-lived thread, so that short-lived connections don't each leave
+    /// per-thread statistics behind.
+    pub fn spawn(self) -> io::Result<()> {
+        let Self {
+            listener,
+            token,
+            mut registry,
+        } = self;
+        let (events_tx, events_rx) = mpsc::channel::<AllocationEvent>();
+
+        thread::Builder::new()
+            .name(PROXY_THREAD_NAME.to_string())
+            .spawn(move || {
+                without_allocation_tracing(|| apply_events(&events_rx, &mut registry));
+            })?;
+        thread::Builder::new()
+            .name(PROXY_THREAD_NAME.to_string())
+            .spawn(move || {
+                without_allocation_tracing(|| accept_connections(&listener, &token, &events_tx));
+            })?;
+        Ok(())
+    }
+}
+
+fn apply_events(events_rx: &mpsc::Receiver<AllocationEvent>, registry: &mut ProxyRegistry) {
+    for event in events_rx {
+        if let Some(group_id) = registry.group_id(&event.group_id_str) {
+            apply_allocation_delta(group_id, event.delta_bytes);
+        }
+    }
+}
+
+fn accept_connections(
+    listener: &UnixListener,
+    token: &Arc<String>,
+    events_tx: &mpsc::Sender<AllocationEvent>,
+) {
+    let connections = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let acquired = connections
+                    .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+                        (count < MAX_CONNECTIONS).then(|| count + 1)
+                    })
+                    .is_ok();
+                if !acquired {
+                    warn!(
+                        message =
+                            "Closed allocation proxy connection, too many connections are open.",
+                        max_connections = MAX_CONNECTIONS,
+                        internal_log_rate_limit = true
+                    );
+                    continue;
+                }
+
+                let token = Arc::clone(token);
+                let events_tx = events_tx.clone();
+                let thread_connections = Arc::clone(&connections);
+                let spawned = thread::Builder::new()
+                    .name(PROXY_THREAD_NAME.to_string())
+                    .spawn(move || {
+                        without_allocation_tracing(|| read_events(stream, &token, &events_tx));
+                        thread_connections.fetch_sub(1, Ordering::AcqRel);
+                    });
+                if let Err(error) = spawned {
+                    connections.fetch_sub(1, Ordering::AcqRel);
+                    error!(message = "Failed to handle allocation proxy connection.", %error);
+                }
+            }
+            Err(error) => {
+                warn!(
+                    message = "Failed to accept allocation proxy connection.",
+                    %error,
+                    internal_log_rate_limit = true
+                );
+            }
+        }
+    }
+}
+
+fn read_events(stream: UnixStream, token: &str, events_tx: &mpsc::Sender<AllocationEvent>) {
+    let mut lines = BufReader::new(stream).lines();
+    let authenticated = matches!(lines.next(), Some(Ok(line)) if tokens_match(line.trim(), token));
+    if !authenticated {
+        warn!(
+            message = "Rejected allocation proxy connection with an invalid token.",
+            internal_log_rate_limit = true
+        );
+        return;
+    }
+
+    for line in lines {
+        let line = match line {
+            Ok(line) => line,
+            Err(error) => {
+                debug!(message = "Allocation proxy connection closed.", %error);
+                return;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<AllocationEvent>(&line) {
+            Ok(event) => {
+                if events_tx.send(event).is_err() {
+                    return;
+                }
+            }
+            Err(error) => {
+                warn!(
+                    message = "Failed to parse allocation proxy event.",
+                    %error,
+                    internal_log_rate_limit = true
+                );
+            }
+        }
+    }
+}
+
+impl ProxyRegistry {
+    /// Maps the name of a group of another process to a local allocation group, registering it on first use.
+    ///
+    /// If the proxy already registered its maximum number of groups, or no more allocation groups can be registered,
+    /// returns `None` rather than attributing the bytes to a shared allocation group. A name that couldn't be
+    /// registered is remembered, so that its next events are dropped without registering it again.
+    fn group_id(&mut self, name: &str) -> Option<AllocationGroupId> {
+        if let Some(group_id) = self.groups.get(name) {
+            return Some(*group_id);
+        }
+        if self.groups.len() >= self.max_groups || self.rejected.contains(name) {
+            return None;
+        }
+
+        let registration = register_allocation_group(component_tags(
+            name.to_string(),
+            "allocation_proxy".to_string(),
+            "proxy".to_string(),
+        ));
+        if registration.id.is_shared() {
+            if self.rejected.len() < MAX_REJECTED_NAMES {
+                self.rejected.insert(name.to_string());
+            }
+            return None;
+        }
+        self.groups.insert(name.to_string(), registration.id);
+        Some(registration.id)
+    }
+}
+
+/// Compares the tokens without returning early, so the time taken does not tell how much of the token was right.
+fn tokens_match(received: &str, expected: &str) -> bool {
+    received.len() == expected.len()
+        && received
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, time::Duration};
+
+    use super::*;
+    use crate::internal_telemetry::allocations::{current_allocated_bytes, tests::test_group_id};
+
+    #[test]
+    fn compares_tokens() {
+        assert!(tokens_match("secret", "secret"));
+        assert!(!tokens_match("secreT", "secret"));
+        assert!(!tokens_match("secret1", "secret"));
+    }
+
+    #[test]
+    fn rejects_token_file_accessible_by_others() {
+        let dir = crate::test_util::temp_dir();
+        fs::create_dir_all(&dir).unwrap();
+        let token_path = dir.join("token");
+        fs::write(&token_path, "secret").unwrap();
+        fs::set_permissions(&token_path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        let error = AllocationGroupProxySource::bind(&AllocationProxyConfig {
+            socket_path: dir.join("proxy.sock"),
+            token_path,
+            max_groups: 1,
+        })
+        .err()
+        .unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn keeps_files_at_the_socket_path_that_are_not_sockets() {
+        let dir = crate::test_util::temp_dir();
+        fs::create_dir_all(&dir).unwrap();
+        let token_path = dir.join("token");
+        fs::write(&token_path, "secret").unwrap();
+        fs::set_permissions(&token_path, fs::Permissions::from_mode(0o600)).unwrap();
+        let socket_path = dir.join("proxy.sock");
+        fs::write(&socket_path, "not a socket").unwrap();
+
+        let error = AllocationGroupProxySource::bind(&AllocationProxyConfig {
+            socket_path: socket_path.clone(),
+            token_path,
+            max_groups: 1,
+        })
+        .err()
+        .unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&socket_path).unwrap(), "not a socket");
+    }
+
+    #[test]
+    fn registers_at_most_the_maximum_number_of_groups() {
+        let group_id = test_group_id(61);
+        let mut registry = ProxyRegistry {
+            groups: HashMap::from([("sidecar".to_string(), group_id)]),
+            rejected: HashSet::new(),
+            max_groups: 1,
+        };
+
+        assert_eq!(registry.group_id("sidecar"), Some(group_id));
+        assert_eq!(registry.group_id("other"), None);
+        assert_eq!(registry.groups.len(), 1);
+    }
+
+    #[test]
+    fn applies_events_of_authenticated_clients() {
+        let group_id = test_group_id(121);
+        let dir = crate::test_util::temp_dir();
+        fs::create_dir_all(&dir).unwrap();
+        let token_path = dir.join("token");
+        fs::write(&token_path, "secret\n").unwrap();
+        fs::set_permissions(&token_path, fs::Permissions::from_mode(0o600)).unwrap();
+        let socket_path = dir.join("proxy.sock");
+
+        let mut proxy = AllocationGroupProxySource::bind(&AllocationProxyConfig {
+            socket_path: socket_path.clone(),
+            token_path,
+            max_groups: 1,
+        })
+        .unwrap();
+        proxy
+            .registry
+            .groups
+            .insert("sidecar".to_string(), group_id);
+        proxy.spawn().unwrap();
+
+        let event = "{\"group_id_str\":\"sidecar\",\"delta_bytes\":100}\n";
+        let mut unauthenticated = UnixStream::connect(&socket_path).unwrap();
+        write!(unauthenticated, "wrong\n{}", event).unwrap();
+        drop(unauthenticated);
+
+        let mut client = UnixStream::connect(&socket_path).unwrap();
+        write!(
+            client,
+            "secret\n{}{{\"group_id_str\":\"sidecar\",\"delta_bytes\":-40}}\n",
+            event
+        )
+        .unwrap();
+        drop(client);
+
+        // Connections are read on their own threads, so wait for both to be done.
+        for _ in 0..100 {
+            if current_allocated_bytes(group_id) == 60 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(current_allocated_bytes(group_id), 60);
+    }
+}
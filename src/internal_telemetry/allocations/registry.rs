@@ -0,0 +1,89 @@
+//! A registry of the allocation groups of components, for looking them up by component ID.
+
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+
+use super::AllocationGroupToken;
+
+static GLOBAL_REGISTRY: Lazy<AllocationGroupRegistry> = Lazy::new(AllocationGroupRegistry::default);
+
+/// Maps the IDs of components to the tokens of their allocation groups.
+///
+/// This lets layers that only know the ID of a component, such as the API, look its allocation group up without the
+/// token being threaded through every layer in between.
+#[derive(Default)]
+pub struct AllocationGroupRegistry {
+    tokens: DashMap<String, Arc<AllocationGroupToken>>,
+}
+
+impl AllocationGroupRegistry {
+    /// Gets the registry of the allocation groups of the running topology.
+    pub fn global() -> &'static Self {
+        &GLOBAL_REGISTRY
+    }
+
+    /// Registers the token of the allocation group of the given component.
+    ///
+    /// The token previously registered for the component, if any, is released as if it was deregistered.
+    pub fn register(&self, component_id: &str, token: Arc<AllocationGroupToken>) {
+        if let Some(previous) = self.tokens.insert(component_id.to_string(), token) {
+            release(previous);
+        }
+    }
+
+    /// Looks up the token of the allocation group of the given component.
+    pub fn lookup(&self, component_id: &str) -> Option<Arc<AllocationGroupToken>> {
+        self.tokens
+            .get(component_id)
+            .map(|token| Arc::clone(token.value()))
+    }
+
+    /// Deregisters the token of the allocation group of the given component.
+    ///
+    /// If the registry held the last reference to the token, its allocation group is deregistered along with it.
+    /// Otherwise, the group is deregistered once the last reference is dropped, without being checked for leaked bytes
+    /// either way.
+    pub fn deregister(&self, component_id: &str) {
+        if let Some((_, token)) = self.tokens.remove(component_id) {
+            release(token);
+        }
+    }
+}
+
+/// Deregisters the allocation group of a token that was removed from the registry, once nothing else references it.
+///
+/// The component may have just shut down, so bytes it allocated can still be in flight: they remain attributed to its
+/// group until they are deallocated, rather than being treated as leaked.
+fn release(token: Arc<AllocationGroupToken>) {
+    token.drain_on_drop();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal_telemetry::allocations::tests::TestGroup;
+
+    #[test]
+    fn looks_up_registered_tokens() {
+        let registry = AllocationGroupRegistry::default();
+        let group = TestGroup::register(85);
+        let token = Arc::new(group.token());
+        registry.register("my_kafka_source", Arc::clone(&token));
+
+        assert_eq!(
+            registry.lookup("my_kafka_source").map(|token| token.id()),
+            Some(token.id())
+        );
+        assert!(registry.lookup("other_source").is_none());
+
+        registry.deregister("my_kafka_source");
+        assert!(registry.lookup("my_kafka_source").is_none());
+        // The test still holds the token, which deregisters the group once dropped, without checking it for leaks.
+        let weak = token.downgrade();
+        assert!(weak.upgrade().is_some());
+        drop(token);
+        assert!(weak.upgrade().is_none());
+    }
+}
@@ -0,0 +1,150 @@
+//! Memory reports of the components with an allocation group, for diagnostic bundles.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use arr_macro::arr;
+use serde::Serialize;
+
+use super::{
+    allocation_group_registration, current_allocated_bytes, without_allocation_tracing,
+    AllocationGroupId, NUM_GROUPS,
+};
+
+/// Whether each allocation group is currently registered.
+static GROUP_REGISTERED: [AtomicBool; NUM_GROUPS] = arr![AtomicBool::new(false); 128];
+
+/// The hash of the configuration of the component of each allocation group, or zero if it was not set.
+static GROUP_CONFIG_HASHES: [AtomicU64; NUM_GROUPS] = arr![AtomicU64::new(0); 128];
+
+/// The highest number of bytes each allocation group had allocated as of the processor's emissions.
+static GROUP_PEAK_BYTES: [AtomicU64; NUM_GROUPS] = arr![AtomicU64::new(0); 128];
+
+/// The memory statistics of a component, along with the metadata of its configuration.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ComponentMemoryReport {
+    /// The ID of the component, or `unknown` if the allocation group has no `component_id` tag.
+    pub component_id: String,
+
+    /// The type of the component, or `unknown` if the allocation group has no `component_type` tag.
+    pub component_type: String,
+
+    /// The hash of the configuration of the component, or zero if it was not set with
+    /// [`set_allocation_group_config_hash`].
+    pub config_hash: u64,
+
+    /// The number of bytes currently allocated by the component.
+    pub allocated_bytes: u64,
+
+    /// The highest number of bytes allocated by the component since its allocation group was registered.
+    ///
+    /// This is sampled once per reporting interval, so shorter spikes are not accounted for.
+    pub peak_bytes: u64,
+
+    /// The tags of the allocation group of the component.
+    pub tags: Vec<(String, String)>,
+}
+
+/// Sets the hash of the configuration of the component that the given allocation group belongs to.
+///
+/// This lets memory reports be matched with the configuration a component was running with, such as across reloads.
+pub fn set_allocation_group_config_hash(group_id: AllocationGroupId, config_hash: u64) {
+    if let Some(group_idx) = group_id.slot_index() {
+        GROUP_CONFIG_HASHES[group_idx].store(config_hash, Ordering::Relaxed);
+    }
+}
+
+/// Gets the memory report of the given allocation group.
+pub(super) fn memory_report(group_id: AllocationGroupId) -> ComponentMemoryReport {
+    let group_idx = group_id.slot_index();
+    let tags = allocation_group_registration(group_id)
+        .map(|registration| registration.tags)
+        .unwrap_or_default();
+    let tag = |key: &str| {
+        tags.iter()
+            .find(|(tag_key, _)| tag_key == key)
+            .map_or_else(|| "unknown".to_string(), |(_, value)| value.clone())
+    };
+    let allocated_bytes = current_allocated_bytes(group_id);
+    ComponentMemoryReport {
+        component_id: tag("component_id"),
+        component_type: tag("component_type"),
+        config_hash: group_idx.map_or(0, |group_idx| {
+            GROUP_CONFIG_HASHES[group_idx].load(Ordering::Relaxed)
+        }),
+        allocated_bytes,
+        peak_bytes: group_idx
+            .map_or(0, |group_idx| {
+                GROUP_PEAK_BYTES[group_idx].load(Ordering::Relaxed)
+            })
+            .max(allocated_bytes),
+        tags,
+    }
+}
+
+/// Gathers the memory reports of every registered allocation group, for serialization into a support bundle.
+pub fn gather_all_memory_reports() -> Vec<ComponentMemoryReport> {
+    let mut reports = Vec::new();
+    without_allocation_tracing(|| {
+        reports = (1..NUM_GROUPS)
+            .filter(|group_idx| GROUP_REGISTERED[*group_idx].load(Ordering::Relaxed))
+            .map(|group_idx| memory_report(AllocationGroupId::from_raw(group_idx as u8)))
+            .collect();
+    });
+    reports
+}
+
+/// Marks the given allocation group as registered, or not, clearing the metadata of its previous registration.
+pub(super) fn set_registered(group_id: AllocationGroupId, registered: bool) {
+    let group_idx = match group_id.slot_index() {
+        Some(group_idx) => group_idx,
+        None => return,
+    };
+    if registered {
+        GROUP_CONFIG_HASHES[group_idx].store(0, Ordering::Relaxed);
+        GROUP_PEAK_BYTES[group_idx].store(0, Ordering::Relaxed);
+    }
+    GROUP_REGISTERED[group_idx].store(registered, Ordering::Relaxed);
+}
+
+/// Records the number of bytes the given allocation group had allocated as of the processor's emission.
+pub(super) fn record_peak_bytes(group_idx: usize, allocated_bytes: u64) {
+    GROUP_PEAK_BYTES[group_idx].fetch_max(allocated_bytes, Ordering::Relaxed);
+}
+
+/// Forgets the peak bytes of the given allocation group.
+pub(super) fn reset_peak_bytes(group_idx: usize) {
+    GROUP_PEAK_BYTES[group_idx].store(0, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal_telemetry::allocations::{
+        component_tags, set_group_tags, tests::TestGroup, MainTracer, Tracer,
+    };
+
+    #[test]
+    fn reports_registered_groups() {
+        let group = TestGroup::register(99);
+        let group_id = group.id();
+        set_group_tags(
+            group_id,
+            component_tags("out".to_string(), "console".to_string(), "sink".to_string()),
+        );
+        set_allocation_group_config_hash(group_id, 42);
+        MainTracer.trace_allocation(300, group_id);
+        record_peak_bytes(group_id.as_raw() as usize, 300);
+        MainTracer.trace_deallocation(200, group_id);
+
+        let report = gather_all_memory_reports()
+            .into_iter()
+            .find(|report| report.component_id == "out")
+            .unwrap();
+        assert_eq!(report.component_type, "console");
+        assert_eq!(report.config_hash, 42);
+        assert_eq!(report.allocated_bytes, 100);
+        assert_eq!(report.peak_bytes, 300);
+
+        MainTracer.trace_deallocation(100, group_id);
+    }
+}
@@ -0,0 +1,44 @@
+//! The sink that the processor emits the metrics of allocation tracing to.
+
+use metrics::{counter, decrement_gauge, gauge, increment_gauge};
+
+/// Receives the metrics that the processor emits after each reporting interval.
+///
+/// This lets embedders export the metrics of allocation tracing to another backend than the `metrics` crate. The sink
+/// is called on the processor thread with allocation tracing suspended, so it must not block for long.
+pub trait AllocationMetricSink: Send {
+    /// Adds `value` to the counter with the given name and labels.
+    fn record_counter(&self, name: &'static str, value: u64, labels: &[(String, String)]);
+
+    /// Sets the gauge with the given name and labels to `value`.
+    fn record_gauge(&self, name: &'static str, value: f64, labels: &[(String, String)]);
+
+    /// Adds `value` to the gauge with the given name and labels.
+    fn increment_gauge(&self, name: &'static str, value: f64, labels: &[(String, String)]);
+
+    /// Subtracts `value` from the gauge with the given name and labels.
+    fn decrement_gauge(&self, name: &'static str, value: f64, labels: &[(String, String)]);
+}
+
+/// Emits the metrics of allocation tracing through the `metrics` crate, where the `internal_metrics` source collects
+/// them.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MetricsCrateSink;
+
+impl AllocationMetricSink for MetricsCrateSink {
+    fn record_counter(&self, name: &'static str, value: u64, labels: &[(String, String)]) {
+        counter!(name, value, labels);
+    }
+
+    fn record_gauge(&self, name: &'static str, value: f64, labels: &[(String, String)]) {
+        gauge!(name, value, labels);
+    }
+
+    fn increment_gauge(&self, name: &'static str, value: f64, labels: &[(String, String)]) {
+        increment_gauge!(name, value, labels);
+    }
+
+    fn decrement_gauge(&self, name: &'static str, value: f64, labels: &[(String, String)]) {
+        decrement_gauge!(name, value, labels);
+    }
+}
@@ -0,0 +1,119 @@
+//! Counts of the allocations of each allocation group by size class, for studying how allocations distribute and the
+//! fragmentation they may cause.
+//!
+//! The size classes are coarser than those of jemalloc, so that every group has a fixed, small number of counters. The
+//! upper bounds of the classes grow by a factor of 4, from 16 bytes to 64 KiB, and the last class counts every larger
+//! allocation:
+//!
+//! | Class | Allocation sizes in bytes |
+//! |-------|---------------------------|
+//! | 0     | 0 to 16                   |
+//! | 1     | 17 to 64                  |
+//! | 2     | 65 to 256                 |
+//! | 3     | 257 to 1024               |
+//! | 4     | 1025 to 4096              |
+//! | 5     | 4097 to 16384             |
+//! | 6     | 16385 to 65536            |
+//! | 7     | 65537 and more            |
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use arr_macro::arr;
+
+use super::{AllocationMetricSink, NUM_GROUPS};
+
+/// The number of size classes.
+const SIZE_CLASSES: usize = 8;
+
+/// The upper bound of the first size class, in bytes.
+const SMALLEST_SIZE_CLASS_BYTES: u64 = 16;
+
+/// The number of allocations of each group in each size class since the processor's last emission.
+static GROUP_ALLOCATION_SIZE_CLASSES: [[AtomicU64; SIZE_CLASSES]; NUM_GROUPS] =
+    arr![arr![AtomicU64::new(0); 8]; 128];
+
+/// Gets the size class of an allocation of the given size.
+///
+/// This only counts the bits of the size, without branching on it, as it runs for every allocation.
+#[inline(always)]
+fn size_class(object_size: usize) -> usize {
+    let bits = usize::BITS - object_size.saturating_sub(1).leading_zeros();
+    ((bits.saturating_sub(3) / 2) as usize).min(SIZE_CLASSES - 1)
+}
+
+/// Gets the upper bound of the given size class for its metric label, which is `+Inf` for the last class.
+fn size_class_label(class_idx: usize) -> String {
+    if class_idx == SIZE_CLASSES - 1 {
+        "+Inf".to_string()
+    } else {
+        (SMALLEST_SIZE_CLASS_BYTES << (2 * class_idx)).to_string()
+    }
+}
+
+/// Counts an allocation of the given group in its size class.
+#[inline(always)]
+pub(super) fn record_allocation_size(group_idx: usize, object_size: usize) {
+    GROUP_ALLOCATION_SIZE_CLASSES[group_idx][size_class(object_size)]
+        .fetch_add(1, Ordering::Relaxed);
+}
+
+/// Takes the number of allocations in each size class of the given group since the last call.
+fn take_allocation_size_classes(group_idx: usize) -> [u64; SIZE_CLASSES] {
+    let mut classes = [0; SIZE_CLASSES];
+    for (class, count) in classes
+        .iter_mut()
+        .zip(&GROUP_ALLOCATION_SIZE_CLASSES[group_idx])
+    {
+        *class = count.swap(0, Ordering::Relaxed);
+    }
+    classes
+}
+
+/// Emits the number of allocations of the given group in each size class, labeled with the upper bound of the class in
+/// bytes.
+pub(super) fn emit_allocation_size_classes(
+    metric_sink: &dyn AllocationMetricSink,
+    group_idx: usize,
+    labels: &[(String, String)],
+) {
+    for (class_idx, count) in take_allocation_size_classes(group_idx)
+        .into_iter()
+        .enumerate()
+    {
+        if count == 0 {
+            continue;
+        }
+        let mut labels = labels.to_vec();
+        labels.push(("size_class".to_string(), size_class_label(class_idx)));
+        metric_sink.record_counter("component_allocations_by_size_class_total", count, &labels);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_by_powers_of_four() {
+        assert_eq!(size_class(0), 0);
+        assert_eq!(size_class(16), 0);
+        assert_eq!(size_class(17), 1);
+        assert_eq!(size_class(64), 1);
+        assert_eq!(size_class(65), 2);
+        assert_eq!(size_class(65536), 6);
+        assert_eq!(size_class(65537), 7);
+        assert_eq!(size_class(usize::MAX), 7);
+        assert_eq!(size_class_label(1), "64");
+        assert_eq!(size_class_label(7), "+Inf");
+    }
+
+    #[test]
+    fn takes_recorded_size_classes() {
+        record_allocation_size(90, 8);
+        record_allocation_size(90, 100);
+        record_allocation_size(90, 1 << 20);
+
+        assert_eq!(take_allocation_size_classes(90), [1, 0, 1, 0, 0, 0, 0, 1]);
+        assert_eq!(take_allocation_size_classes(90), [0; SIZE_CLASSES]);
+    }
+}
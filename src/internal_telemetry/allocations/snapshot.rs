@@ -0,0 +1,174 @@
+//! Point-in-time snapshots of the statistics of every allocation group, for audits and reports that must not mix
+//! statistics read at different times.
+//!
+//! Reading the statistics of each group in turn gives a rolling view, in which an allocation made while the groups are
+//! being read can be counted for some groups but not for others. Each thread's statistics are guarded by a sequence
+//! lock instead: the thread increments its generation before and after writing them, so it is odd while a write is in
+//! progress. A snapshot reads every thread's generation before and after reading the statistics, and only keeps the
+//! statistics if no generation changed in between, retrying otherwise.
+
+use std::sync::atomic::{fence, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    group_mem_totals, without_allocation_tracing, AllocationGroupId, GroupDelta, NUM_GROUPS,
+    THREAD_LOCAL_REFS,
+};
+
+/// How many times a snapshot is read again when threads wrote their statistics while it was being read.
+const MAX_CAPTURE_ATTEMPTS: usize = 100;
+
+/// The statistics of an allocation group in an [`AllocationGroupSnapshot`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupSnapshot {
+    /// The raw ID of the allocation group.
+    pub group_id: u8,
+
+    /// The total number of bytes allocated by the allocation group.
+    pub allocated_bytes: u64,
+
+    /// The total number of bytes deallocated by the allocation group.
+    pub deallocated_bytes: u64,
+
+    /// The total number of allocator calls that allocated or reallocated memory for the allocation group.
+    pub allocation_events: u64,
+
+    /// The total number of allocator calls that deallocated memory of the allocation group.
+    pub deallocation_events: u64,
+}
+
+impl GroupSnapshot {
+    /// Gets the number of bytes allocated by the allocation group when the snapshot was captured.
+    pub const fn current_allocated_bytes(&self) -> u64 {
+        self.allocated_bytes.saturating_sub(self.deallocated_bytes)
+    }
+}
+
+/// The statistics of every allocation group that had allocated or deallocated any bytes, at a single point in time.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AllocationGroupSnapshot {
+    /// The statistics of each allocation group, by group ID.
+    pub groups: Vec<GroupSnapshot>,
+
+    /// Whether the statistics were read while no thread was writing its own.
+    ///
+    /// This is only false when threads kept allocating throughout every attempt to capture the snapshot, in which
+    /// case the statistics are the rolling view of the last attempt.
+    pub consistent: bool,
+}
+
+impl AllocationGroupSnapshot {
+    /// Captures the statistics of every allocation group at a single point in time.
+    ///
+    /// This retries for as long as any thread writes its statistics while they are being read, up to a bounded number
+    /// of attempts, so it is meant for occasional audits rather than for every reporting interval.
+    pub fn capture() -> Self {
+        let mut snapshot = Self::default();
+        without_allocation_tracing(|| {
+            let thread_local_refs = THREAD_LOCAL_REFS.lock().unwrap();
+            for _ in 0..MAX_CAPTURE_ATTEMPTS {
+                let generations = thread_local_refs
+                    .iter()
+                    .map(|metrics| metrics.generation.load(Ordering::Acquire))
+                    .collect::<Vec<_>>();
+                let groups = (1..NUM_GROUPS)
+                    .map(|group_idx| (group_idx, group_mem_totals(&thread_local_refs, group_idx)))
+                    .filter(|(_, totals)| !totals.is_empty())
+                    .map(|(group_idx, totals)| GroupSnapshot {
+                        group_id: group_idx as u8,
+                        allocated_bytes: totals.allocations,
+                        deallocated_bytes: totals.deallocations,
+                        allocation_events: totals.allocation_events,
+                        deallocation_events: totals.deallocation_events,
+                    })
+                    .collect();
+                // The statistics must be read before the generations are read again.
+                fence(Ordering::Acquire);
+                let consistent = generations.iter().all(|generation| generation % 2 == 0)
+                    && thread_local_refs
+                        .iter()
+                        .zip(&generations)
+                        .all(|(metrics, generation)| {
+                            metrics.generation.load(Ordering::Relaxed) == *generation
+                        });
+                snapshot = Self { groups, consistent };
+                if consistent {
+                    break;
+                }
+                std::hint::spin_loop();
+            }
+        });
+        snapshot
+    }
+
+    /// Gets the change in the bytes allocated by every allocation group whose allocated bytes changed since the
+    /// `previous` snapshot.
+    ///
+    /// A group that is missing from either snapshot had no bytes allocated in it.
+    pub fn diff(&self, previous: &Self) -> Vec<GroupDelta> {
+        let allocated_bytes = |snapshot: &Self, group_idx: usize| {
+            snapshot
+                .groups
+                .iter()
+                .find(|group| group.group_id as usize == group_idx)
+                .map_or(0, GroupSnapshot::current_allocated_bytes)
+        };
+        (1..NUM_GROUPS)
+            .filter_map(|group_idx| {
+                let current = allocated_bytes(self, group_idx);
+                let previous = allocated_bytes(previous, group_idx);
+                (current != previous).then(|| GroupDelta {
+                    group_id: AllocationGroupId::from_raw(group_idx as u8),
+                    delta_bytes: current as i64 - previous as i64,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal_telemetry::allocations::{tests::test_group_id, MainTracer, Tracer};
+
+    #[test]
+    fn captures_group_statistics() {
+        let group_id = test_group_id(79);
+        MainTracer.trace_allocation(100, group_id);
+        MainTracer.trace_deallocation(30, group_id);
+
+        let previous = AllocationGroupSnapshot::capture();
+        let group = previous
+            .groups
+            .iter()
+            .find(|group| group.group_id == group_id.as_raw())
+            .copied();
+        assert_eq!(
+            group,
+            Some(GroupSnapshot {
+                group_id: group_id.as_raw(),
+                allocated_bytes: 100,
+                deallocated_bytes: 30,
+                allocation_events: 1,
+                deallocation_events: 1,
+            })
+        );
+
+        MainTracer.trace_allocation(50, group_id);
+        let current = AllocationGroupSnapshot::capture();
+        let delta = current
+            .diff(&previous)
+            .into_iter()
+            .find(|delta| delta.group_id == group_id);
+        assert_eq!(
+            delta,
+            Some(GroupDelta {
+                group_id,
+                delta_bytes: 50,
+            })
+        );
+
+        MainTracer.trace_deallocation(120, group_id);
+    }
+}
@@ -0,0 +1,104 @@
+//! Allocation groups derived from the names of spans, for exploratory profiling.
+//!
+//! With the `allocation-tracing-span-groups` feature, every span that has no allocation group attached to it gets the
+//! allocation group of its name when it is entered, so allocations are accounted per span without acquiring any tokens.
+//! This changes what the allocations of such spans are attributed to, which is why it is not meant for production.
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    sync::Mutex,
+};
+
+use once_cell::sync::Lazy;
+
+use super::{register_allocation_group, without_allocation_tracing, AllocationGroupId};
+
+/// The allocation group of each span name, by the hash of the name.
+static SPAN_NAME_GROUPS: Lazy<Mutex<HashMap<u64, SpanNameGroup>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+struct SpanNameGroup {
+    name: &'static str,
+    group_id: AllocationGroupId,
+}
+
+fn span_name_hash(name: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    name.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Gets the allocation group of the given span name, registering it on first use.
+///
+/// Returns `None` if no more allocation groups can be registered, or if the name collides with another span name that
+/// already has an allocation group, rather than merging the allocations of both spans.
+pub(crate) fn span_name_group(name: &'static str) -> Option<AllocationGroupId> {
+    let mut group_id = None;
+    without_allocation_tracing(|| {
+        let mut groups = SPAN_NAME_GROUPS.lock().unwrap();
+        group_id = lookup_or_register(&mut groups, span_name_hash(name), name, || {
+            let registration =
+                register_allocation_group(vec![("span_name".to_string(), name.to_string())]);
+            (!registration.id.is_shared()).then_some(registration.id)
+        });
+    });
+    group_id
+}
+
+fn lookup_or_register(
+    groups: &mut HashMap<u64, SpanNameGroup>,
+    hash: u64,
+    name: &'static str,
+    register: impl FnOnce() -> Option<AllocationGroupId>,
+) -> Option<AllocationGroupId> {
+    if let Some(group) = groups.get(&hash) {
+        if group.name == name {
+            return Some(group.group_id);
+        }
+        warn!(
+            message = "Span name collides with the allocation group of another span name. Its allocations are not attributed to a group of its own.",
+            span_name = name,
+            colliding_span_name = group.name,
+            internal_log_rate_limit = true,
+        );
+        return None;
+    }
+
+    let group_id = register()?;
+    groups.insert(hash, SpanNameGroup { name, group_id });
+    Some(group_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal_telemetry::allocations::tests::test_group_id;
+
+    #[test]
+    fn reuses_group_of_span_name() {
+        let mut groups = HashMap::new();
+        let group_id = test_group_id(108);
+
+        assert_eq!(
+            lookup_or_register(&mut groups, 1, "source", || Some(group_id)),
+            Some(group_id)
+        );
+        assert_eq!(
+            lookup_or_register(&mut groups, 1, "source", || unreachable!()),
+            Some(group_id)
+        );
+    }
+
+    #[test]
+    fn detects_colliding_span_names() {
+        let mut groups = HashMap::new();
+        let group_id = test_group_id(107);
+        lookup_or_register(&mut groups, 1, "source", || Some(group_id));
+
+        assert_eq!(
+            lookup_or_register(&mut groups, 1, "sink", || unreachable!()),
+            None
+        );
+    }
+}
@@ -0,0 +1,117 @@
+//! The bytes allocated by the allocation groups that share the value of a tag, such as all the groups of a component
+//! that spawns groups of its own.
+
+use std::collections::BTreeMap;
+
+use super::GROUP_INFO;
+
+/// The tag that allocation groups are usually aggregated by.
+pub const DEFAULT_AGGREGATION_TAG: &str = "component_id";
+
+/// Sums the bytes allocated by the allocation groups that share each value of the given tag.
+///
+/// `group_tags` and `live_bytes` are indexed by the raw group ID. Groups without the tag are left out.
+fn sum_live_bytes_by_tag(
+    tag: &str,
+    group_tags: &[Vec<(String, String)>],
+    live_bytes: &[u64],
+) -> BTreeMap<String, u64> {
+    let mut aggregates = BTreeMap::new();
+    for (tags, bytes) in group_tags.iter().zip(live_bytes) {
+        if let Some((_, value)) = tags.iter().find(|(key, _)| key == tag) {
+            *aggregates.entry(value.clone()).or_default() += bytes;
+        }
+    }
+    aggregates
+}
+
+/// The sums of the bytes allocated by the allocation groups that share each value of a tag, as last emitted.
+pub(super) struct TagAggregates {
+    tag: String,
+    emitted: BTreeMap<String, u64>,
+}
+
+impl TagAggregates {
+    pub(super) const fn new(tag: String) -> Self {
+        Self {
+            tag,
+            emitted: BTreeMap::new(),
+        }
+    }
+
+    /// The tag that allocation groups are aggregated by.
+    pub(super) fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// Sums the bytes allocated by the allocation groups sharing each value of the tag, returning the sums that changed
+    /// since the last update.
+    ///
+    /// A value that no group has anymore is returned with a sum of zero once, so that its gauge doesn't stay stuck at
+    /// the last sum.
+    pub(super) fn update(&mut self, live_bytes: &[u64]) -> Vec<(String, u64)> {
+        let group_tags = GROUP_INFO
+            .iter()
+            .map(|group| group.lock().unwrap().tags.clone())
+            .collect::<Vec<_>>();
+        self.update_with_tags(&group_tags, live_bytes)
+    }
+
+    fn update_with_tags(
+        &mut self,
+        group_tags: &[Vec<(String, String)>],
+        live_bytes: &[u64],
+    ) -> Vec<(String, u64)> {
+        let aggregates = sum_live_bytes_by_tag(&self.tag, group_tags, live_bytes);
+        let mut changed = self
+            .emitted
+            .keys()
+            .filter(|value| !aggregates.contains_key(*value))
+            .map(|value| (value.clone(), 0))
+            .collect::<Vec<_>>();
+        changed.extend(
+            aggregates
+                .iter()
+                .filter(|(value, bytes)| self.emitted.get(*value) != Some(bytes))
+                .map(|(value, bytes)| (value.clone(), *bytes)),
+        );
+        self.emitted = aggregates;
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn component_id(value: &str) -> Vec<(String, String)> {
+        vec![("component_id".to_string(), value.to_string())]
+    }
+
+    #[test]
+    fn sums_groups_sharing_a_tag_value() {
+        let group_tags = vec![
+            Vec::new(),
+            component_id("in"),
+            component_id("in"),
+            component_id("out"),
+            Vec::new(),
+        ];
+        let mut aggregates = TagAggregates::new(DEFAULT_AGGREGATION_TAG.to_string());
+
+        assert_eq!(
+            aggregates.update_with_tags(&group_tags, &[0, 10, 20, 30, 40]),
+            vec![("in".to_string(), 30), ("out".to_string(), 30)]
+        );
+        // Only the sums that changed are returned again.
+        assert_eq!(
+            aggregates.update_with_tags(&group_tags, &[0, 15, 20, 30, 40]),
+            vec![("in".to_string(), 35)]
+        );
+        // A value that no group has anymore is zeroed.
+        assert_eq!(
+            aggregates.update_with_tags(&group_tags[..3], &[0, 15, 20]),
+            vec![("out".to_string(), 0)]
+        );
+    }
+}
@@ -0,0 +1,230 @@
+//! Allocation groups of the Tokio tasks that allocate outside of any allocation group.
+//!
+//! The allocations made outside of any allocation group are attributed to the root allocation group, which mixes the
+//! allocations of unrelated tasks. With [`AllocationFallback::UseTokioTaskId`], a pool of allocation groups is
+//! registered at initialization instead, and the tracer claims one of them for each task it sees allocating outside of
+//! any allocation group. The mapping from task IDs to these groups is read and claimed from the allocator itself, so it
+//! is a fixed table of atomics: it can neither allocate nor lock.
+//!
+//! Tasks are told apart by an ID of our own, which [`with_task_id`] assigns to the task it runs, rather than by the ID
+//! Tokio gives them, which is only public in builds with `--cfg tokio_unstable`.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+
+use arr_macro::arr;
+
+use super::{
+    component_tags, current_allocated_bytes, register_allocation_group, AllocationGroupId,
+};
+
+/// The number of allocation groups registered for the tasks that allocate outside of any allocation group.
+const TASK_GROUP_SLOTS: usize = 16;
+
+/// The value of a slot's task ID while no task claimed the slot, which [`with_task_id`] never assigns.
+const UNCLAIMED: u64 = 0;
+
+/// What the allocations made outside of any allocation group are attributed to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AllocationFallback {
+    /// The root allocation group.
+    #[default]
+    Root,
+
+    /// An allocation group of the Tokio task making them, or the root allocation group outside of a task.
+    ///
+    /// Each task claims one of a fixed pool of allocation groups, which are tagged with the ID of the task. The group
+    /// of a task is released once it has no allocated bytes left, for another task to claim; a task that allocates
+    /// after its group was released claims a group again. Once every group of the pool is claimed, the allocations of
+    /// other tasks are attributed to the root allocation group. Only the tasks run with [`with_task_id`], as the
+    /// tasks spawned with `spawn_named` are, have an ID; the allocations of other tasks are attributed to the root
+    /// allocation group as well.
+    UseTokioTaskId,
+}
+
+/// A slot of the pool of allocation groups of tasks.
+struct TaskGroupSlot {
+    /// The ID of the task that claimed the slot, or [`UNCLAIMED`].
+    task_id: AtomicU64,
+
+    /// The raw allocation group ID of the slot, or zero if it has no allocation group.
+    group_id: AtomicU8,
+}
+
+impl TaskGroupSlot {
+    const fn new() -> Self {
+        Self {
+            task_id: AtomicU64::new(UNCLAIMED),
+            group_id: AtomicU8::new(0),
+        }
+    }
+
+    fn group_id(&self) -> Option<AllocationGroupId> {
+        match self.group_id.load(Ordering::Relaxed) {
+            0 => None,
+            raw_group_id => Some(AllocationGroupId::from_raw(raw_group_id)),
+        }
+    }
+}
+
+/// Whether the tracer falls back to the allocation groups of tasks, as set at initialization.
+static TASK_FALLBACK: AtomicBool = AtomicBool::new(false);
+
+/// The pool of allocation groups of tasks.
+static TASK_GROUPS: [TaskGroupSlot; TASK_GROUP_SLOTS] = arr![TaskGroupSlot::new(); 16];
+
+/// The ID [`with_task_id`] assigns to the next task it runs.
+static NEXT_TASK_ID: AtomicU64 = AtomicU64::new(UNCLAIMED + 1);
+
+tokio::task_local! {
+    /// The ID of the task that is currently being polled.
+    static TASK_ID: u64;
+}
+
+/// Runs the given future as a task with an ID of its own, which its allocation group of the pool is claimed for.
+pub fn with_task_id<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    TASK_ID.scope(NEXT_TASK_ID.fetch_add(1, Ordering::Relaxed), future)
+}
+
+/// Registers the pool of allocation groups of tasks, if the given fallback uses them.
+pub(super) fn init_task_fallback(fallback: AllocationFallback) {
+    if fallback != AllocationFallback::UseTokioTaskId {
+        return;
+    }
+
+    for (slot_idx, slot) in TASK_GROUPS.iter().enumerate() {
+        let registration = register_allocation_group(component_tags(
+            format!("tokio_task_{}", slot_idx),
+            "tokio_task".to_string(),
+            "task".to_string(),
+        ));
+        if registration.id.is_shared() {
+            break;
+        }
+        slot.group_id
+            .store(registration.id.as_raw(), Ordering::Relaxed);
+    }
+    TASK_FALLBACK.store(true, Ordering::Relaxed);
+}
+
+/// Gets the allocation group of the current Tokio task, claiming one for it if it doesn't have one yet.
+///
+/// This is called by the allocator for allocations made outside of any allocation group, so it only reads and claims
+/// slots with atomics. Returns `None` outside of a task, or if every allocation group of the pool is claimed.
+#[inline(always)]
+pub(super) fn task_fallback_group() -> Option<AllocationGroupId> {
+    if !TASK_FALLBACK.load(Ordering::Relaxed) {
+        return None;
+    }
+    current_task_id().and_then(claim_task_group)
+}
+
+/// Gets the ID of the task that is currently being polled, if it was run with [`with_task_id`].
+fn current_task_id() -> Option<u64> {
+    TASK_ID.try_with(|task_id| *task_id).ok()
+}
+
+/// Gets the allocation group of the slot the given task claimed, or claims a free slot for it.
+fn claim_task_group(task_id: u64) -> Option<AllocationGroupId> {
+    let start = task_id as usize % TASK_GROUP_SLOTS;
+    let probe =
+        || (0..TASK_GROUP_SLOTS).map(|offset| &TASK_GROUPS[(start + offset) % TASK_GROUP_SLOTS]);
+    // Another slot may have been released since the task claimed its slot, so every slot is looked at before claiming
+    // one, to keep the task to a single slot.
+    if let Some(slot) = probe().find(|slot| slot.task_id.load(Ordering::Relaxed) == task_id) {
+        return slot.group_id();
+    }
+    probe()
+        .filter(|slot| slot.group_id().is_some())
+        .find(|slot| {
+            slot.task_id
+                .compare_exchange(UNCLAIMED, task_id, Ordering::Relaxed, Ordering::Relaxed)
+                .map_or_else(|claimed_by| claimed_by == task_id, |_| true)
+        })
+        .and_then(TaskGroupSlot::group_id)
+}
+
+/// Releases the allocation groups of the tasks that have no allocated bytes left, for other tasks to claim.
+///
+/// This is called by the processor on each emission.
+pub(super) fn release_idle_task_groups() {
+    for slot in &TASK_GROUPS {
+        let task_id = slot.task_id.load(Ordering::Relaxed);
+        if task_id == UNCLAIMED {
+            continue;
+        }
+        if let Some(group_id) = slot.group_id() {
+            if current_allocated_bytes(group_id) == 0 {
+                // The task may allocate again in between, in which case those bytes stay attributed to this group.
+                let _ = slot.task_id.compare_exchange(
+                    task_id,
+                    UNCLAIMED,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                );
+            }
+        }
+    }
+}
+
+/// Gets the ID of the task that claimed the allocation group of the given slot, if it is an allocation group of a task.
+pub(super) fn group_task_id(group_idx: usize) -> Option<u64> {
+    TASK_GROUPS
+        .iter()
+        .find(|slot| slot.group_id().and_then(AllocationGroupId::slot_index) == Some(group_idx))
+        .map(|slot| slot.task_id.load(Ordering::Relaxed))
+        .filter(|task_id| *task_id != UNCLAIMED)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal_telemetry::allocations::tests::test_group_id;
+
+    /// Gives a slot of the pool an allocation group for the duration of a test, leaving it unclaimed and without an
+    /// allocation group again when dropped.
+    struct TaskGroupSlotGuard(&'static TaskGroupSlot);
+
+    impl TaskGroupSlotGuard {
+        fn set(slot: &'static TaskGroupSlot, group_id: AllocationGroupId) -> Self {
+            slot.group_id.store(group_id.as_raw(), Ordering::Relaxed);
+            Self(slot)
+        }
+    }
+
+    impl Drop for TaskGroupSlotGuard {
+        fn drop(&mut self) {
+            self.0.task_id.store(UNCLAIMED, Ordering::Relaxed);
+            self.0.group_id.store(0, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn tasks_claim_a_single_group_each() {
+        let _slot = TaskGroupSlotGuard::set(&TASK_GROUPS[TASK_GROUP_SLOTS - 1], test_group_id(73));
+        // Task IDs that start probing at the last slot, the only one with an allocation group in tests.
+        let task_id = u64::MAX - (u64::MAX % TASK_GROUP_SLOTS as u64) - 1;
+
+        assert_eq!(claim_task_group(task_id), Some(test_group_id(73)));
+        assert_eq!(claim_task_group(task_id), Some(test_group_id(73)));
+        assert_eq!(group_task_id(73), Some(task_id));
+        assert_eq!(claim_task_group(task_id - TASK_GROUP_SLOTS as u64), None);
+
+        release_idle_task_groups();
+        assert_eq!(group_task_id(73), None);
+        assert_eq!(
+            claim_task_group(task_id - TASK_GROUP_SLOTS as u64),
+            Some(test_group_id(73))
+        );
+    }
+
+    #[tokio::test]
+    async fn tasks_run_with_an_id_of_their_own() {
+        assert_eq!(current_task_id(), None);
+        let first = with_task_id(async { current_task_id() }).await;
+        let second = with_task_id(async { current_task_id() }).await;
+        assert!(first.is_some());
+        assert_ne!(first, second);
+        assert_ne!(first, Some(UNCLAIMED));
+    }
+}
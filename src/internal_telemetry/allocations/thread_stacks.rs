@@ -0,0 +1,128 @@
+//! An estimate of the memory reserved for the stacks of Vector's threads.
+//!
+//! Thread stacks are mapped by the operating system rather than allocated through the global allocator, so allocation
+//! groups never see them, even though they can make up a large part of the memory of a process with many threads. The
+//! pages of a stack that are actually used can't be known from user mode either, so this only adds up the size each
+//! thread reserves for its stack: an upper bound, which shows how much fewer or smaller threads could save.
+
+use std::{collections::BTreeMap, io, sync::Mutex, thread};
+
+use once_cell::sync::Lazy;
+
+use super::{without_allocation_tracing, AllocationMetricSink};
+
+/// The stack size of threads spawned without one, for both the standard library and Tokio.
+///
+/// The standard library reads `RUST_MIN_STACK` to override it, which is not accounted for.
+pub const DEFAULT_THREAD_STACK_SIZE: usize = 2 * 1024 * 1024;
+
+/// The stack bytes reserved by the running threads of each pool, by the name of the pool.
+static THREAD_STACK_RESERVED_BYTES: Lazy<Mutex<BTreeMap<String, u64>>> =
+    Lazy::new(Default::default);
+
+/// Records that a thread of the given pool started, with a stack of the given size.
+///
+/// This must be called from the thread itself, once, and be paired with [`record_thread_stop`].
+pub fn record_thread_start(pool: &str, stack_size: usize) {
+    without_allocation_tracing(|| {
+        *THREAD_STACK_RESERVED_BYTES
+            .lock()
+            .unwrap()
+            .entry(pool.to_string())
+            .or_default() += stack_size as u64;
+    });
+}
+
+/// Records that a thread of the given pool, which was recorded with [`record_thread_start`], stopped.
+pub fn record_thread_stop(pool: &str, stack_size: usize) {
+    without_allocation_tracing(|| {
+        if let Some(reserved_bytes) = THREAD_STACK_RESERVED_BYTES.lock().unwrap().get_mut(pool) {
+            *reserved_bytes = reserved_bytes.saturating_sub(stack_size as u64);
+        }
+    });
+}
+
+/// Spawns a thread named after its pool, accounting for its stack until it exits.
+///
+/// The stack is [`DEFAULT_THREAD_STACK_SIZE`] bytes unless `stack_size` is set.
+pub fn spawn_accounted_thread<F, T>(
+    pool: &str,
+    stack_size: Option<usize>,
+    f: F,
+) -> io::Result<thread::JoinHandle<T>>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    struct StopGuard {
+        pool: String,
+        stack_size: usize,
+    }
+
+    impl Drop for StopGuard {
+        fn drop(&mut self) {
+            record_thread_stop(&self.pool, self.stack_size);
+        }
+    }
+
+    let stack_size = stack_size.unwrap_or(DEFAULT_THREAD_STACK_SIZE);
+    let pool = pool.to_string();
+    thread::Builder::new()
+        .name(pool.clone())
+        .stack_size(stack_size)
+        .spawn(move || {
+            record_thread_start(&pool, stack_size);
+            // The stop is recorded even if the thread panics.
+            let _guard = StopGuard { pool, stack_size };
+            f()
+        })
+}
+
+/// Emits the stack bytes reserved by the running threads of each pool.
+pub(super) fn emit_thread_stack_reserved_bytes(
+    metric_sink: &dyn AllocationMetricSink,
+    instance_labels: &[(String, String)],
+) {
+    let reserved_bytes = THREAD_STACK_RESERVED_BYTES.lock().unwrap().clone();
+    for (pool, reserved_bytes) in reserved_bytes {
+        let mut labels = instance_labels.to_vec();
+        labels.push(("thread_pool".to_string(), pool));
+        metric_sink.record_gauge(
+            "thread_stack_reserved_bytes",
+            reserved_bytes as f64,
+            &labels,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reserved_bytes(pool: &str) -> Option<u64> {
+        THREAD_STACK_RESERVED_BYTES
+            .lock()
+            .unwrap()
+            .get(pool)
+            .copied()
+    }
+
+    #[test]
+    fn accounts_for_stacks_until_threads_exit() {
+        let pool = "thread-stacks-test";
+        let (started_tx, started_rx) = std::sync::mpsc::channel();
+        let (stop_tx, stop_rx) = std::sync::mpsc::channel::<()>();
+        let handle = spawn_accounted_thread(pool, Some(64 * 1024), move || {
+            started_tx.send(()).unwrap();
+            let _ = stop_rx.recv();
+        })
+        .unwrap();
+
+        started_rx.recv().unwrap();
+        assert_eq!(reserved_bytes(pool), Some(64 * 1024));
+
+        drop(stop_tx);
+        handle.join().unwrap();
+        assert_eq!(reserved_bytes(pool), Some(0));
+    }
+}
@@ -0,0 +1,352 @@
+//! Warnings about allocation groups whose allocated bytes exceed a threshold, and the action taken against them.
+
+use std::time::{Duration, Instant};
+
+use once_cell::sync::OnceCell;
+
+use super::{
+    component_log_fields, group_log_name, AllocationGroupId, GROUP_USAGE_LOG_TARGET, NUM_GROUPS,
+};
+
+/// How long after warning about an allocation group the processor waits before warning about it again.
+const THRESHOLD_WARNING_WINDOW: Duration = Duration::from_secs(60);
+
+/// The default fraction of its threshold that an allocation group must go back under for the action taken against it to
+/// stop.
+pub const DEFAULT_RESUME_RATIO: f64 = 0.9;
+
+/// What happens when an allocation group exceeds its threshold, which acts as its memory budget.
+///
+/// The processor warns about every group that exceeds its threshold, whatever the action. The actions that apply to
+/// events are taken by sources as they send events, so they apply to the allocation groups of sources.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AllocationBudgetExceededAction {
+    /// Only warns about the allocation group.
+    #[default]
+    Log,
+
+    /// Counts every interval the allocation group exceeds its threshold, until it is back under its resume ratio, with
+    /// the `allocation_budget_exceeded_total` counter.
+    EmitMetric,
+
+    /// Holds the events of the allocation group back until its allocated bytes are back under the resume ratio of its
+    /// threshold.
+    ApplyBackPressure,
+
+    /// Drops the events of the allocation group until its allocated bytes are back under the resume ratio of its
+    /// threshold.
+    DropEvents,
+
+    /// Sends a [`MemoryPressureEvent`] along with the next events of the allocation group, each time it exceeds its
+    /// threshold after being back under its resume ratio.
+    ///
+    /// The event goes to the default output of the source, where a transform downstream can act on it, such as by
+    /// shedding load or raising an alert. The events of the group are sent as usual otherwise.
+    ///
+    /// [`MemoryPressureEvent`]: super::MemoryPressureEvent
+    EmitEvent,
+
+    /// Drops the events of the allocation group, logging an error each time, in debug builds only. Release builds only
+    /// warn about the group.
+    PanicInDebug,
+}
+
+/// The number of allocated bytes above which the processor warns about an allocation group.
+#[derive(Clone, Debug)]
+pub struct AllocationThresholds {
+    /// The threshold of every allocation group not matched by any override.
+    pub default_bytes: Option<u64>,
+
+    /// The action taken against allocation groups that exceed their threshold, unless their override sets another.
+    pub action: AllocationBudgetExceededAction,
+
+    /// Thresholds of specific allocation groups, of which the first matching one applies.
+    pub overrides: Vec<AllocationThresholdOverride>,
+
+    /// The fraction of its threshold that an allocation group must go back under for the action taken against it to
+    /// stop, usually [`DEFAULT_RESUME_RATIO`].
+    ///
+    /// The action is taken once the group exceeds its threshold, but only stops once the group allocates fewer bytes
+    /// than this fraction of it. Without this gap, a group applying back pressure around its threshold would flap
+    /// between pausing and resuming its intake on every interval, as the few events let through push it back over.
+    /// It is clamped between `0` and `1`, where `1` stops the action as soon as the group is within its threshold.
+    pub resume_ratio: f64,
+}
+
+impl Default for AllocationThresholds {
+    fn default() -> Self {
+        Self {
+            default_bytes: None,
+            action: AllocationBudgetExceededAction::default(),
+            overrides: Vec::new(),
+            resume_ratio: DEFAULT_RESUME_RATIO,
+        }
+    }
+}
+
+/// The threshold of the allocation groups that have all of the given tags.
+#[derive(Clone, Debug)]
+pub struct AllocationThresholdOverride {
+    /// The tags an allocation group must have, such as `("component_id", "my_source")`.
+    pub tags: Vec<(String, String)>,
+
+    /// The number of allocated bytes above which the processor warns about the allocation group.
+    pub threshold_bytes: u64,
+
+    /// The action taken against the allocation group when it exceeds its threshold, instead of the default one.
+    pub action: Option<AllocationBudgetExceededAction>,
+}
+
+impl AllocationThresholds {
+    fn threshold_bytes(&self, tags: &[(String, String)]) -> Option<u64> {
+        self.budget(tags)
+            .map(|(threshold_bytes, _)| threshold_bytes)
+    }
+
+    /// Gets the threshold and the action of the allocation group with the given tags, if it has a threshold.
+    fn budget(&self, tags: &[(String, String)]) -> Option<(u64, AllocationBudgetExceededAction)> {
+        self.overrides
+            .iter()
+            .find(|threshold| threshold.tags.iter().all(|tag| tags.contains(tag)))
+            .map(|threshold| {
+                (
+                    threshold.threshold_bytes,
+                    threshold.action.unwrap_or(self.action),
+                )
+            })
+            .or_else(|| {
+                self.default_bytes
+                    .map(|threshold_bytes| (threshold_bytes, self.action))
+            })
+    }
+
+    fn memory_pressure_ratio(
+        &self,
+        tags: &[(String, String)],
+        allocated_bytes: u64,
+    ) -> Option<f64> {
+        self.threshold_bytes(tags)
+            .map(|threshold_bytes| allocated_bytes as f64 / threshold_bytes.max(1) as f64)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.default_bytes.is_none() && self.overrides.is_empty()
+    }
+}
+
+/// The thresholds of the allocation groups, set at initialization, for reading them outside of the processor.
+static THRESHOLDS: OnceCell<AllocationThresholds> = OnceCell::new();
+
+/// Sets the thresholds of the allocation groups that [`memory_pressure_ratio`] reads.
+///
+/// Only the first call has any effect.
+pub(super) fn set_thresholds(thresholds: AllocationThresholds) {
+    let _ = THRESHOLDS.set(thresholds);
+}
+
+/// Gets the fraction of its threshold that the allocation group with the given tags allocates, if it has a threshold.
+///
+/// This is `0.0` when the group allocates nothing and `1.0` at its threshold, and keeps growing past it.
+pub(super) fn memory_pressure_ratio(
+    tags: &[(String, String)],
+    allocated_bytes: u64,
+) -> Option<f64> {
+    THRESHOLDS
+        .get()
+        .and_then(|thresholds| thresholds.memory_pressure_ratio(tags, allocated_bytes))
+}
+
+/// Checks allocation groups against their thresholds, warning at most once per window for each group.
+pub(super) struct ThresholdMonitor {
+    thresholds: AllocationThresholds,
+    last_warnings: [Option<Instant>; NUM_GROUPS],
+    /// Whether the action of each allocation group is taken, until the group goes back under its resume ratio.
+    enforced: [bool; NUM_GROUPS],
+}
+
+impl ThresholdMonitor {
+    pub(super) const fn new(thresholds: AllocationThresholds) -> Self {
+        Self {
+            thresholds,
+            last_warnings: [None; NUM_GROUPS],
+            enforced: [false; NUM_GROUPS],
+        }
+    }
+
+    /// Whether any allocation group has a threshold, so that checking can be skipped otherwise.
+    pub(super) fn is_enabled(&self) -> bool {
+        !self.thresholds.is_empty()
+    }
+
+    /// Gets the threshold of the allocation group with the given tags, if it has one.
+    pub(super) fn threshold_bytes(&self, tags: &[(String, String)]) -> Option<u64> {
+        self.thresholds.threshold_bytes(tags)
+    }
+
+    /// Gets the action to take against an allocation group, if its allocated bytes exceeded its threshold and have not
+    /// gone back under its resume ratio since.
+    pub(super) fn exceeded_action(
+        &mut self,
+        group_idx: usize,
+        tags: &[(String, String)],
+        allocated_bytes: u64,
+    ) -> Option<AllocationBudgetExceededAction> {
+        let (threshold_bytes, action) = match self.thresholds.budget(tags) {
+            Some(budget) => budget,
+            None => {
+                self.enforced[group_idx] = false;
+                return None;
+            }
+        };
+        let limit_bytes = if self.enforced[group_idx] {
+            let resume_ratio = self.thresholds.resume_ratio.clamp(0.0, 1.0);
+            (threshold_bytes as f64 * resume_ratio) as u64
+        } else {
+            threshold_bytes
+        };
+        self.enforced[group_idx] = allocated_bytes > limit_bytes;
+        self.enforced[group_idx].then_some(action)
+    }
+
+    /// Checks the allocated bytes of an allocation group, returning whether a warning was emitted.
+    pub(super) fn check(
+        &mut self,
+        group_idx: usize,
+        tags: &[(String, String)],
+        allocated_bytes: u64,
+        now: Instant,
+    ) -> bool {
+        let threshold_bytes = match self.thresholds.threshold_bytes(tags) {
+            Some(threshold_bytes) if allocated_bytes > threshold_bytes => threshold_bytes,
+            _ => return false,
+        };
+        let warned_recently = self.last_warnings[group_idx].map_or(false, |last| {
+            now.saturating_duration_since(last) < THRESHOLD_WARNING_WINDOW
+        });
+        if warned_recently {
+            return false;
+        }
+
+        self.last_warnings[group_idx] = Some(now);
+        let [component_id, component_type, component_kind] = component_log_fields(tags);
+        warn!(
+            target: GROUP_USAGE_LOG_TARGET,
+            message = "Allocation group exceeds its allocated bytes threshold.",
+            group = %group_log_name(AllocationGroupId::from_raw(group_idx as u8)),
+            group_id = group_idx,
+            component_id,
+            component_type,
+            component_kind,
+            allocated_bytes,
+            threshold_bytes,
+        );
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(component_id: &str) -> Vec<(String, String)> {
+        vec![("component_id".to_string(), component_id.to_string())]
+    }
+
+    #[test]
+    fn warns_once_per_window() {
+        let mut monitor = ThresholdMonitor::new(AllocationThresholds {
+            default_bytes: Some(100),
+            ..Default::default()
+        });
+        let start = Instant::now();
+
+        assert!(!monitor.check(2, &tags("in"), 100, start));
+        assert!(monitor.check(2, &tags("in"), 101, start));
+        assert!(!monitor.check(2, &tags("in"), 200, start + Duration::from_secs(1)));
+        assert!(monitor.check(3, &tags("out"), 200, start + Duration::from_secs(1)));
+        assert!(monitor.check(2, &tags("in"), 200, start + THRESHOLD_WARNING_WINDOW));
+    }
+
+    #[test]
+    fn memory_pressure_is_relative_to_the_threshold() {
+        let thresholds = AllocationThresholds {
+            overrides: vec![AllocationThresholdOverride {
+                tags: tags("in"),
+                threshold_bytes: 1000,
+                action: None,
+            }],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            thresholds.memory_pressure_ratio(&tags("in"), 250),
+            Some(0.25)
+        );
+        assert_eq!(
+            thresholds.memory_pressure_ratio(&tags("in"), 1500),
+            Some(1.5)
+        );
+        assert_eq!(thresholds.memory_pressure_ratio(&tags("out"), 250), None);
+    }
+
+    #[test]
+    fn overrides_apply_by_tags() {
+        let mut monitor = ThresholdMonitor::new(AllocationThresholds {
+            default_bytes: Some(100),
+            overrides: vec![AllocationThresholdOverride {
+                tags: tags("in"),
+                threshold_bytes: 1000,
+                action: None,
+            }],
+            ..Default::default()
+        });
+        let start = Instant::now();
+
+        assert!(!monitor.check(2, &tags("in"), 500, start));
+        assert!(monitor.check(3, &tags("out"), 500, start));
+    }
+
+    #[test]
+    fn overrides_set_their_own_action() {
+        let mut monitor = ThresholdMonitor::new(AllocationThresholds {
+            default_bytes: Some(100),
+            action: AllocationBudgetExceededAction::EmitMetric,
+            overrides: vec![AllocationThresholdOverride {
+                tags: tags("in"),
+                threshold_bytes: 1000,
+                action: Some(AllocationBudgetExceededAction::DropEvents),
+            }],
+            ..Default::default()
+        });
+
+        assert_eq!(monitor.exceeded_action(2, &tags("in"), 500), None);
+        assert_eq!(
+            monitor.exceeded_action(2, &tags("in"), 1001),
+            Some(AllocationBudgetExceededAction::DropEvents)
+        );
+        assert_eq!(
+            monitor.exceeded_action(3, &tags("out"), 101),
+            Some(AllocationBudgetExceededAction::EmitMetric)
+        );
+    }
+
+    #[test]
+    fn actions_stop_under_the_resume_ratio() {
+        let mut monitor = ThresholdMonitor::new(AllocationThresholds {
+            default_bytes: Some(1000),
+            action: AllocationBudgetExceededAction::ApplyBackPressure,
+            resume_ratio: 0.8,
+            ..Default::default()
+        });
+        let pausing = Some(AllocationBudgetExceededAction::ApplyBackPressure);
+
+        assert_eq!(monitor.exceeded_action(2, &tags("in"), 900), None);
+        assert_eq!(monitor.exceeded_action(2, &tags("in"), 1001), pausing);
+        // Going back within the threshold is not enough to resume.
+        assert_eq!(monitor.exceeded_action(2, &tags("in"), 900), pausing);
+        assert_eq!(monitor.exceeded_action(2, &tags("in"), 800), None);
+        // Once resumed, the group only pauses again over the threshold.
+        assert_eq!(monitor.exceeded_action(2, &tags("in"), 900), None);
+        assert_eq!(monitor.exceeded_action(2, &tags("in"), 1200), pausing);
+    }
+}
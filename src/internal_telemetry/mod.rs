@@ -6,3 +6,18 @@ pub mod allocations;
 pub const fn is_allocation_tracking_enabled() -> bool {
     cfg!(feature = "allocation-tracing")
 }
+
+/// Whether allocations are currently being tracked, as opposed to only being built with support for tracking them.
+///
+/// This reads the flag that enabling allocation tracing sets at runtime, so subsystems can skip work, such as expensive
+/// profiling, that is only useful while allocations are tracked.
+pub fn is_allocation_tracing_enabled() -> bool {
+    #[cfg(feature = "allocation-tracing")]
+    {
+        allocations::TRACK_ALLOCATIONS.load(std::sync::atomic::Ordering::Relaxed)
+    }
+    #[cfg(not(feature = "allocation-tracing"))]
+    {
+        false
+    }
+}
@@ -189,6 +189,9 @@ pub(crate) fn spawn_named<T>(
 where
     T: Send + 'static,
 {
+    #[cfg(feature = "allocation-tracing")]
+    let task = internal_telemetry::allocations::with_task_id(task);
+
     #[cfg(tokio_unstable)]
     return tokio::task::Builder::new().name(_name).spawn(task);
 
@@ -6,12 +6,15 @@ use vector::app::Application;
 #[cfg(unix)]
 fn main() {
     #[cfg(feature = "allocation-tracing")]
-    {
+    let allocation_processor = {
+        use crate::vector::config::internal_telemetry::AllocationTracingConfigFromFile;
         use crate::vector::internal_telemetry::allocations::{
-            init_allocation_tracing, REPORTING_INTERVAL_MS, TRACK_ALLOCATIONS,
+            init_allocation_tracing, set_allocation_tracing_config_overrides,
+            AllocationGroupProxySource, AllocationProxyConfig, AllocationTracingConfig,
+            REPORTING_INTERVAL_MS, TRACK_ALLOCATIONS,
         };
         use std::sync::atomic::Ordering;
-        let opts = vector::cli::Opts::get_matches()
+        let mut opts = vector::cli::Opts::get_matches()
             .map_err(|error| {
                 // Printing to stdout/err can itself fail; ignore it.
                 let _ = error.print();
@@ -21,21 +24,97 @@ fn main() {
                 std::process::exit(code);
             });
         let allocation_tracing = opts.root.allocation_tracing;
-        REPORTING_INTERVAL_MS.store(
-            opts.root.allocation_tracing_reporting_interval_ms,
-            Ordering::Relaxed,
-        );
+        let allocation_tracing_namespace = opts.root.allocation_tracing_namespace.take();
+        let validate_allocation_tracking = opts.root.validate_allocation_tracking;
+        let allocation_tracing_dry_run = opts.root.allocation_tracing_dry_run;
+        let allocation_tracing_exemplars = opts.root.allocation_tracing_exemplars;
+        let allocation_tracing_max_overhead_bytes = opts.root.allocation_tracing_max_overhead_bytes;
+        let allocation_tracing_threshold_bytes = opts.root.allocation_tracing_threshold_bytes;
+        let allocation_tracing_metric_prefix = opts.root.allocation_tracing_metric_prefix.take();
+        let allocation_tracing_proxy = opts
+            .root
+            .allocation_tracing_proxy_socket
+            .take()
+            .zip(opts.root.allocation_tracing_proxy_token_file.take());
+        let allocation_tracing_proxy_max_groups = opts.root.allocation_tracing_proxy_max_groups;
+        #[cfg(feature = "allocation-tracing-sigusr1")]
+        let allocation_tracing_heap_summary = opts
+            .root
+            .allocation_tracing_heap_summary_on_sigusr1
+            .then(|| {
+                use crate::vector::internal_telemetry::allocations::HeapSummaryOutput;
+                opts.root
+                    .allocation_tracing_heap_summary_path
+                    .take()
+                    .map_or(HeapSummaryOutput::Log, HeapSummaryOutput::File)
+            });
+        let allocation_tracing_reporting_interval_ms =
+            opts.root.allocation_tracing_reporting_interval_ms;
+        if let Some(reporting_interval_ms) = allocation_tracing_reporting_interval_ms {
+            REPORTING_INTERVAL_MS.store(reporting_interval_ms, Ordering::Relaxed);
+        }
+        // The options set on the command line take precedence over those of the configuration,
+        // which is only loaded later on.
+        set_allocation_tracing_config_overrides(AllocationTracingConfigFromFile {
+            reporting_interval_ms: allocation_tracing_reporting_interval_ms,
+            max_overhead_bytes: allocation_tracing_max_overhead_bytes,
+            ..Default::default()
+        });
         drop(opts);
         // At this point, we make the following assumption:
         // The heap does not contain any allocations that have a shorter lifetime than the program.
         if allocation_tracing {
-            // Start tracking allocations
-            TRACK_ALLOCATIONS.store(true, Ordering::Relaxed);
-            init_allocation_tracing();
+            let mut allocation_tracing_config = AllocationTracingConfig::default();
+            if let Some(namespace) = allocation_tracing_namespace {
+                allocation_tracing_config.namespace = namespace;
+            }
+            if let Some(metric_prefix) = allocation_tracing_metric_prefix {
+                allocation_tracing_config.metric_prefix = metric_prefix;
+            }
+            allocation_tracing_config.validate = validate_allocation_tracking;
+            allocation_tracing_config.dry_run = allocation_tracing_dry_run;
+            allocation_tracing_config.exemplars = allocation_tracing_exemplars;
+            allocation_tracing_config.max_overhead_bytes = allocation_tracing_max_overhead_bytes;
+            allocation_tracing_config.thresholds.default_bytes = allocation_tracing_threshold_bytes;
+            #[cfg(feature = "allocation-tracing-sigusr1")]
+            {
+                allocation_tracing_config.heap_summary_on_sigusr1 = allocation_tracing_heap_summary;
+            }
+            // The proxy is bound now, so that none of the memory allocated for it before tracking
+            // allocations is freed afterwards.
+            if let Some((socket_path, token_path)) = allocation_tracing_proxy {
+                let proxy_config = AllocationProxyConfig {
+                    socket_path,
+                    token_path,
+                    max_groups: allocation_tracing_proxy_max_groups,
+                };
+                match AllocationGroupProxySource::bind(&proxy_config) {
+                    Ok(proxy) => allocation_tracing_config.proxy = Some(proxy),
+                    Err(error) => {
+                        eprintln!("Failed to bind allocation tracing proxy: {}", error);
+                        std::process::exit(exitcode::IOERR);
+                    }
+                }
+            }
+            // Start tracking allocations, unless only the allocation groups are checked.
+            if !allocation_tracing_dry_run {
+                TRACK_ALLOCATIONS.store(true, Ordering::Relaxed);
+            }
+            Some(init_allocation_tracing(allocation_tracing_config))
+        } else {
+            None
         }
-    }
+    };
 
     Application::run();
+
+    // Every component has stopped, so the statistics of their last allocations can be flushed.
+    #[cfg(feature = "allocation-tracing")]
+    {
+        if let Some(allocation_processor) = allocation_processor {
+            allocation_processor.shutdown();
+        }
+    }
 }
 
 #[cfg(windows)]
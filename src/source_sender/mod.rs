@@ -6,6 +6,8 @@ use futures::{Stream, StreamExt};
 use metrics::{register_histogram, Histogram};
 use value::Value;
 use vector_buffers::topology::channel::{self, LimitedReceiver, LimitedSender};
+#[cfg(feature = "allocation-tracing")]
+use vector_core::event::LogEvent;
 #[cfg(test)]
 use vector_core::event::{into_event_stream, EventStatus};
 use vector_core::{
@@ -237,6 +239,17 @@ impl fmt::Debug for Inner {
     }
 }
 
+/// Waits until the source may send events within its allocation budget, returning whether they should be sent. The
+/// events are counted as dropped otherwise.
+#[cfg(feature = "allocation-tracing")]
+async fn admit_within_allocation_budget(count: usize) -> bool {
+    let admitted = crate::internal_telemetry::allocations::admit_events_within_budget().await;
+    if !admitted {
+        emit!(crate::internal_events::AllocationBudgetEventsDropped { count });
+    }
+    admitted
+}
+
 impl Inner {
     fn new_with_buffer(
         n: usize,
@@ -258,6 +271,13 @@ impl Inner {
     }
 
     async fn send(&mut self, events: EventArray) -> Result<(), ClosedError> {
+        #[cfg(feature = "allocation-tracing")]
+        if crate::internal_telemetry::allocations::any_budget_enforced() {
+            if !admit_within_allocation_budget(events.len()).await {
+                return Ok(());
+            }
+            self.send_memory_pressure_event().await?;
+        }
         let reference = Utc::now().timestamp_millis();
         events
             .iter_events()
@@ -273,6 +293,23 @@ impl Inner {
         self.send(event.into()).await
     }
 
+    /// Sends the memory pressure event of the source's allocation group ahead of its events, if it has one to send.
+    ///
+    /// The event only goes to the default output, so that it is sent once.
+    #[cfg(feature = "allocation-tracing")]
+    async fn send_memory_pressure_event(&mut self) -> Result<(), ClosedError> {
+        if self.output != DEFAULT_OUTPUT {
+            return Ok(());
+        }
+        if let Some(event) = crate::internal_telemetry::allocations::take_memory_pressure_event() {
+            let events = EventArray::from(LogEvent::from(event));
+            let cbs = CountByteSize(events.len(), events.estimated_json_encoded_size_of());
+            self.inner.send(events).await.map_err(|_| ClosedError)?;
+            self.events_sent.emit(cbs);
+        }
+        Ok(())
+    }
+
     async fn send_event_stream<S, E>(&mut self, events: S) -> Result<(), ClosedError>
     where
         S: Stream<Item = E> + Unpin,
@@ -293,6 +330,13 @@ impl Inner {
         let reference = Utc::now().timestamp_millis();
         let events = events.into_iter().map(Into::into);
         for events in array::events_into_arrays(events, Some(CHUNK_SIZE)) {
+            #[cfg(feature = "allocation-tracing")]
+            if crate::internal_telemetry::allocations::any_budget_enforced() {
+                if !admit_within_allocation_budget(events.len()).await {
+                    continue;
+                }
+                self.send_memory_pressure_event().await?;
+            }
             events
                 .iter_events()
                 .for_each(|event| self.emit_lag_time(event, reference));
@@ -1,7 +1,7 @@
 use crate::internal_telemetry::is_allocation_tracking_enabled;
 use crossterm::{
     cursor::Show,
-    event::{DisableMouseCapture, EnableMouseCapture, KeyCode},
+    event::{DisableMouseCapture, EnableMouseCapture, KeyCode, KeyEvent, KeyModifiers},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     tty::IsTty,
@@ -104,8 +104,25 @@ fn format_metric(total: i64, throughput: i64, human_metrics: bool) -> String {
     }
 }
 
+/// Format allocated bytes, with the change in allocated bytes per second
+#[cfg(feature = "allocation-tracing")]
+fn format_allocated_bytes(total: i64, throughput: i64) -> String {
+    match total {
+        0 => "N/A".to_string(),
+        v => format!(
+            "{} ({}{}/s)",
+            v.human_format_bytes(),
+            if throughput < 0 { "-" } else { "+" },
+            match throughput.abs() {
+                0 => "0".to_string(),
+                t => t.human_format_bytes(),
+            }
+        ),
+    }
+}
+
 const NUM_COLUMNS: usize = if is_allocation_tracking_enabled() {
-    9
+    10
 } else {
     8
 };
@@ -121,6 +138,8 @@ static HEADER: [&str; NUM_COLUMNS] = [
     "Errors",
     #[cfg(feature = "allocation-tracing")]
     "Mem Usage Bytes",
+    #[cfg(feature = "allocation-tracing")]
+    "Peak Mem Bytes",
 ];
 
 struct Widgets<'a> {
@@ -182,9 +201,17 @@ impl<'a> Widgets<'a> {
             .map(|s| Cell::from(*s).style(Style::default().add_modifier(Modifier::BOLD)))
             .collect::<Vec<_>>();
 
+        #[allow(unused_mut)]
+        let mut components = state.components.values().collect::<Vec<_>>();
+        #[cfg(feature = "allocation-tracing")]
+        if self.opts.sort_by_allocated_bytes {
+            // The sort is stable, so components with the same allocated bytes remain sorted by ID.
+            components.sort_by_key(|r| std::cmp::Reverse(r.allocated_bytes));
+        }
+
         // Data columns
         let mut items = Vec::new();
-        for (_, r) in state.components.iter() {
+        for r in components {
             let mut data = vec![
                 r.key.id().to_string(),
                 (!r.has_displayable_outputs())
@@ -217,7 +244,9 @@ impl<'a> Widgets<'a> {
                     r.errors.thousands_format()
                 },
                 #[cfg(feature = "allocation-tracing")]
-                r.allocated_bytes.human_format(),
+                format_allocated_bytes(r.allocated_bytes, r.allocated_bytes_throughput_sec),
+                #[cfg(feature = "allocation-tracing")]
+                r.allocated_bytes_peak.human_format_bytes(),
             ];
 
             data.extend_from_slice(&formatted_metrics);
@@ -248,15 +277,16 @@ impl<'a> Widgets<'a> {
             .column_spacing(2)
             .widths(if is_allocation_tracking_enabled() {
                 &[
-                    Constraint::Percentage(15), // ID
-                    Constraint::Percentage(6),  // Output
-                    Constraint::Percentage(8),  // Kind
-                    Constraint::Percentage(10), // Type
+                    Constraint::Percentage(13), // ID
+                    Constraint::Percentage(5),  // Output
+                    Constraint::Percentage(6),  // Kind
+                    Constraint::Percentage(8),  // Type
                     Constraint::Percentage(10), // Events In
                     Constraint::Percentage(10), // Events Out
                     Constraint::Percentage(10), // Bytes
-                    Constraint::Percentage(5),  // Errors
-                    Constraint::Percentage(16), // Allocated Bytes
+                    Constraint::Percentage(4),  // Errors
+                    Constraint::Percentage(14), // Allocated Bytes
+                    Constraint::Percentage(10), // Peak Allocated Bytes
                 ]
             } else {
                 &[
@@ -285,7 +315,7 @@ impl<'a> Widgets<'a> {
 
     /// Renders a box showing instructions on how to exit from `vector top`.
     fn quit_box<B: Backend>(&self, f: &mut Frame<B>, area: Rect) {
-        let text = vec![Spans::from("To quit, press ESC or 'q'")];
+        let text = vec![Spans::from("To quit, press ESC, 'q' or Ctrl-C")];
 
         let block = Block::default()
             .borders(Borders::ALL)
@@ -318,6 +348,16 @@ impl<'a> Widgets<'a> {
     }
 }
 
+/// Determine if a key press exits the dashboard. The terminal is in raw mode, so Ctrl-C is
+/// received as a key press rather than as a signal.
+fn is_quit_key(k: KeyEvent) -> bool {
+    match k.code {
+        KeyCode::Esc | KeyCode::Char('q') => true,
+        KeyCode::Char('c') => k.modifiers.contains(KeyModifiers::CONTROL),
+        _ => false,
+    }
+}
+
 /// Determine if the terminal is a TTY
 pub fn is_tty() -> bool {
     stdout().is_tty()
@@ -360,7 +400,7 @@ pub async fn init_dashboard<'a>(
                 terminal.draw(|f| widgets.draw(f, state))?;
             },
             k = key_press_rx.recv() => {
-                if let KeyCode::Esc | KeyCode::Char('q') = k.unwrap() {
+                if is_quit_key(k.unwrap()) {
                     let _ = key_press_kill_tx.send(());
                     break
                 }
@@ -478,4 +518,35 @@ mod tests {
         assert_eq!((N * (N * (N * N))).human_format_bytes(), "1.00 TiB");
         assert_eq!((N * (N * (N * (N * N)))).human_format_bytes(), "1.00 PiB");
     }
+
+    #[test]
+    #[cfg(feature = "allocation-tracing")]
+    /// Should format allocated bytes with their signed rate of change
+    fn format_allocated_bytes_rate() {
+        assert_eq!(format_allocated_bytes(0, 0), "N/A");
+        assert_eq!(format_allocated_bytes(2048, 0), "2.00 KiB (+0/s)");
+        assert_eq!(format_allocated_bytes(2048, 1024), "2.00 KiB (+1.00 KiB/s)");
+        assert_eq!(
+            format_allocated_bytes(2048, -1024),
+            "2.00 KiB (-1.00 KiB/s)"
+        );
+    }
+
+    #[test]
+    /// Should quit on ESC, 'q' and Ctrl-C only
+    fn quit_keys() {
+        assert!(is_quit_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE)));
+        assert!(is_quit_key(KeyEvent::new(
+            KeyCode::Char('q'),
+            KeyModifiers::NONE
+        )));
+        assert!(is_quit_key(KeyEvent::new(
+            KeyCode::Char('c'),
+            KeyModifiers::CONTROL
+        )));
+        assert!(!is_quit_key(KeyEvent::new(
+            KeyCode::Char('c'),
+            KeyModifiers::NONE
+        )));
+    }
 }
@@ -1,10 +1,10 @@
-use crossterm::event::{Event, EventStream, KeyCode};
+use crossterm::event::{Event, EventStream, KeyEvent};
 use futures::StreamExt;
 use tokio::sync::{mpsc, oneshot};
 
 /// Capture keyboard input, and send it upstream via a channel. This is used for interaction
 /// with the dashboard, and exiting from `vector top`.
-pub fn capture_key_press() -> (mpsc::UnboundedReceiver<KeyCode>, oneshot::Sender<()>) {
+pub fn capture_key_press() -> (mpsc::UnboundedReceiver<KeyEvent>, oneshot::Sender<()>) {
     let (tx, rx) = mpsc::unbounded_channel();
     let (kill_tx, mut kill_rx) = oneshot::channel();
 
@@ -17,7 +17,7 @@ pub fn capture_key_press() -> (mpsc::UnboundedReceiver<KeyCode>, oneshot::Sender
                 _ = &mut kill_rx => return,
                 Some(Ok(event)) = events.next() => {
                      if let Event::Key(k) = event {
-                        let _ = tx.clone().send(k.code);
+                        let _ = tx.clone().send(k);
                     };
                 }
             }
@@ -37,6 +37,10 @@ async fn component_added(client: Arc<SubscriptionClient>, tx: state::EventTx) {
                     processed_bytes_throughput_sec: 0,
                     #[cfg(feature = "allocation-tracing")]
                     allocated_bytes: 0,
+                    #[cfg(feature = "allocation-tracing")]
+                    allocated_bytes_peak: 0,
+                    #[cfg(feature = "allocation-tracing")]
+                    allocated_bytes_throughput_sec: 0,
                     errors: 0,
                 }))
                 .await;
@@ -56,6 +60,7 @@ async fn allocated_bytes(client: Arc<SubscriptionClient>, tx: state::EventTx, in
             let c = d.component_allocated_bytes;
             let _ = tx
                 .send(state::EventType::AllocatedBytes(
+                    interval,
                     c.into_iter()
                         .map(|c| {
                             (
@@ -352,6 +357,10 @@ pub async fn init_components(client: &Client) -> Result<state::State, ()> {
                         processed_bytes_throughput_sec: 0,
                         #[cfg(feature = "allocation-tracing")]
                         allocated_bytes: 0,
+                        #[cfg(feature = "allocation-tracing")]
+                        allocated_bytes_peak: 0,
+                        #[cfg(feature = "allocation-tracing")]
+                        allocated_bytes_throughput_sec: 0,
                         errors: 0,
                     },
                 ))
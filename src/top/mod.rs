@@ -26,4 +26,10 @@ pub struct Opts {
     /// Whether to reconnect if the underlying Vector API connection drops. By default, top will attempt to reconnect if the connection drops.
     #[arg(short, long)]
     no_reconnect: bool,
+
+    /// Sort components by the bytes they currently have allocated, largest first, instead of by their ID. Requires
+    /// allocation tracing to be enabled in the Vector instance
+    #[cfg(feature = "allocation-tracing")]
+    #[arg(short = 'm', long)]
+    sort_by_allocated_bytes: bool,
 }
@@ -32,8 +32,9 @@ pub enum EventType {
     /// Interval + identified metric
     ProcessedBytesThroughputs(i64, Vec<IdentifiedMetric>),
     ErrorsTotals(Vec<IdentifiedMetric>),
+    /// Interval in ms + identified metric
     #[cfg(feature = "allocation-tracing")]
-    AllocatedBytes(Vec<IdentifiedMetric>),
+    AllocatedBytes(i64, Vec<IdentifiedMetric>),
     ComponentAdded(ComponentRow),
     ComponentRemoved(ComponentKey),
     ConnectionUpdated(ConnectionStatus),
@@ -122,6 +123,11 @@ pub struct ComponentRow {
     pub sent_events_throughput_sec: i64,
     #[cfg(feature = "allocation-tracing")]
     pub allocated_bytes: i64,
+    /// The most bytes the component had allocated since the dashboard connected.
+    #[cfg(feature = "allocation-tracing")]
+    pub allocated_bytes_peak: i64,
+    #[cfg(feature = "allocation-tracing")]
+    pub allocated_bytes_throughput_sec: i64,
     pub errors: i64,
 }
 
@@ -213,10 +219,17 @@ pub async fn updater(mut event_rx: EventRx) -> StateRx {
                     }
                 }
                 #[cfg(feature = "allocation-tracing")]
-                EventType::AllocatedBytes(rows) => {
+                EventType::AllocatedBytes(interval, rows) => {
                     for (key, v) in rows {
                         if let Some(r) = state.components.get_mut(&key) {
+                            // The first reading has nothing to compare against.
+                            r.allocated_bytes_throughput_sec = if r.allocated_bytes_peak == 0 {
+                                0
+                            } else {
+                                ((v - r.allocated_bytes) as f64 * (1000.0 / interval as f64)) as i64
+                            };
                             r.allocated_bytes = v;
+                            r.allocated_bytes_peak = r.allocated_bytes_peak.max(v);
                         }
                     }
                 }
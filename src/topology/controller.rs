@@ -93,8 +93,16 @@ impl TopologyController {
             }
         }
 
+        #[cfg(feature = "allocation-tracing")]
+        let allocation_tracing_config = new_config.internal_telemetry.allocations;
+
         match self.topology.reload_config_and_respawn(new_config).await {
             Ok(true) => {
+                #[cfg(feature = "allocation-tracing")]
+                crate::internal_telemetry::allocations::apply_allocation_tracing_config(
+                    allocation_tracing_config,
+                );
+
                 #[cfg(feature = "api")]
                 // Pass the new config to the API server.
                 if let Some(ref api_server) = self.api_server {
@@ -486,6 +486,19 @@ impl RunningTopology {
             }
         }
 
+        // The allocation groups of removed components are released once they were shut down.
+        #[cfg(feature = "allocation-tracing")]
+        for key in diff
+            .sources
+            .to_remove
+            .iter()
+            .chain(&diff.transforms.to_remove)
+            .chain(&diff.sinks.to_remove)
+        {
+            crate::internal_telemetry::allocations::AllocationGroupRegistry::global()
+                .deregister(key.id());
+        }
+
         let mut buffers = HashMap::<ComponentKey, BuiltBuffer>::new();
         for key in &diff.sinks.to_change {
             if wait_for_sinks.contains(key) {
@@ -828,7 +841,7 @@ impl RunningTopology {
 
         let task_span = span.or_current();
         #[cfg(feature = "allocation-tracing")]
-        {
+        let group_id = {
             let group_id = crate::internal_telemetry::allocations::acquire_allocation_group_id(
                 task.id().to_string(),
                 "sink".to_string(),
@@ -841,11 +854,20 @@ impl RunningTopology {
                 group_id = group_id.as_raw().to_string(),
                 "Registered new allocation group."
             );
+            crate::internal_telemetry::allocations::AllocationGroupRegistry::global().register(
+                task.id(),
+                std::sync::Arc::new(
+                    crate::internal_telemetry::allocations::AllocationGroupToken::from(group_id),
+                ),
+            );
             group_id.attach_to_span(&task_span);
-        }
+            group_id
+        };
 
         let task_name = format!(">> {} ({})", task.typetag(), task.id());
         let task = handle_errors(task, self.abort_tx.clone()).instrument(task_span);
+        #[cfg(feature = "allocation-tracing")]
+        let task = crate::internal_telemetry::allocations::in_task_allocation_group(group_id, task);
         let spawned = spawn_named(task, task_name.as_ref());
         if let Some(previous) = self.tasks.insert(key.clone(), spawned) {
             drop(previous); // detach and forget
@@ -865,7 +887,7 @@ impl RunningTopology {
 
         let task_span = span.or_current();
         #[cfg(feature = "allocation-tracing")]
-        {
+        let group_id = {
             let group_id = crate::internal_telemetry::allocations::acquire_allocation_group_id(
                 task.id().to_string(),
                 "transform".to_string(),
@@ -878,11 +900,20 @@ impl RunningTopology {
                 group_id = group_id.as_raw().to_string(),
                 "Registered new allocation group."
             );
+            crate::internal_telemetry::allocations::AllocationGroupRegistry::global().register(
+                task.id(),
+                std::sync::Arc::new(
+                    crate::internal_telemetry::allocations::AllocationGroupToken::from(group_id),
+                ),
+            );
             group_id.attach_to_span(&task_span);
-        }
+            group_id
+        };
 
         let task_name = format!(">> {} ({}) >>", task.typetag(), task.id());
         let task = handle_errors(task, self.abort_tx.clone()).instrument(task_span);
+        #[cfg(feature = "allocation-tracing")]
+        let task = crate::internal_telemetry::allocations::in_task_allocation_group(group_id, task);
         let spawned = spawn_named(task, task_name.as_ref());
         if let Some(previous) = self.tasks.insert(key.clone(), spawned) {
             drop(previous); // detach and forget
@@ -902,7 +933,7 @@ impl RunningTopology {
 
         let task_span = span.or_current();
         #[cfg(feature = "allocation-tracing")]
-        {
+        let group_id = {
             let group_id = crate::internal_telemetry::allocations::acquire_allocation_group_id(
                 task.id().to_string(),
                 "source".to_string(),
@@ -916,11 +947,20 @@ impl RunningTopology {
                 group_id = group_id.as_raw().to_string(),
                 "Registered new allocation group."
             );
+            crate::internal_telemetry::allocations::AllocationGroupRegistry::global().register(
+                task.id(),
+                std::sync::Arc::new(
+                    crate::internal_telemetry::allocations::AllocationGroupToken::from(group_id),
+                ),
+            );
             group_id.attach_to_span(&task_span);
-        }
+            group_id
+        };
 
         let task_name = format!("{} ({}) >>", task.typetag(), task.id());
         let task = handle_errors(task, self.abort_tx.clone()).instrument(task_span.clone());
+        #[cfg(feature = "allocation-tracing")]
+        let task = crate::internal_telemetry::allocations::in_task_allocation_group(group_id, task);
         let spawned = spawn_named(task, task_name.as_ref());
         if let Some(previous) = self.tasks.insert(key.clone(), spawned) {
             drop(previous); // detach and forget
@@ -932,6 +972,9 @@ impl RunningTopology {
         // Now spawn the actual source task.
         let source_task = new_pieces.source_tasks.remove(key).unwrap();
         let source_task = handle_errors(source_task, self.abort_tx.clone()).instrument(task_span);
+        #[cfg(feature = "allocation-tracing")]
+        let source_task =
+            crate::internal_telemetry::allocations::in_task_allocation_group(group_id, source_task);
         self.source_tasks
             .insert(key.clone(), spawn_named(source_task, task_name.as_ref()));
     }
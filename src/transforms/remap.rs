@@ -162,6 +162,8 @@ impl RemapConfig {
         let mut functions = vrl_stdlib::all();
         functions.append(&mut enrichment::vrl_functions());
         functions.append(&mut vector_vrl_functions::all());
+        #[cfg(feature = "allocation-tracing")]
+        functions.append(&mut crate::internal_telemetry::allocations::vrl_functions());
 
         let state = TypeState {
             local: Default::default(),
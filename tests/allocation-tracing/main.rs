@@ -0,0 +1,242 @@
+//! Checks that a running topology attributes the memory it allocates to the allocation groups of its components.
+//!
+//! Allocations made before tracking allocations is enabled are not tagged with their group, so they must never be
+//! deallocated once it is. This runs without the test harness, whose own allocations would break that assumption, and
+//! enables tracking allocations before anything else.
+//!
+//! The tests of the allocation processor run here too, rather than in the library's test binary, whose tests read the
+//! statistics that the processor resets on each emission.
+
+use std::{
+    alloc::System,
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+
+use vector::{
+    config::{load_from_str, Format},
+    internal_telemetry::allocations::{
+        allocation_snapshot, get_grouped_tracing_allocator, init_allocation_tracing, next_sample,
+        AllocationGroupRegistry, AllocationTracingConfig, Allocator, REPORTING_INTERVAL_MS,
+        TRACK_ALLOCATIONS,
+    },
+    test_util::{start_topology, temp_file},
+};
+
+#[global_allocator]
+static ALLOC: Allocator<System> = get_grouped_tracing_allocator(System);
+
+const EVENT_COUNT: usize = 100;
+
+fn main() {
+    TRACK_ALLOCATIONS.store(true, Ordering::Relaxed);
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(attributes_allocations_to_components());
+    println!("test attributes_allocations_to_components ... ok");
+    runtime.block_on(frees_component_memory_on_shutdown());
+    println!("test frees_component_memory_on_shutdown ... ok");
+    runtime.block_on(awaits_the_next_sample());
+    println!("test awaits_the_next_sample ... ok");
+    #[cfg(target_os = "linux")]
+    {
+        initializing_twice_spawns_one_processor();
+        println!("test initializing_twice_spawns_one_processor ... ok");
+    }
+}
+
+async fn attributes_allocations_to_components() {
+    let out_path = temp_file();
+    let config = load_from_str(
+        &format!(
+            r#"
+                [sources.in]
+                type = "demo_logs"
+                format = "json"
+                count = {}
+                interval = 0.0
+
+                [transforms.parse]
+                type = "remap"
+                inputs = ["in"]
+                source = ". = parse_json!(.message)"
+
+                [sinks.out]
+                type = "file"
+                inputs = ["parse"]
+                path = "{}"
+                encoding.codec = "json"
+            "#,
+            EVENT_COUNT,
+            out_path.display()
+        ),
+        Format::Toml,
+    )
+    .unwrap();
+    let (topology, _crash) = start_topology(config, false).await;
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let written = tokio::fs::read_to_string(&out_path)
+            .await
+            .map_or(0, |output| output.lines().count());
+        if written == EVENT_COUNT {
+            break;
+        }
+        assert!(
+            Instant::now() < deadline,
+            "only {} of {} events were written",
+            written,
+            EVENT_COUNT
+        );
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let snapshot = allocation_snapshot();
+    for (component_id, component_kind, component_type) in [
+        ("in", "source", "demo_logs"),
+        ("parse", "transform", "remap"),
+        ("out", "sink", "file"),
+    ] {
+        let tag = |key: &str, value: &str| (key.to_string(), value.to_string());
+        let group = snapshot
+            .iter()
+            .find(|group| group.tags.contains(&tag("component_id", component_id)))
+            .unwrap_or_else(|| panic!("no allocation group for component {}", component_id));
+        assert!(
+            group.tags.contains(&tag("component_kind", component_kind)),
+            "{:?}",
+            group.tags
+        );
+        assert!(
+            group.tags.contains(&tag("component_type", component_type)),
+            "{:?}",
+            group.tags
+        );
+        assert!(
+            group.allocated_bytes > 0,
+            "component {} allocated no bytes",
+            component_id
+        );
+    }
+
+    topology.stop().await;
+}
+
+async fn frees_component_memory_on_shutdown() {
+    let config = load_from_str(
+        r#"
+            [sources.generate]
+            type = "demo_logs"
+            format = "json"
+            interval = 0.0
+
+            [transforms.reshape]
+            type = "remap"
+            inputs = ["generate"]
+            source = ". = parse_json!(.message)"
+
+            [sinks.discard]
+            type = "blackhole"
+            inputs = ["reshape"]
+            print_interval_secs = 0
+        "#,
+        Format::Toml,
+    )
+    .unwrap();
+    let (topology, _crash) = start_topology(config, false).await;
+
+    let component_ids = ["generate", "reshape", "discard"];
+    let tokens = component_ids
+        .iter()
+        .map(|component_id| {
+            AllocationGroupRegistry::global()
+                .lookup(component_id)
+                .unwrap_or_else(|| panic!("no allocation group for component {}", component_id))
+        })
+        .collect::<Vec<_>>();
+
+    // Events are constantly in flight while the topology runs, so every component eventually holds some memory.
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let running_bytes = loop {
+        let current_bytes = tokens
+            .iter()
+            .map(|token| token.current_allocated_bytes())
+            .collect::<Vec<_>>();
+        if current_bytes.iter().all(|bytes| *bytes > 0) {
+            break current_bytes.iter().sum::<u64>();
+        }
+        assert!(
+            Instant::now() < deadline,
+            "components {:?} hold {:?} bytes",
+            component_ids,
+            current_bytes
+        );
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    };
+    // Nothing allocates anywhere near a gigabyte for a few events, so more means bytes are attributed to the wrong
+    // group or never freed.
+    assert!(
+        running_bytes < 1024 * 1024 * 1024,
+        "components hold {} bytes",
+        running_bytes
+    );
+
+    topology.stop().await;
+
+    let stopped_bytes = tokens
+        .iter()
+        .map(|token| token.current_allocated_bytes())
+        .sum::<u64>();
+    assert!(
+        stopped_bytes < running_bytes,
+        "components hold {} bytes after shutting down, and {} while running",
+        stopped_bytes,
+        running_bytes
+    );
+}
+
+async fn awaits_the_next_sample() {
+    // The processor emits as soon as it starts, and then once per reporting interval, which is shortened so that the
+    // emission after the sample is requested follows shortly.
+    let reporting_interval_ms = REPORTING_INTERVAL_MS.swap(100, Ordering::Relaxed);
+    init_allocation_tracing(AllocationTracingConfig::default());
+
+    let sample = tokio::time::timeout(Duration::from_secs(10), next_sample()).await;
+    REPORTING_INTERVAL_MS.store(reporting_interval_ms, Ordering::Relaxed);
+    let sample = sample.expect("the processor never emitted");
+    assert!(
+        sample
+            .iter()
+            .any(|stats| stats.current_allocated_bytes() > 0),
+        "no allocation group holds any bytes"
+    );
+}
+
+#[cfg(target_os = "linux")]
+fn initializing_twice_spawns_one_processor() {
+    fn count_processor_threads() -> usize {
+        std::fs::read_dir("/proc/self/task")
+            .unwrap()
+            .filter_map(|task| std::fs::read_to_string(task.unwrap().path().join("comm")).ok())
+            // Thread names are truncated to 15 bytes.
+            .filter(|name| {
+                let name = name.trim_end();
+                !name.is_empty() && "vector-alloc-processor".starts_with(name)
+            })
+            .count()
+    }
+
+    init_allocation_tracing(AllocationTracingConfig::default());
+    init_allocation_tracing(AllocationTracingConfig::default());
+
+    // The processor thread names itself once it starts running.
+    for _ in 0..100 {
+        if count_processor_threads() > 0 {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(count_processor_threads(), 1);
+}
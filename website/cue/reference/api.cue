@@ -82,6 +82,43 @@ api: {
 				}
 			}
 		}
+		"/internal/allocations/{group_id}/history": {
+			GET: {
+				description: """
+					The bytes currently allocated by the given allocation
+					group, as read at each of its last 60 reporting
+					intervals, oldest first. Only available when Vector
+					is run with allocation tracing enabled.
+					"""
+				responses: {
+					"200": {
+						description: "The readings of the allocation group."
+					}
+					"404": {
+						description: "The allocation group has no readings."
+					}
+				}
+			}
+		}
+		"/metrics/allocations": {
+			GET: {
+				description: """
+					The bytes allocated by each component, in the
+					Prometheus text exposition format, or in the
+					OpenMetrics text format when the `Accept` header
+					asks for `application/openmetrics-text`. With
+					`--allocation-tracing-exemplars`, the OpenMetrics
+					gauges link to the trace ID recorded on the span of
+					each component. Only available when Vector is run
+					with allocation tracing enabled.
+					"""
+				responses: {
+					"200": {
+						description: "The metrics of every allocation group."
+					}
+				}
+			}
+		}
 		"/playground": {
 			GET: {
 				description: """
@@ -0,0 +1,28 @@
+package metadata
+
+remap: functions: get_allocation_group_bytes: {
+	category: "System"
+	description: """
+		Returns the number of bytes currently allocated by the component running the program.
+
+		This requires Vector to be started with allocation tracing enabled (`--allocation-tracing`),
+		and always returns `0` otherwise.
+		"""
+
+	arguments: []
+	internal_failure_reasons: []
+	return: types: ["integer"]
+
+	examples: [
+		{
+			title: "Tag events while the component uses more than 100MB of memory"
+			input: log: {}
+			source: #"""
+				if get_allocation_group_bytes() > 100_000_000 {
+					.memory_pressure = true
+				}
+				"""#
+			output: log: {}
+		},
+	]
+}